@@ -0,0 +1,55 @@
+//! Runs Binary Agreement among four nodes in the same process, using `MemoryTransport` to deliver
+//! the messages. In some rounds, the nodes use a Threshold Sign instance as their common coin.
+//!
+//! An application would replace `MemoryTransport` with its own implementation of `Transport`
+//! that sends the messages over the network.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use rand::Rng;
+
+use hbbft::binary_agreement::BinaryAgreement;
+use hbbft::{DistAlgorithm, MemoryTransport, NetworkInfo};
+
+const NUM_NODES: usize = 4;
+
+fn main() {
+    let mut rng = rand::thread_rng();
+    let netinfos = NetworkInfo::generate_map(0..NUM_NODES, &mut rng)
+        .expect("failed to generate `NetworkInfo` map");
+    let mut transports = MemoryTransport::network(0..NUM_NODES);
+    let mut nodes: BTreeMap<usize, BinaryAgreement<usize, &str>> = netinfos
+        .into_iter()
+        .map(|(id, netinfo)| {
+            let ba = BinaryAgreement::new(Arc::new(netinfo), "example")
+                .expect("failed to create Binary Agreement instance");
+            (id, ba)
+        })
+        .collect();
+    let mut decisions = BTreeMap::new();
+
+    // Each node proposes a random value, and handles all messages it has received so far.
+    for (id, ba) in &mut nodes {
+        let input = rng.gen::<bool>();
+        println!("Node {} proposes {}.", id, input);
+        let step = ba.propose(input).expect("failed to propose");
+        let transport = transports.get_mut(id).expect("missing transport");
+        let step = hbbft::run(ba, step, transport, &mut rng).expect("failed to run");
+        decisions.extend(step.output.into_iter().map(|b| (*id, b)));
+    }
+
+    // Let every node handle its messages in turn, until all of them have decided.
+    while !nodes.values().all(DistAlgorithm::terminated) {
+        for (id, ba) in &mut nodes {
+            let transport = transports.get_mut(id).expect("missing transport");
+            let step =
+                hbbft::run(ba, Default::default(), transport, &mut rng).expect("failed to run");
+            decisions.extend(step.output.into_iter().map(|b| (*id, b)));
+        }
+    }
+
+    for (id, decision) in &decisions {
+        println!("Node {} decided {}.", id, decision);
+    }
+}
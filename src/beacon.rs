@@ -0,0 +1,217 @@
+//! # Randomness beacon
+//!
+//! The signatures output by `ThresholdSign` are unpredictable until more than _f_ validators have
+//! contributed, and the same for every node, so they can serve as a public randomness beacon, e.g.
+//! one signature per epoch. To let third parties audit the sequence of values, `Beacon` links them
+//! in a hash chain: Each `BeaconRecord` contains the hash of the previous one, so no value can be
+//! replaced, removed or reordered without changing all hashes after it.
+//!
+//! `verify_chain` checks that a list of records is correctly linked. Whether each value is a valid
+//! signature must be checked separately, using the public master key and the signed document.
+
+use byteorder::{BigEndian, ByteOrder};
+use failure::Fail;
+use serde_derive::{Deserialize, Serialize};
+use tiny_keccak::sha3_256;
+
+use crate::crypto::Signature;
+
+/// A SHA-3 hash that links a record to its predecessor.
+pub type Digest = [u8; 32];
+
+/// The `prev_hash` of the first record in a beacon.
+pub const GENESIS_HASH: Digest = [0; 32];
+
+/// A beacon error.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Fail)]
+pub enum Error {
+    /// The record with the given index doesn't have the epoch after its predecessor's.
+    #[fail(display = "Beacon record {} has an unexpected epoch", _0)]
+    UnexpectedEpoch(usize),
+    /// The record with the given index doesn't refer to its predecessor's hash.
+    #[fail(display = "Beacon record {} does not link to its predecessor", _0)]
+    BrokenLink(usize),
+    /// The record with the given index has a hash that doesn't match its contents.
+    #[fail(display = "Beacon record {} has an invalid hash", _0)]
+    InvalidHash(usize),
+}
+
+/// A beacon result.
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+/// An epoch's beacon value, linked to the previous one.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BeaconRecord {
+    /// The epoch in which the value was produced.
+    pub epoch: u64,
+    /// The combined signature.
+    pub value: Signature,
+    /// The hash of the previous record, or `GENESIS_HASH` if this is the first one.
+    pub prev_hash: Digest,
+    /// The hash of the epoch, the value and `prev_hash`.
+    pub this_hash: Digest,
+}
+
+impl BeaconRecord {
+    /// Creates a record with the given contents, and computes its hash.
+    pub fn new(epoch: u64, value: Signature, prev_hash: Digest) -> Self {
+        let this_hash = BeaconRecord::compute_hash(epoch, &value, &prev_hash);
+        BeaconRecord {
+            epoch,
+            value,
+            prev_hash,
+            this_hash,
+        }
+    }
+
+    /// Returns the hash of the big-endian epoch, the signature bytes and the previous hash.
+    pub fn compute_hash(epoch: u64, value: &Signature, prev_hash: &Digest) -> Digest {
+        let mut bytes = vec![0; 8];
+        BigEndian::write_u64(&mut bytes, epoch);
+        bytes.extend_from_slice(&value.to_bytes());
+        bytes.extend_from_slice(prev_hash);
+        sha3_256(&bytes)
+    }
+
+    /// Returns `true` if `this_hash` matches the record's contents.
+    pub fn is_hash_valid(&self) -> bool {
+        self.this_hash == BeaconRecord::compute_hash(self.epoch, &self.value, &self.prev_hash)
+    }
+
+    /// Returns 32 uniformly distributed random bytes derived from the value.
+    pub fn randomness(&self) -> Digest {
+        sha3_256(&self.value.to_bytes())
+    }
+}
+
+/// A hash chain of beacon values, one for each epoch.
+#[derive(Clone, Debug, Default)]
+pub struct Beacon {
+    /// The records, in the order of their epochs.
+    records: Vec<BeaconRecord>,
+    /// The epoch of the next record.
+    next_epoch: u64,
+}
+
+impl Beacon {
+    /// Creates an empty beacon whose first record will have the given epoch.
+    pub fn new(first_epoch: u64) -> Self {
+        Beacon {
+            records: Vec::new(),
+            next_epoch: first_epoch,
+        }
+    }
+
+    /// Appends the signature of the next epoch, and returns the new record.
+    pub fn push(&mut self, value: Signature) -> &BeaconRecord {
+        let prev_hash = self.last_hash();
+        self.records
+            .push(BeaconRecord::new(self.next_epoch, value, prev_hash));
+        self.next_epoch += 1;
+        self.records.last().expect("record was just added")
+    }
+
+    /// Returns the records, in the order of their epochs.
+    pub fn records(&self) -> &[BeaconRecord] {
+        &self.records
+    }
+
+    /// Returns the hash of the last record, or `GENESIS_HASH` if there are none.
+    pub fn last_hash(&self) -> Digest {
+        self.records
+            .last()
+            .map_or(GENESIS_HASH, |record| record.this_hash)
+    }
+}
+
+/// Verifies that the records have consecutive epochs, that their hashes are correct, and that each
+/// one links to its predecessor. The first record must link to `prev_hash`, which is
+/// `GENESIS_HASH` if the records start at the beginning of the beacon.
+///
+/// Returns an error containing the index of the first invalid record.
+pub fn verify_chain(records: &[BeaconRecord], prev_hash: &Digest) -> Result<()> {
+    let mut prev_hash = prev_hash;
+    let mut next_epoch = records.first().map(|record| record.epoch);
+    for (index, record) in records.iter().enumerate() {
+        if next_epoch != Some(record.epoch) {
+            return Err(Error::UnexpectedEpoch(index));
+        }
+        if record.prev_hash != *prev_hash {
+            return Err(Error::BrokenLink(index));
+        }
+        if !record.is_hash_valid() {
+            return Err(Error::InvalidHash(index));
+        }
+        prev_hash = &record.this_hash;
+        next_epoch = record.epoch.checked_add(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_chain, Beacon, BeaconRecord, Error, GENESIS_HASH};
+    use crate::crypto::SecretKey;
+
+    /// Returns a beacon with five records, starting at epoch 10.
+    fn new_beacon() -> Beacon {
+        let mut rng = rand::thread_rng();
+        let sk: SecretKey = rand::Rng::gen(&mut rng);
+        let mut beacon = Beacon::new(10);
+        for epoch in 10..15u64 {
+            let record = beacon.push(sk.sign(format!("epoch {}", epoch)));
+            assert_eq!(epoch, record.epoch);
+        }
+        beacon
+    }
+
+    #[test]
+    fn test_chain_integrity() {
+        let beacon = new_beacon();
+        let records = beacon.records();
+        assert_eq!(Ok(()), verify_chain(records, &GENESIS_HASH));
+        assert_eq!(GENESIS_HASH, records[0].prev_hash);
+        assert_eq!(records[4].this_hash, beacon.last_hash());
+        // A suffix of the chain can be verified given the preceding hash.
+        assert_eq!(Ok(()), verify_chain(&records[2..], &records[1].this_hash));
+        assert_eq!(
+            Err(Error::BrokenLink(0)),
+            verify_chain(&records[2..], &GENESIS_HASH)
+        );
+    }
+
+    #[test]
+    fn test_tampered_chain() {
+        let beacon = new_beacon();
+        let records = beacon.records();
+
+        // Replacing a value invalidates that record's hash.
+        let mut tampered = records.to_vec();
+        tampered[2].value = records[3].value.clone();
+        assert_eq!(
+            Err(Error::InvalidHash(2)),
+            verify_chain(&tampered, &GENESIS_HASH)
+        );
+
+        // Recomputing its hash breaks the link to the next record.
+        tampered[2] = BeaconRecord::new(12, records[3].value.clone(), records[1].this_hash);
+        assert_eq!(
+            Err(Error::BrokenLink(3)),
+            verify_chain(&tampered, &GENESIS_HASH)
+        );
+
+        // Removing or reordering records is detected, too.
+        let mut removed = records.to_vec();
+        removed.remove(1);
+        assert_eq!(
+            Err(Error::UnexpectedEpoch(1)),
+            verify_chain(&removed, &GENESIS_HASH)
+        );
+        let mut swapped = records.to_vec();
+        swapped.swap(3, 4);
+        assert_eq!(
+            Err(Error::UnexpectedEpoch(3)),
+            verify_chain(&swapped, &GENESIS_HASH)
+        );
+    }
+}
@@ -2,8 +2,9 @@ use std::collections::BTreeMap;
 use std::sync::Arc;
 use std::{fmt, result};
 
-use crate::crypto::SignatureShare;
+use crate::crypto::{self, Signature};
 use bincode;
+use derivative::Derivative;
 use log::debug;
 use rand::Rng;
 
@@ -13,7 +14,8 @@ use super::sbv_broadcast::{self, Message as SbvMessage, SbvBroadcast};
 use super::{Error, Message, MessageContent, Result, Step};
 use crate::fault_log::{Fault, FaultKind};
 use crate::threshold_sign::{self, Message as TsMessage, ThresholdSign};
-use crate::{DistAlgorithm, NetworkInfo, NodeIdT, SessionIdT, Target};
+use crate::util::RoundCost;
+use crate::{Diagnose, DiagnosticTree, DistAlgorithm, NetworkInfo, NodeIdT, SessionIdT, Target};
 
 /// The state of the current epoch's coin. In some epochs this is fixed, in others it starts
 /// with in `InProgress`.
@@ -52,8 +54,8 @@ struct ReceivedMessages {
     conf: Option<BoolSet>,
     /// Received `Term` message.
     term: Option<bool>,
-    /// Received `Coin` message, namely its `SignatureShare`.
-    coin: Option<SignatureShare>,
+    /// Received `Coin` message.
+    coin: Option<TsMessage>,
 }
 
 impl ReceivedMessages {
@@ -99,7 +101,7 @@ impl ReceivedMessages {
             }
             MessageContent::Coin(msg) => {
                 if self.coin.is_none() {
-                    self.coin = Some(msg.0);
+                    self.coin = Some(*msg);
                 } else {
                     return Some(FaultKind::AgreementEpoch);
                 }
@@ -130,15 +132,19 @@ impl ReceivedMessages {
         if let Some(b) = term {
             messages.push(MessageContent::Term(b));
         }
-        if let Some(ss) = coin {
-            messages.push(MessageContent::Coin(Box::new(TsMessage(ss))));
+        if let Some(msg) = coin {
+            messages.push(MessageContent::Coin(Box::new(msg)));
         }
         messages
     }
 }
 
+/// A function that is called once with the decided value.
+pub type DecisionCallback = Box<dyn FnOnce(bool) + Send + Sync>;
+
 /// Binary Agreement instance
-#[derive(Debug)]
+#[derive(Derivative)]
+#[derivative(Debug)]
 pub struct BinaryAgreement<N, S> {
     /// Shared network information.
     netinfo: Arc<NetworkInfo<N>>,
@@ -146,6 +152,8 @@ pub struct BinaryAgreement<N, S> {
     session_id: S,
     /// Binary Agreement algorithm epoch.
     epoch: u64,
+    /// The last epoch in which the instance made progress within the epoch.
+    last_progress_epoch: u64,
     /// Maximum number of future epochs for which incoming messages are accepted.
     max_future_epochs: u64,
     /// This epoch's Synchronized Binary Value Broadcast instance.
@@ -169,6 +177,14 @@ pub struct BinaryAgreement<N, S> {
     conf_values: Option<BoolSet>,
     /// The state of this epoch's coin.
     coin_state: CoinState<N>,
+    /// Whether the coin is replaced by a value derived from the epoch number. _Insecure_: see
+    /// `new_with_deterministic_coin`.
+    deterministic_coin: bool,
+    /// The function that maps the threshold signature to the coin value.
+    coin_projection: fn(&Signature) -> bool,
+    /// The function to be called with the decision, if it hasn't been called yet.
+    #[derivative(Debug = "ignore")]
+    decision_callback: Option<DecisionCallback>,
 }
 
 impl<N: NodeIdT, S: SessionIdT> DistAlgorithm for BinaryAgreement<N, S> {
@@ -202,6 +218,29 @@ impl<N: NodeIdT, S: SessionIdT> DistAlgorithm for BinaryAgreement<N, S> {
     }
 }
 
+impl<N: NodeIdT, S: SessionIdT> Diagnose for BinaryAgreement<N, S> {
+    /// Returns the epoch, estimate, decision and coin state. If the coin is a threshold signature
+    /// that is still in progress, its diagnostic is the only child.
+    fn diagnose(&self) -> DiagnosticTree {
+        let fmt_value = |value: Option<bool>| value.map_or("none".to_string(), |b| b.to_string());
+        let coin = match self.coin_state {
+            CoinState::Decided(value) => value.to_string(),
+            CoinState::InProgress(_) => "in progress".to_string(),
+        };
+        let tree = DiagnosticTree::new(format!(
+            "BinaryAgreement epoch {}: estimate {}, decision {}, coin {}",
+            self.epoch,
+            fmt_value(self.estimated),
+            fmt_value(self.decision),
+            coin
+        ));
+        match self.coin_state {
+            CoinState::InProgress(ref ts) => tree.with_child(ts.diagnose()),
+            CoinState::Decided(_) => tree,
+        }
+    }
+}
+
 impl<N: NodeIdT, S: SessionIdT> BinaryAgreement<N, S> {
     /// Creates a new `BinaryAgreement` instance with the given session identifier, to prevent
     /// replaying messages in other instances.
@@ -210,6 +249,7 @@ impl<N: NodeIdT, S: SessionIdT> BinaryAgreement<N, S> {
             netinfo: netinfo.clone(),
             session_id,
             epoch: 0,
+            last_progress_epoch: 0,
             max_future_epochs: 1000,
             sbv_broadcast: SbvBroadcast::new(netinfo),
             received_conf: BTreeMap::new(),
@@ -219,9 +259,85 @@ impl<N: NodeIdT, S: SessionIdT> BinaryAgreement<N, S> {
             incoming_queue: BTreeMap::new(),
             conf_values: None,
             coin_state: CoinState::Decided(true),
+            deterministic_coin: false,
+            coin_projection: Signature::parity,
+            decision_callback: None,
         })
     }
 
+    /// Creates a new `BinaryAgreement` instance that uses `coin_projection` instead of the
+    /// signature's parity to turn the threshold signature into a coin value.
+    ///
+    /// The projection is a network-wide parameter: All nodes must use the same function, or they
+    /// will disagree on the coin value. It should depend on the signature's bits in a way that
+    /// keeps both values equally likely, e.g. by returning a particular bit of the signature or of
+    /// its hash.
+    pub fn new_with_coin_projection(
+        netinfo: Arc<NetworkInfo<N>>,
+        session_id: S,
+        coin_projection: fn(&Signature) -> bool,
+    ) -> Result<Self> {
+        let mut ba = BinaryAgreement::new(netinfo, session_id)?;
+        ba.coin_projection = coin_projection;
+        Ok(ba)
+    }
+
+    /// Creates a new `BinaryAgreement` instance that never flips the threshold signature coin,
+    /// and instead uses the epoch's parity wherever a coin value is required.
+    ///
+    /// **This breaks the security guarantees of Binary Agreement!** An adversary who knows the
+    /// coin values in advance and controls the message scheduling can prevent the algorithm from
+    /// ever terminating. Never use this outside of tests: It only exists to make the sequence of
+    /// epochs reproducible, independently of the key shares, e.g. for deterministic CI runs. All
+    /// nodes in the network must use the same kind of coin.
+    pub fn new_with_deterministic_coin(
+        netinfo: Arc<NetworkInfo<N>>,
+        session_id: S,
+    ) -> Result<Self> {
+        let mut ba = BinaryAgreement::new(netinfo, session_id)?;
+        ba.deterministic_coin = true;
+        Ok(ba)
+    }
+
+    /// Returns the current Binary Agreement epoch.
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Returns the last epoch in which the instance made progress: Synchronized Binary Value
+    /// Broadcast produced output, the coin produced a value, or the instance decided.
+    ///
+    /// Starting a new epoch doesn't count as progress by itself. So if this is less than `epoch()`,
+    /// the instance is waiting for messages that it hasn't received in the current epoch yet. If
+    /// this doesn't change for a long time, the instance is likely stalled, e.g. because too many
+    /// nodes are unreachable.
+    pub fn last_progress_epoch(&self) -> u64 {
+        self.last_progress_epoch
+    }
+
+    /// Returns the decided value, or `None` if the instance hasn't decided yet.
+    ///
+    /// Unlike the step's output, this can be queried at any time after the decision.
+    pub fn decision(&self) -> Option<bool> {
+        self.decision
+    }
+
+    /// Registers a function that is called exactly once with the decided value: immediately if
+    /// the instance has already decided, otherwise as soon as it decides. This replaces any
+    /// function registered earlier that hasn't been called yet.
+    ///
+    /// This lets asynchronous code wait for the decision without polling, e.g. by completing a
+    /// one-shot channel in the callback.
+    pub fn on_decision<F>(&mut self, callback: F)
+    where
+        F: FnOnce(bool) + Send + Sync + 'static,
+    {
+        match self.decision {
+            Some(b) => callback(b),
+            None => self.decision_callback = Some(Box::new(callback)),
+        }
+    }
+
     /// Proposes a boolean value for Binary Agreement.
     ///
     /// If more than two thirds of validators propose the same value, that will eventually be
@@ -246,7 +362,7 @@ impl<N: NodeIdT, S: SessionIdT> BinaryAgreement<N, S> {
         if self.decision.is_some() || (epoch < self.epoch && content.can_expire()) {
             // Message is obsolete: We are already in a later epoch or terminated.
             Ok(Step::default())
-        } else if epoch > self.epoch + self.max_future_epochs {
+        } else if epoch > self.epoch.saturating_add(self.max_future_epochs) {
             Ok(Fault::new(sender_id.clone(), FaultKind::AgreementEpoch).into())
         } else if epoch > self.epoch {
             // Message is for a later epoch. We can't handle that yet.
@@ -302,6 +418,9 @@ impl<N: NodeIdT, S: SessionIdT> BinaryAgreement<N, S> {
         let output = step.extend_with(sbvb_step, |msg| {
             MessageContent::SbvBroadcast(msg).with_epoch(self.epoch)
         });
+        if !output.is_empty() {
+            self.last_progress_epoch = self.epoch;
+        }
         if self.conf_values.is_some() {
             return Ok(step); // The `Conf` round has already started.
         }
@@ -336,7 +455,7 @@ impl<N: NodeIdT, S: SessionIdT> BinaryAgreement<N, S> {
         // Check for the expedite termination condition.
         if self.decision.is_some() {
             Ok(Step::default())
-        } else if self.received_term[b].len() > self.netinfo.num_faulty() {
+        } else if self.netinfo.has_weak_quorum(self.received_term[b].len()) {
             Ok(self.decide(b))
         } else {
             // Otherwise handle the `Term` as a `BVal`, `Aux` and `Conf`.
@@ -395,8 +514,9 @@ impl<N: NodeIdT, S: SessionIdT> BinaryAgreement<N, S> {
         let to_msg = |c_msg| MessageContent::Coin(Box::new(c_msg)).with_epoch(epoch);
         let ts_output = step.extend_with(ts_step, to_msg);
         if let Some(sig) = ts_output.into_iter().next() {
-            // Take the parity of the signature as the coin value.
-            self.coin_state = sig.parity().into();
+            self.last_progress_epoch = self.epoch;
+            // Project the signature to the coin value; by default this is its parity.
+            self.coin_state = (self.coin_projection)(&sig).into();
             step.extend(self.try_update_epoch()?);
         }
         Ok(step)
@@ -435,8 +555,9 @@ impl<N: NodeIdT, S: SessionIdT> BinaryAgreement<N, S> {
         match self.epoch % 3 {
             0 => Ok(CoinState::Decided(true)),
             1 => Ok(CoinState::Decided(false)),
+            _ if self.deterministic_coin => Ok(CoinState::Decided(self.epoch % 2 == 0)),
             _ => {
-                let coin_id = bincode::serialize(&(&self.session_id, self.epoch))?;
+                let coin_id = coin_nonce(&self.session_id, self.epoch)?;
                 let mut ts = ThresholdSign::new(self.netinfo.clone());
                 ts.set_document(coin_id).map_err(Error::InvokeCoin)?;
                 Ok(CoinState::InProgress(Box::new(ts)))
@@ -454,9 +575,13 @@ impl<N: NodeIdT, S: SessionIdT> BinaryAgreement<N, S> {
         step.output.push(b);
         // Latch the decided state.
         self.decision = Some(b);
+        self.last_progress_epoch = self.epoch;
         debug!("{}: decision: {}", self, b);
+        if let Some(callback) = self.decision_callback.take() {
+            callback(b);
+        }
         if self.netinfo.is_validator() {
-            let msg = MessageContent::Term(b).with_epoch(self.epoch + 1);
+            let msg = MessageContent::Term(b).with_epoch(self.epoch.saturating_add(1));
             step.messages.push(Target::All.message(msg));
         }
         step
@@ -490,7 +615,7 @@ impl<N: NodeIdT, S: SessionIdT> BinaryAgreement<N, S> {
             self.received_conf.insert(id.clone(), BoolSet::from(v));
         }
         self.conf_values = None;
-        self.epoch += 1;
+        self.epoch = self.epoch.checked_add(1).ok_or(Error::EpochOverflow)?;
         self.coin_state = self.coin_state()?;
         debug!(
             "{}: epoch started, {} terminated",
@@ -518,6 +643,47 @@ impl<N: NodeIdT, S: SessionIdT> BinaryAgreement<N, S> {
     }
 }
 
+/// Returns an estimate of the traffic in an instance among `num_nodes` validators that decides in
+/// round `num_rounds - 1`, assuming every node sends only one `BVal` per round.
+///
+/// In each round, every node multicasts a `BVal` and an `Aux` message. Every third round uses the
+/// threshold signature coin, and in these every node additionally multicasts a `Conf` message and
+/// a coin share. In the end, every node multicasts a `Term` message. If the nodes disagree in a
+/// round, some send a second `BVal`, which is not included.
+pub fn estimated_messages(num_nodes: usize, num_rounds: u64) -> RoundCost {
+    // Each message contains the epoch and the type of content, followed by the content itself.
+    let header = 8 + 4;
+    let sbv_size = header + 4 + 1;
+    let conf_size = header + 1;
+    // The coin share is preceded by its wire version and round.
+    let coin_size = header + 1 + 8 + crypto::SIG_SIZE;
+    let term_size = header + 1;
+    let mut cost = RoundCost::multicasts(num_nodes, 1, term_size);
+    for round in 0..num_rounds {
+        cost = cost + RoundCost::multicasts(num_nodes, 2, sbv_size);
+        if round % 3 == 2 {
+            cost = cost
+                + RoundCost::multicasts(num_nodes, 1, conf_size)
+                + RoundCost::multicasts(num_nodes, 1, coin_size);
+        }
+    }
+    cost
+}
+
+/// The domain separation tag at the start of every coin nonce.
+const COIN_NONCE_TAG: &[u8] = b"hbbft binary agreement coin";
+
+/// Returns the document that is signed by the threshold signature coin in the given epoch of the
+/// instance with the given session ID.
+///
+/// This is the bincode serialization of `COIN_NONCE_TAG`, the session ID and the epoch. All three
+/// parts are either of fixed length or length-prefixed, so for a given session ID type, distinct
+/// pairs of session ID and epoch always produce distinct nonces, and the tag keeps them distinct
+/// from documents signed for other purposes.
+pub fn coin_nonce<S: SessionIdT>(session_id: &S, epoch: u64) -> Result<Vec<u8>> {
+    Ok(bincode::serialize(&(COIN_NONCE_TAG, session_id, epoch))?)
+}
+
 impl<N: NodeIdT, S: SessionIdT> fmt::Display for BinaryAgreement<N, S> {
     fn fmt(&self, f: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
         write!(
@@ -534,3 +700,26 @@ impl<N: NodeIdT, S: SessionIdT> fmt::Display for BinaryAgreement<N, S> {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::BinaryAgreement;
+    use crate::binary_agreement::Error;
+    use crate::testing;
+
+    #[test]
+    fn test_binary_agreement_epoch_overflow() {
+        let (_, netinfos) = testing::setup(1, 0);
+        let mut ba = BinaryAgreement::new(Arc::new(netinfos[0].clone()), 0).expect("agreement");
+        // In the last epoch, starting the next one fails instead of wrapping around to epoch 0.
+        ba.epoch = u64::max_value();
+        match ba.update_epoch(false) {
+            Err(Error::EpochOverflow) => (),
+            result => panic!("expected an epoch overflow error, got {:?}", result),
+        }
+        assert_eq!(u64::max_value(), ba.epoch());
+        assert_eq!(None, ba.decision());
+    }
+}
@@ -62,6 +62,15 @@
 //!
 //! * After _f + 1_ nodes have sent us their coin shares, we receive the coin output and assign it
 //! to `s`.
+//!
+//! ### Coin nonces
+//!
+//! The coin is a threshold signature of a nonce, which is computed by `coin_nonce` from the
+//! session ID and the epoch. If two instances signed the same nonce, an adversary could replay
+//! the coin shares from one of them in the other, and learn the coin value early. So every
+//! instance in the network must have a different session ID. In `Subset`, the session ID contains
+//! the `Subset` instance's own session ID and the proposer's index, and in Honey Badger the former
+//! consists of the Honey Badger session ID and the epoch.
 
 mod binary_agreement;
 mod bool_multimap;
@@ -78,7 +87,9 @@ use serde_derive::{Deserialize, Serialize};
 use self::bool_set::BoolSet;
 use crate::threshold_sign;
 
-pub use self::binary_agreement::BinaryAgreement;
+pub use self::binary_agreement::{
+    coin_nonce, estimated_messages, BinaryAgreement, DecisionCallback,
+};
 pub use self::sbv_broadcast::Message as SbvMessage;
 
 /// A `BinaryAgreement` error.
@@ -86,14 +97,17 @@ pub use self::sbv_broadcast::Message as SbvMessage;
 pub enum Error {
     /// Error handling a `ThresholdSign` message.
     #[fail(display = "Error handling ThresholdSign message: {}", _0)]
-    HandleThresholdSign(threshold_sign::Error),
+    HandleThresholdSign(#[cause] threshold_sign::Error),
     /// Error invoking the common coin.
     #[fail(display = "Error invoking the common coin: {}", _0)]
-    InvokeCoin(threshold_sign::Error),
+    InvokeCoin(#[cause] threshold_sign::Error),
     // String because `io` and `bincode` errors lack `Eq` and `Clone`.
     /// Error serializing the session ID for the common coin.
     #[fail(display = "Error serializing session ID for coin: {}", _0)]
     Serialize(String),
+    /// The epoch number would overflow.
+    #[fail(display = "Epoch number overflow")]
+    EpochOverflow,
 }
 
 impl From<bincode::Error> for Error {
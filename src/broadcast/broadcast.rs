@@ -1,21 +1,19 @@
 use std::collections::BTreeMap;
 use std::sync::Arc;
-use std::{fmt, result};
+use std::{fmt, mem, result};
 
 use byteorder::{BigEndian, ByteOrder};
 use hex_fmt::{HexFmt, HexList};
 use log::{debug, warn};
 use rand::Rng;
-use reed_solomon_erasure as rse;
-use reed_solomon_erasure::ReedSolomon;
 
+use super::coding::{Coding, ErasureCoder};
 use super::merkle::{Digest, MerkleTree, Proof};
 use super::message::HexProof;
 use super::{Error, Message, Result};
 use crate::fault_log::{Fault, FaultKind};
-use crate::{DistAlgorithm, NetworkInfo, NodeIdT, Target};
-
-type RseResult<T> = result::Result<T, rse::Error>;
+use crate::util::RoundCost;
+use crate::{Diagnose, DiagnosticTree, DistAlgorithm, NetworkInfo, NodeIdT, Target};
 
 /// Broadcast algorithm instance.
 #[derive(Debug)]
@@ -24,20 +22,83 @@ pub struct Broadcast<N> {
     netinfo: Arc<NetworkInfo<N>>,
     /// The ID of the sending node.
     proposer_id: N,
-    /// The Reed-Solomon erasure coding configuration.
-    coding: Coding,
+    /// The erasure coding scheme, by default Reed-Solomon.
+    coding: Box<dyn ErasureCoder>,
+    /// If we are the proposer: the maximum length of a value that is sent to every validator in
+    /// full instead of being erasure-coded, or `0` if every value is coded.
+    replication_threshold: usize,
     /// If we are the proposer: whether we have already sent the `Value` messages with the shards.
     value_sent: bool,
     /// Whether we have already multicast `Echo`.
     echo_sent: bool,
     /// Whether we have already multicast `Ready`.
     ready_sent: bool,
-    /// Whether we have already output a value.
-    decided: bool,
-    /// The proofs we have received via `Echo` messages, by sender ID.
-    echos: BTreeMap<N, Proof<Vec<u8>>>,
+    /// The value we have output, if any.
+    output: Option<Vec<u8>>,
+    /// The root hash of the Merkle tree of the value we have output, if any.
+    output_root_hash: Option<Digest>,
+    /// Whether the instance was cancelled, and all further messages are ignored.
+    cancelled: bool,
+    /// Whether we have already reported the proposer for sending different trees.
+    equivocation_reported: bool,
+    /// The shards we have received via `Echo` messages, by sender ID. The proofs are dropped
+    /// once they have been verified.
+    echos: BTreeMap<N, EchoShard>,
     /// The root hashes we received via `Ready` messages, by sender ID.
     readys: BTreeMap<N, Vec<u8>>,
+    /// Whether the tree with the given root hash is a replicated value, for every root hash we
+    /// have checked.
+    replicated: BTreeMap<Digest, bool>,
+    /// The progress events that haven't been taken yet, or `None` if they are not tracked.
+    progress: Option<Vec<Progress>>,
+}
+
+/// A milestone in a `Broadcast` instance's lifecycle.
+///
+/// If tracking is enabled with `Broadcast::set_track_progress`, each of these is recorded at most
+/// once, in the order in which they happen, and can be retrieved with `Broadcast::take_progress`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Progress {
+    /// We received a valid `Value` message from the proposer.
+    ValueReceived,
+    /// We multicast our `Echo` message.
+    EchoSent,
+    /// We multicast our `Ready` message.
+    ReadySent,
+    /// We decoded the value and output it.
+    Decoded,
+}
+
+/// A shard received in an `Echo` message, whose proof has been verified.
+///
+/// Only the root hash and the leaf value are kept: The branch is not needed anymore once it has
+/// been checked, and can be recomputed from the reconstructed tree. For _N = 256_ the branch
+/// consists of eight 32-byte digests, so this saves 256 bytes of digests, plus the `Vec` header
+/// and the index, i.e. 288 bytes on a 64-bit platform for each `Echo`. That is about 72 KiB per
+/// `Broadcast` instance, or 18 MiB per Honey Badger epoch, with one instance per validator.
+#[derive(Debug)]
+struct EchoShard {
+    /// The root hash of the Merkle tree the shard belongs to.
+    root_hash: Digest,
+    /// The shard itself, i.e. the leaf value.
+    value: Vec<u8>,
+}
+
+impl EchoShard {
+    /// Returns `true` if the proof has the same root hash and leaf value. Since the index was
+    /// verified, too, the proofs are then equal.
+    fn matches(&self, p: &Proof<Vec<u8>>) -> bool {
+        self.root_hash == *p.root_hash() && self.value == *p.value()
+    }
+}
+
+impl From<Proof<Vec<u8>>> for EchoShard {
+    fn from(p: Proof<Vec<u8>>) -> Self {
+        EchoShard {
+            root_hash: *p.root_hash(),
+            value: p.into_value(),
+        }
+    }
 }
 
 /// A `Broadcast` step, containing at most one output.
@@ -64,7 +125,7 @@ impl<N: NodeIdT> DistAlgorithm for Broadcast<N> {
     }
 
     fn terminated(&self) -> bool {
-        self.decided
+        self.output.is_some() || self.cancelled
     }
 
     fn our_id(&self) -> &N {
@@ -72,6 +133,31 @@ impl<N: NodeIdT> DistAlgorithm for Broadcast<N> {
     }
 }
 
+impl<N: NodeIdT> Diagnose for Broadcast<N> {
+    /// Returns a leaf with the number of `Echo` and `Ready` messages, and the furthest step the
+    /// instance has reached.
+    fn diagnose(&self) -> DiagnosticTree {
+        let state = if self.output.is_some() {
+            "decoded"
+        } else if self.cancelled {
+            "cancelled"
+        } else if self.ready_sent {
+            "ready sent"
+        } else if self.echo_sent {
+            "echo sent"
+        } else {
+            "waiting for value"
+        };
+        DiagnosticTree::new(format!(
+            "Broadcast from {:?}: {} echos, {} readys, {}",
+            self.proposer_id,
+            self.echos.len(),
+            self.readys.len(),
+            state
+        ))
+    }
+}
+
 impl<N: NodeIdT> Broadcast<N> {
     /// Creates a new broadcast instance to be used by node `our_id` which expects a value proposal
     /// from node `proposer_id`.
@@ -80,17 +166,43 @@ impl<N: NodeIdT> Broadcast<N> {
         let data_shard_num = netinfo.num_nodes() - parity_shard_num;
         let coding =
             Coding::new(data_shard_num, parity_shard_num).map_err(|_| Error::InvalidNodeCount)?;
+        Broadcast::new_with_coder(netinfo, proposer_id, coding)
+    }
+
+    /// Creates a new broadcast instance that uses the given erasure coding scheme instead of the
+    /// default Reed-Solomon coding.
+    ///
+    /// The coder's total number of shards must equal the number of validators. A value can only
+    /// be decoded once `data_shard_count` `Echo` messages with the same root hash have arrived, so
+    /// with more than _N - 2 f_ data shards, the faulty nodes can prevent termination.
+    pub fn new_with_coder<C: ErasureCoder + 'static>(
+        netinfo: Arc<NetworkInfo<N>>,
+        proposer_id: N,
+        coder: C,
+    ) -> Result<Self> {
+        let data_shard_num = coder.data_shard_count();
+        if data_shard_num == 0 || data_shard_num + coder.parity_shard_count() != netinfo.num_nodes()
+        {
+            return Err(Error::InvalidShardCount);
+        }
+        let coding = Box::new(coder);
 
         Ok(Broadcast {
             netinfo,
             proposer_id,
             coding,
+            replication_threshold: 0,
             value_sent: false,
             echo_sent: false,
             ready_sent: false,
-            decided: false,
+            output: None,
+            output_root_hash: None,
+            cancelled: false,
+            equivocation_reported: false,
             echos: BTreeMap::new(),
             readys: BTreeMap::new(),
+            replicated: BTreeMap::new(),
+            progress: None,
         })
     }
 
@@ -102,6 +214,9 @@ impl<N: NodeIdT> Broadcast<N> {
         if self.value_sent {
             return Err(Error::MultipleInputs);
         }
+        if self.cancelled {
+            return Ok(Step::default());
+        }
         self.value_sent = true;
         // Split the value into chunks/shards, encode them with erasure codes.
         // Assemble a Merkle tree from data and parity shards. Take all proofs
@@ -111,6 +226,47 @@ impl<N: NodeIdT> Broadcast<N> {
         Ok(step.join(self.handle_value(our_id, proof)?))
     }
 
+    /// Sets the maximum length of a value that the proposer sends to every validator in full,
+    /// instead of splitting it into erasure-coded shards. This only has an effect in the proposer,
+    /// since the other nodes detect from the shards whether the value was replicated.
+    ///
+    /// The default is `0`, i.e. every value is erasure-coded, even the empty one. A good threshold
+    /// is the number _N - 2 f_ of data shards: Any shorter value would be coded into shards of a
+    /// single byte, so sending it in full costs at most a few bytes more per message, and saves
+    /// the encoding and decoding.
+    ///
+    /// Nodes running a version of this crate that doesn't support replicated values fail to decode
+    /// them, and report the proposer as faulty. Only set a threshold once all validators have been
+    /// upgraded.
+    pub fn set_replication_threshold(&mut self, max_len: usize) {
+        self.replication_threshold = max_len;
+    }
+
+    /// Returns the maximum length of a value that the proposer sends to every validator in full,
+    /// or `0` if every value is erasure-coded.
+    pub fn replication_threshold(&self) -> usize {
+        self.replication_threshold
+    }
+
+    /// Enables or disables the recording of `Progress` events. Disabling it drops the events that
+    /// haven't been taken yet.
+    ///
+    /// This is useful for monitoring and debugging, e.g. to find out how far a broadcast got
+    /// before it stalled, without having to parse the logs.
+    pub fn set_track_progress(&mut self, track: bool) {
+        if !track {
+            self.progress = None;
+        } else if self.progress.is_none() {
+            self.progress = Some(Vec::new());
+        }
+    }
+
+    /// Returns the `Progress` events recorded since the last call, in the order in which they
+    /// happened. This is always empty unless tracking was enabled with `set_track_progress`.
+    pub fn take_progress(&mut self) -> Vec<Progress> {
+        self.progress.as_mut().map_or_else(Vec::new, mem::take)
+    }
+
     /// Handles a message received from `sender_id`.
     ///
     /// This must be called with every message we receive from another node.
@@ -118,6 +274,9 @@ impl<N: NodeIdT> Broadcast<N> {
         if !self.netinfo.is_node_validator(sender_id) {
             return Err(Error::UnknownSender);
         }
+        if self.cancelled {
+            return Ok(Step::default());
+        }
         match message {
             Message::Value(p) => self.handle_value(sender_id, p),
             Message::Echo(p) => self.handle_echo(sender_id, p),
@@ -125,51 +284,109 @@ impl<N: NodeIdT> Broadcast<N> {
         }
     }
 
+    /// Cancels the broadcast: The instance terminates without output, the received `Echo` and
+    /// `Ready` messages are dropped, and all further messages are ignored.
+    ///
+    /// This is useful if the value is not needed anymore, e.g. because it has been decided that it
+    /// won't be included in the subset.
+    pub fn cancel(&mut self) {
+        debug!("{}: Cancelled.", self);
+        self.cancelled = true;
+        self.echos = BTreeMap::new();
+        self.readys = BTreeMap::new();
+    }
+
+    /// Returns `true` if the broadcast was cancelled.
+    pub fn cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    /// Returns the value, if it has already been reconstructed and output.
+    ///
+    /// Unlike the step's output, this doesn't consume the value, so it can be retrieved again,
+    /// e.g. for debugging or to serve it to nodes that join late.
+    pub fn output_value(&self) -> Option<&[u8]> {
+        self.output.as_ref().map(Vec::as_slice)
+    }
+
+    /// Returns the shard with the given index of the value we have output, together with a proof
+    /// that it is a leaf of the Merkle tree with the value's root hash. Returns `None` if there is
+    /// no output yet, or if the index is out of range.
+    ///
+    /// This allows proving to a third party, e.g. a light client that knows the root hash, that a
+    /// shard is part of the value, without sending the whole value. The shards that weren't
+    /// received via `Echo` are interpolated, so this requires an erasure code reconstruction.
+    pub fn opening(&self, index: usize) -> Option<(Vec<u8>, Proof<Vec<u8>>)> {
+        let root_hash = self.output_root_hash?;
+        let shards = if let Some(leaf) = self.replicated_leaf(&root_hash) {
+            vec![leaf.clone(); self.netinfo.num_nodes()]
+        } else {
+            let mut leaf_values = self.leaf_values(&root_hash);
+            self.coding.reconstruct(&mut leaf_values).ok()?;
+            leaf_values
+                .into_iter()
+                .map(|leaf| leaf.map(Vec::from))
+                .collect::<Option<Vec<Vec<u8>>>>()?
+        };
+        let proof = MerkleTree::from_vec(shards).proof(index)?;
+        Some((proof.value().clone(), proof))
+    }
+
+    /// Returns the IDs of the validators whose `Ready` message we haven't received yet, or an
+    /// empty list if the instance has terminated.
+    pub fn missing_contributors(&self) -> Vec<N> {
+        if self.terminated() {
+            return Vec::new();
+        }
+        self.netinfo
+            .all_ids()
+            .filter(|id| !self.readys.contains_key(id))
+            .cloned()
+            .collect()
+    }
+
     /// Returns the proposer's node ID.
     pub fn proposer_id(&self) -> &N {
         &self.proposer_id
     }
 
+    /// Returns the index of the shard assigned to the given validator, or `None` if it is not a
+    /// validator.
+    ///
+    /// This is the validator's position among the sorted validator IDs, i.e. its
+    /// `NetworkInfo::node_index`, so the assignment is the same on all nodes. The first
+    /// `N - 2 f` shards are data shards, the remaining `2 f` are parity shards. The proposer sends
+    /// each validator the proof for its shard in a `Value` message, and the validator's `Echo` must
+    /// contain a proof with the same index.
+    pub fn shard_index_of(&self, id: &N) -> Option<usize> {
+        self.netinfo.node_index(id)
+    }
+
     /// Breaks the input value into shards of equal length and encodes them --
     /// and some extra parity shards -- with a Reed-Solomon erasure coding
     /// scheme. The returned value contains the shard assigned to this
     /// node. That shard doesn't need to be sent anywhere. It gets recorded in
     /// the broadcast instance.
+    ///
+    /// If the replication threshold is set and the value is not longer, every shard is the full
+    /// value instead.
     fn send_shards(&mut self, mut value: Vec<u8>) -> Result<(Proof<Vec<u8>>, Step<N>)> {
-        let data_shard_num = self.coding.data_shard_count();
-        let parity_shard_num = self.coding.parity_shard_count();
+        let replicate = self.replication_threshold > 0 && value.len() <= self.replication_threshold;
 
         // Insert the length of `v` so it can be decoded without the padding.
         let payload_len = value.len() as u32;
         value.splice(0..0, 0..4); // Insert four bytes at the beginning.
         BigEndian::write_u32(&mut value[..4], payload_len); // Write the size.
-        let value_len = value.len(); // This is at least 4 now, due to the payload length.
-
-        // Size of a Merkle tree leaf value: the value size divided by the number of data shards,
-        // and rounded up, so that the full value always fits in the data shards. Always at least 1.
-        let shard_len = (value_len + data_shard_num - 1) / data_shard_num;
-        // Pad the last data shard with zeros. Fill the parity shards with zeros.
-        value.resize(shard_len * (data_shard_num + parity_shard_num), 0);
 
-        // Divide the vector into chunks/shards.
-        let shards_iter = value.chunks_mut(shard_len);
-        // Convert the iterator over slices into a vector of slices.
-        let mut shards: Vec<&mut [u8]> = shards_iter.collect();
-
-        // Construct the parity chunks/shards. This only fails if a shard is empty or the shards
-        // have different sizes. Our shards all have size `shard_len`, which is at least 1.
-        self.coding.encode(&mut shards).expect("wrong shard size");
-
-        debug!(
-            "{}: Value: {} bytes, {} per shard. Shards: {:0.10}",
-            self,
-            value_len,
-            shard_len,
-            HexList(&shards)
-        );
+        let leaves = if replicate {
+            debug!("{}: Value: {} bytes, replicated.", self, value.len());
+            vec![value; self.netinfo.num_nodes()]
+        } else {
+            self.encode_shards(value)?
+        };
 
         // Create a Merkle tree from the shards.
-        let mtree = MerkleTree::from_vec(shards.into_iter().map(|shard| shard.to_vec()).collect());
+        let mtree = MerkleTree::from_vec(leaves);
 
         // Default result in case of `proof` error.
         let mut result = Err(Error::ProofConstructionFailed);
@@ -192,6 +409,39 @@ impl<N: NodeIdT> Broadcast<N> {
         result.map(|proof| (proof, step))
     }
 
+    /// Splits the length-prefixed value into data shards of equal length, and computes the parity
+    /// shards with the erasure coding scheme.
+    fn encode_shards(&self, mut value: Vec<u8>) -> Result<Vec<Vec<u8>>> {
+        let data_shard_num = self.coding.data_shard_count();
+        let parity_shard_num = self.coding.parity_shard_count();
+        let value_len = value.len(); // This is at least 4, due to the payload length.
+
+        // Size of a Merkle tree leaf value: the value size divided by the number of data shards,
+        // and rounded up, so that the full value always fits in the data shards. Always at least 1.
+        let shard_len = (value_len + data_shard_num - 1) / data_shard_num;
+        // Pad the last data shard with zeros. Fill the parity shards with zeros.
+        value.resize(shard_len * (data_shard_num + parity_shard_num), 0);
+
+        // Divide the vector into chunks/shards.
+        let shards_iter = value.chunks_mut(shard_len);
+        // Convert the iterator over slices into a vector of slices.
+        let mut shards: Vec<&mut [u8]> = shards_iter.collect();
+
+        // Construct the parity chunks/shards. Our shards all have size `shard_len`, which is at
+        // least 1, so this only fails if the coder doesn't satisfy the `ErasureCoder` contract.
+        self.coding.encode(&mut shards).map_err(Error::Encode)?;
+
+        debug!(
+            "{}: Value: {} bytes, {} per shard. Shards: {:0.10}",
+            self,
+            value_len,
+            shard_len,
+            HexList(&shards)
+        );
+
+        Ok(shards.into_iter().map(|shard| shard.to_vec()).collect())
+    }
+
     /// Handles a received echo and verifies the proof it contains.
     fn handle_value(&mut self, sender_id: &N, p: Proof<Vec<u8>>) -> Result<Step<N>> {
         // If the sender is not the proposer or if this is not the first `Value`, ignore.
@@ -200,7 +450,11 @@ impl<N: NodeIdT> Broadcast<N> {
             return Ok(Fault::new(sender_id.clone(), fault_kind).into());
         }
         if self.echo_sent {
-            if self.echos.get(self.our_id()) == Some(&p) {
+            if self
+                .echos
+                .get(self.our_id())
+                .map_or(false, |shard| shard.matches(&p))
+            {
                 warn!(
                     "Node {:?} received Value({:?}) multiple times from {:?}.",
                     self.our_id(),
@@ -218,6 +472,7 @@ impl<N: NodeIdT> Broadcast<N> {
             return Ok(Fault::new(sender_id.clone(), FaultKind::InvalidProof).into());
         }
 
+        self.record_progress(Progress::ValueReceived);
         // Otherwise multicast the proof in an `Echo` message, and handle it ourselves.
         self.send_echo(p)
     }
@@ -225,8 +480,8 @@ impl<N: NodeIdT> Broadcast<N> {
     /// Handles a received `Echo` message.
     fn handle_echo(&mut self, sender_id: &N, p: Proof<Vec<u8>>) -> Result<Step<N>> {
         // If the sender has already sent `Echo`, ignore.
-        if let Some(old_p) = self.echos.get(sender_id) {
-            if *old_p == p {
+        if let Some(old_shard) = self.echos.get(sender_id) {
+            if old_shard.matches(&p) {
                 warn!(
                     "Node {:?} received Echo({:?}) multiple times from {:?}.",
                     self.our_id(),
@@ -246,15 +501,47 @@ impl<N: NodeIdT> Broadcast<N> {
 
         let hash = *p.root_hash();
 
-        // Save the proof for reconstructing the tree later.
-        self.echos.insert(sender_id.clone(), p);
+        // Save the shard for reconstructing the tree later. The proof is not needed anymore.
+        self.echos.insert(sender_id.clone(), EchoShard::from(p));
 
+        let step = self.check_equivocation(&hash);
         if self.ready_sent || self.count_echos(&hash) < self.netinfo.num_correct() {
-            return self.compute_output(&hash);
+            return Ok(step.join(self.compute_output(&hash)?));
         }
 
         // Upon receiving `N - f` `Echo`s with this root hash, multicast `Ready`.
-        self.send_ready(&hash)
+        Ok(step.join(self.send_ready(&hash)?))
+    }
+
+    /// Reports the proposer if more than _f_ nodes echoed `hash`, and more than _f_ echoed a
+    /// different root hash: At least one correct node is among each group, so the proposer sent
+    /// `Value`s from different trees.
+    ///
+    /// The instance is not aborted: Some correct nodes could still output, and we need to keep
+    /// handling `Ready`s, so that we output, too, in that case.
+    fn check_equivocation(&mut self, hash: &Digest) -> Step<N> {
+        if self.equivocation_reported || self.count_echos(hash) != self.netinfo.num_faulty() + 1 {
+            return Step::default();
+        }
+        let mut counts: BTreeMap<&Digest, usize> = BTreeMap::new();
+        for shard in self.echos.values() {
+            *counts.entry(&shard.root_hash).or_insert(0) += 1;
+        }
+        let quorum_roots = counts
+            .values()
+            .filter(|count| self.netinfo.has_weak_quorum(**count))
+            .count();
+        if quorum_roots < 2 {
+            return Step::default();
+        }
+        warn!(
+            "Node {:?} received Echos of different trees from {:?}.",
+            self.our_id(),
+            self.proposer_id
+        );
+        self.equivocation_reported = true;
+        let fault_kind = FaultKind::ProposerEquivocation;
+        Fault::new(self.proposer_id.clone(), fault_kind).into()
     }
 
     /// Handles a received `Ready` message.
@@ -292,6 +579,7 @@ impl<N: NodeIdT> Broadcast<N> {
         if !self.netinfo.is_validator() {
             return Ok(Step::default());
         }
+        self.record_progress(Progress::EchoSent);
         let echo_msg = Message::Echo(p.clone());
         let step: Step<_> = Target::All.message(echo_msg).into();
         let our_id = &self.our_id().clone();
@@ -304,6 +592,7 @@ impl<N: NodeIdT> Broadcast<N> {
         if !self.netinfo.is_validator() {
             return Ok(Step::default());
         }
+        self.record_progress(Progress::ReadySent);
         let ready_msg = Message::Ready(*hash);
         let step: Step<_> = Target::All.message(ready_msg).into();
         let our_id = &self.our_id().clone();
@@ -313,34 +602,87 @@ impl<N: NodeIdT> Broadcast<N> {
     /// Checks whether the conditions for output are met for this hash, and if so, sets the output
     /// value.
     fn compute_output(&mut self, hash: &Digest) -> Result<Step<N>> {
-        if self.decided
-            || self.count_readys(hash) <= 2 * self.netinfo.num_faulty()
-            || self.count_echos(hash) < self.coding.data_shard_count()
-        {
+        if self.output.is_some() || !self.netinfo.has_strong_quorum(self.count_readys(hash)) {
             return Ok(Step::default());
         }
 
-        // Upon receiving 2f + 1 matching Ready(h) messages, wait for N − 2f Echo messages.
-        let mut leaf_values: Vec<Option<Box<[u8]>>> = self
-            .netinfo
+        // Upon receiving 2f + 1 matching Ready(h) messages, wait for N − 2f Echo messages, or a
+        // single one if the value was replicated.
+        self.check_replicated(hash);
+        let opt_value = if let Some(leaf) = self.replicated_leaf(hash) {
+            Some(leaf[4..].to_vec())
+        } else if self.count_echos(hash) < self.coding.data_shard_count() {
+            return Ok(Step::default());
+        } else {
+            let mut leaf_values = self.leaf_values(hash);
+            self.decode_from_shards(&mut leaf_values, hash)
+        };
+        if let Some(value) = opt_value {
+            self.output = Some(value.clone());
+            self.output_root_hash = Some(*hash);
+            self.record_progress(Progress::Decoded);
+            Ok(Step::default().with_output(value))
+        } else {
+            let fault_kind = FaultKind::BroadcastDecoding;
+            Ok(Fault::new(self.proposer_id.clone(), fault_kind).into())
+        }
+    }
+
+    /// Records the event, if progress is being tracked.
+    fn record_progress(&mut self, event: Progress) {
+        if let Some(ref mut progress) = self.progress {
+            progress.push(event);
+        }
+    }
+
+    /// Checks whether the tree with the given root hash is a replicated value, if we have received
+    /// one of its leaves via an `Echo` and haven't checked it yet. This builds a tree with _N_
+    /// leaves, so the result is cached.
+    ///
+    /// A value is replicated if all _N_ leaves are equal and consist of the length-prefixed value.
+    /// An erasure-coded tree never has that form: Each of its _N - 2 f_ data shards is shorter than
+    /// the length-prefixed value, unless _N = 1_, in which case both encodings are the same.
+    fn check_replicated(&mut self, hash: &Digest) {
+        if self.replicated.contains_key(hash) {
+            return;
+        }
+        let leaf = match self.echos.values().find(|shard| shard.root_hash == *hash) {
+            Some(shard) => &shard.value,
+            None => return,
+        };
+        let replicated = leaf.len() >= 4
+            && BigEndian::read_u32(&leaf[..4]) as usize == leaf.len() - 4
+            && MerkleTree::from_vec(vec![leaf.clone(); self.netinfo.num_nodes()]).root_hash()
+                == hash;
+        self.replicated.insert(*hash, replicated);
+    }
+
+    /// Returns the leaf value of a replicated value with the given root hash, if `check_replicated`
+    /// found it to be one.
+    fn replicated_leaf(&self, hash: &Digest) -> Option<&Vec<u8>> {
+        if self.replicated.get(hash) != Some(&true) {
+            return None;
+        }
+        self.echos
+            .values()
+            .find(|shard| shard.root_hash == *hash)
+            .map(|shard| &shard.value)
+    }
+
+    /// Returns the shards with the given root hash received via `Echo` messages, by shard index.
+    fn leaf_values(&self, hash: &Digest) -> Vec<Option<Box<[u8]>>> {
+        self.netinfo
             .all_ids()
             .map(|id| {
-                self.echos.get(id).and_then(|p| {
-                    if p.root_hash() == hash {
-                        Some(p.value().clone().into_boxed_slice())
+                self.echos.get(id).and_then(|shard| {
+                    if shard.root_hash == *hash {
+                        Some(shard.value.clone().into_boxed_slice())
                     } else {
                         None
                     }
                 })
             })
-            .collect();
-        if let Some(value) = self.decode_from_shards(&mut leaf_values, hash) {
-            self.decided = true;
-            Ok(Step::default().with_output(value))
-        } else {
-            let fault_kind = FaultKind::BroadcastDecoding;
-            Ok(Fault::new(self.proposer_id.clone(), fault_kind).into())
-        }
+            .collect()
     }
 
     /// Interpolates the missing shards and glues together the data shards to retrieve the value.
@@ -351,8 +693,8 @@ impl<N: NodeIdT> Broadcast<N> {
         leaf_values: &mut [Option<Box<[u8]>>],
         root_hash: &Digest,
     ) -> Option<Vec<u8>> {
-        // Try to interpolate the Merkle tree using the Reed-Solomon erasure coding scheme.
-        self.coding.reconstruct_shards(leaf_values).ok()?;
+        // Try to interpolate the Merkle tree using the erasure coding scheme.
+        self.coding.reconstruct(leaf_values).ok()?;
 
         // Collect shards for tree construction.
         let shards: Vec<Vec<u8>> = leaf_values
@@ -388,14 +730,14 @@ impl<N: NodeIdT> Broadcast<N> {
 
     /// Returns `true` if the proof is valid and has the same index as the node ID.
     fn validate_proof(&self, p: &Proof<Vec<u8>>, id: &N) -> bool {
-        self.netinfo.node_index(id) == Some(p.index()) && p.validate(self.netinfo.num_nodes())
+        self.shard_index_of(id) == Some(p.index()) && p.validate(self.netinfo.num_nodes())
     }
 
     /// Returns the number of nodes that have sent us an `Echo` message with this hash.
     fn count_echos(&self, hash: &Digest) -> usize {
         self.echos
             .values()
-            .filter(|p| p.root_hash() == hash)
+            .filter(|shard| shard.root_hash == *hash)
             .count()
     }
 
@@ -408,67 +750,55 @@ impl<N: NodeIdT> Broadcast<N> {
     }
 }
 
-impl<N: NodeIdT> fmt::Display for Broadcast<N> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
-        write!(f, "{:?} Broadcast({:?})", self.our_id(), self.proposer_id)
+/// Returns an estimate of the traffic in an instance among `num_nodes` validators, up to
+/// `num_faulty` of which can be faulty, with a value of `value_len` bytes.
+///
+/// The proposer sends a `Value` to each other validator, and every validator multicasts an `Echo`
+/// and a `Ready` message. A `Value` or `Echo` contains a shard of _1 / (N - 2 f)_ of the value and
+/// a Merkle branch. The estimate uses the longest branch, with _⌈log<sub>2</sub>(N)⌉_ digests, so
+/// it is an upper bound if _N_ is not a power of two.
+///
+/// The value is assumed to be erasure-coded, as it is by default. See
+/// `Broadcast::set_replication_threshold`.
+///
+/// Returns `None` if _N ≤ 2 f_: Then there are no data shards, and `Broadcast::new` fails.
+pub fn estimated_messages(
+    num_nodes: usize,
+    num_faulty: usize,
+    value_len: usize,
+) -> Option<RoundCost> {
+    let data_shard_num = match num_faulty
+        .checked_mul(2)
+        .and_then(|f2| num_nodes.checked_sub(f2))
+    {
+        None | Some(0) => return None,
+        Some(data_shard_num) => data_shard_num,
+    };
+    // The value is prefixed with its length, and split into shards of equal size.
+    let shard_len = (value_len + 4 + data_shard_num - 1) / data_shard_num;
+    let mut num_digests = 0;
+    let mut lvl_n = num_nodes;
+    while lvl_n > 1 {
+        num_digests += 1;
+        lvl_n = (lvl_n + 1) / 2;
     }
+    // The message type, followed by the proof: the shard, index, branch and root hash.
+    let proof_size = 4 + (8 + shard_len) + 8 + (8 + num_digests * 32) + 32;
+    let ready_size = 4 + 32;
+    let num_values = num_nodes.saturating_sub(1);
+    let values = RoundCost {
+        messages: num_values,
+        bytes: num_values * proof_size,
+    };
+    Some(
+        values
+            + RoundCost::multicasts(num_nodes, 1, proof_size)
+            + RoundCost::multicasts(num_nodes, 1, ready_size),
+    )
 }
 
-/// A wrapper for `ReedSolomon` that doesn't panic if there are no parity shards.
-#[derive(Debug)]
-enum Coding {
-    /// A `ReedSolomon` instance with at least one parity shard.
-    ReedSolomon(Box<ReedSolomon>),
-    /// A no-op replacement that doesn't encode or decode anything.
-    Trivial(usize),
-}
-
-impl Coding {
-    /// Creates a new `Coding` instance with the given number of shards.
-    fn new(data_shard_num: usize, parity_shard_num: usize) -> RseResult<Self> {
-        Ok(if parity_shard_num > 0 {
-            let rs = ReedSolomon::new(data_shard_num, parity_shard_num)?;
-            Coding::ReedSolomon(Box::new(rs))
-        } else {
-            Coding::Trivial(data_shard_num)
-        })
-    }
-
-    /// Returns the number of data shards.
-    fn data_shard_count(&self) -> usize {
-        match *self {
-            Coding::ReedSolomon(ref rs) => rs.data_shard_count(),
-            Coding::Trivial(dsc) => dsc,
-        }
-    }
-
-    /// Returns the number of parity shards.
-    fn parity_shard_count(&self) -> usize {
-        match *self {
-            Coding::ReedSolomon(ref rs) => rs.parity_shard_count(),
-            Coding::Trivial(_) => 0,
-        }
-    }
-
-    /// Constructs (and overwrites) the parity shards.
-    fn encode(&self, slices: &mut [&mut [u8]]) -> RseResult<()> {
-        match *self {
-            Coding::ReedSolomon(ref rs) => rs.encode(slices),
-            Coding::Trivial(_) => Ok(()),
-        }
-    }
-
-    /// If enough shards are present, reconstructs the missing ones.
-    fn reconstruct_shards(&self, shards: &mut [Option<Box<[u8]>>]) -> RseResult<()> {
-        match *self {
-            Coding::ReedSolomon(ref rs) => rs.reconstruct_shards(shards),
-            Coding::Trivial(_) => {
-                if shards.iter().all(Option::is_some) {
-                    Ok(())
-                } else {
-                    Err(rse::Error::TooFewShardsPresent)
-                }
-            }
-        }
+impl<N: NodeIdT> fmt::Display for Broadcast<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+        write!(f, "{:?} Broadcast({:?})", self.our_id(), self.proposer_id)
     }
 }
@@ -0,0 +1,113 @@
+use std::fmt::Debug;
+use std::result;
+
+use failure::Fail;
+use reed_solomon_erasure as rse;
+use reed_solomon_erasure::ReedSolomon;
+
+/// An error encoding or reconstructing shards.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Fail)]
+pub enum CodingError {
+    /// Not enough shards are present to reconstruct the missing ones.
+    #[fail(display = "Too few shards present")]
+    TooFewShards,
+    /// The shards are empty, have different sizes, or their number doesn't match the coder.
+    #[fail(display = "Invalid shards")]
+    InvalidShards,
+}
+
+/// An erasure coding result.
+pub type CodingResult<T> = result::Result<T, CodingError>;
+
+/// An erasure coding scheme, used by `Broadcast` to split the value into shards.
+///
+/// The value is divided into `data_shard_count` shards of equal size. The coder computes
+/// `parity_shard_count` additional shards from them, so that the value can be reconstructed from
+/// any `data_shard_count` of the shards. The total number of shards must equal the number of
+/// validators. The default, used by `Broadcast::new`, is Reed-Solomon coding with _N - 2 f_ data
+/// shards, but any backend that satisfies this contract can be passed to
+/// `Broadcast::new_with_coder`. All nodes must use the same scheme.
+pub trait ErasureCoder: Debug + Send + Sync {
+    /// Returns the number of data shards.
+    fn data_shard_count(&self) -> usize;
+
+    /// Returns the number of parity shards.
+    fn parity_shard_count(&self) -> usize;
+
+    /// Computes the parity shards, overwriting the last `parity_shard_count` entries of `shards`
+    /// with the codes of the first `data_shard_count` ones. All shards have the same, nonzero
+    /// size.
+    fn encode(&self, shards: &mut [&mut [u8]]) -> CodingResult<()>;
+
+    /// Reconstructs the missing shards, i.e. the `None` entries, if enough shards are present.
+    fn reconstruct(&self, shards: &mut [Option<Box<[u8]>>]) -> CodingResult<()>;
+}
+
+/// A wrapper for `ReedSolomon` that doesn't panic if there are no parity shards.
+#[derive(Debug)]
+pub(crate) enum Coding {
+    /// A `ReedSolomon` instance with at least one parity shard.
+    ReedSolomon(Box<ReedSolomon>),
+    /// A no-op replacement that doesn't encode or decode anything.
+    Trivial(usize),
+}
+
+impl Coding {
+    /// Creates a new `Coding` instance with the given number of shards.
+    pub(crate) fn new(
+        data_shard_num: usize,
+        parity_shard_num: usize,
+    ) -> result::Result<Self, rse::Error> {
+        Ok(if parity_shard_num > 0 {
+            let rs = ReedSolomon::new(data_shard_num, parity_shard_num)?;
+            Coding::ReedSolomon(Box::new(rs))
+        } else {
+            Coding::Trivial(data_shard_num)
+        })
+    }
+}
+
+impl ErasureCoder for Coding {
+    fn data_shard_count(&self) -> usize {
+        match *self {
+            Coding::ReedSolomon(ref rs) => rs.data_shard_count(),
+            Coding::Trivial(dsc) => dsc,
+        }
+    }
+
+    fn parity_shard_count(&self) -> usize {
+        match *self {
+            Coding::ReedSolomon(ref rs) => rs.parity_shard_count(),
+            Coding::Trivial(_) => 0,
+        }
+    }
+
+    fn encode(&self, slices: &mut [&mut [u8]]) -> CodingResult<()> {
+        match *self {
+            Coding::ReedSolomon(ref rs) => rs.encode(slices).map_err(CodingError::from),
+            Coding::Trivial(_) => Ok(()),
+        }
+    }
+
+    fn reconstruct(&self, shards: &mut [Option<Box<[u8]>>]) -> CodingResult<()> {
+        match *self {
+            Coding::ReedSolomon(ref rs) => rs.reconstruct_shards(shards).map_err(CodingError::from),
+            Coding::Trivial(_) => {
+                if shards.iter().all(Option::is_some) {
+                    Ok(())
+                } else {
+                    Err(CodingError::TooFewShards)
+                }
+            }
+        }
+    }
+}
+
+impl From<rse::Error> for CodingError {
+    fn from(err: rse::Error) -> Self {
+        match err {
+            rse::Error::TooFewShardsPresent => CodingError::TooFewShards,
+            _ => CodingError::InvalidShards,
+        }
+    }
+}
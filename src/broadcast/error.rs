@@ -1,5 +1,7 @@
 use failure::Fail;
 
+use super::CodingError;
+
 /// A broadcast error.
 #[derive(Clone, PartialEq, Debug, Fail)]
 pub enum Error {
@@ -15,9 +17,15 @@ pub enum Error {
     /// Failed to construct a Merkle tree proof.
     #[fail(display = "Proof construction failed")]
     ProofConstructionFailed,
+    /// The erasure coder's number of shards doesn't match the number of validators.
+    #[fail(display = "Number of shards must equal the number of validators")]
+    InvalidShardCount,
     /// Unknown sender.
     #[fail(display = "Unknown sender")]
     UnknownSender,
+    /// The erasure coder failed to compute the parity shards.
+    #[fail(display = "Erasure coding failed: {}", _0)]
+    Encode(#[cause] CodingError),
 }
 
 /// A broadcast result.
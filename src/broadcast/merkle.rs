@@ -3,6 +3,7 @@ use std::mem;
 use serde_derive::{Deserialize, Serialize};
 use tiny_keccak::sha3_256;
 
+/// A SHA3-256 digest, e.g. the root hash of a Merkle tree.
 pub type Digest = [u8; 32];
 
 /// A Merkle tree: The leaves are values and their hashes. Each level consists of the hashes of
@@ -123,6 +124,25 @@ impl<T: AsRef<[u8]>> Proof<T> {
     }
 }
 
+/// Returns `true` if `proof` shows that `leaf` is the entry `index` in the Merkle tree with
+/// `num_leaves` leaves and the given root hash.
+///
+/// The number of leaves is needed because a level with an odd number of entries has no sibling
+/// for the last one, so the proof's shape depends on it. In `Broadcast`, it is the number of
+/// validators.
+pub fn verify_opening(
+    root_hash: &Digest,
+    num_leaves: usize,
+    index: usize,
+    leaf: &[u8],
+    proof: &Proof<Vec<u8>>,
+) -> bool {
+    proof.root_hash == *root_hash
+        && proof.index == index
+        && proof.value[..] == *leaf
+        && proof.validate(num_leaves)
+}
+
 /// Takes a chunk of one or two digests. In the former case, returns the digest itself, in the
 /// latter, it returns the hash of the two digests.
 fn hash_chunk(chunk: &[Digest]) -> Digest {
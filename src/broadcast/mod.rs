@@ -39,6 +39,9 @@
 //! * A node that has received _2 f + 1_ `Ready`s **and** _N - 2 f_ `Echo`s with root hash `h`
 //! decodes and outputs the value, and then terminates.
 //!
+//! Instead of Reed-Solomon coding, `Broadcast::new_with_coder` accepts any `ErasureCoder`, e.g. a
+//! faster implementation for a particular platform.
+//!
 //! Only the first valid `Value` from the proposer, and the first valid `Echo` message from every
 //! validator, is handled as above. Invalid messages (where the proof isn't correct), `Values`
 //! received from other nodes, and any further `Value`s and `Echo`s are ignored, and the sender is
@@ -78,6 +81,16 @@
 //! _2 f + 1_ `Ready`s **and** _N - 2 f_ `Echo`s with root hash `h`), we know that
 //! everyone else will eventually satisfy it, too. So at that point, we can output and terminate.
 //!
+//! ### Small values
+//!
+//! Values of at most _N - 2 f_ bytes can optionally be sent without erasure coding: Each shard
+//! would only be a single byte, so instead every leaf of the Merkle tree is the full value,
+//! prefixed with its length. The tree still commits to the value, but a node can decode it from a
+//! single valid `Echo`, once it has received _2 f + 1_ `Ready`s. The other nodes recognize such a
+//! tree by its _N_ equal leaves, so the threshold only needs to be configured in the proposer, with
+//! `Broadcast::set_replication_threshold`. This is disabled by default, since nodes running an
+//! older version can't decode replicated values.
+//!
 //!
 //! ## Example
 //!
@@ -185,10 +198,13 @@
 //! ```
 
 mod broadcast;
+mod coding;
 mod error;
 pub(crate) mod merkle;
 mod message;
 
-pub use self::broadcast::{Broadcast, Step};
+pub use self::broadcast::{estimated_messages, Broadcast, Progress, Step};
+pub use self::coding::{CodingError, CodingResult, ErasureCoder};
 pub use self::error::{Error, Result};
+pub use self::merkle::{verify_opening, Digest, Proof};
 pub use self::message::Message;
@@ -0,0 +1,253 @@
+//! # Coin verification
+//!
+//! Functions to check a common coin, i.e. a combined threshold signature, without running any of
+//! the algorithms, e.g. in a light client that only knows the network's public master key, and
+//! `CoinStats` to monitor the distribution of coin values over many rounds.
+//!
+//! This module only depends on `threshold_crypto`, the `pairing` crate it re-exports, and `rand`
+//! for the random coefficients of the batch verification. It is kept free of the networking and
+//! erasure coding parts of the crate: With `default-features = false`, no Reed-Solomon backend is
+//! built. Note that `threshold_crypto` itself does not support `wasm32-unknown-unknown` yet, since
+//! it zeroes and locks secret keys in memory using OS-specific dependencies: `errno` and `memsec`
+//! fail to compile for that target.
+
+use rand::Rng;
+
+use crate::crypto::{
+    hash_g2, G1Affine, G2Affine, IntoFr, PEngine, PublicKey, PublicKeySet, Signature, G2,
+};
+use crate::pairing::{CurveAffine, CurveProjective, EncodedPoint, Engine, Field};
+
+/// Returns the coin's value, i.e. the signature's parity, if `sig` is a valid signature of `nonce`
+/// by `pk`, or `None` if it isn't.
+///
+/// For a `ThresholdSign` instance, `nonce` is the document, and `pk` the public master key of the
+/// network's `PublicKeySet`. For the coin in `BinaryAgreement`, the nonce is computed by
+/// `binary_agreement::coin_nonce`.
+pub fn verify_coin<M: AsRef<[u8]>>(pk: &PublicKey, sig: &Signature, nonce: M) -> Option<bool> {
+    if pk.verify(sig, nonce) {
+        Some(sig.parity())
+    } else {
+        None
+    }
+}
+
+/// Verifies a log of coin outputs, given as `(nonce, claimed_bit, signature)` entries, and returns
+/// for each entry whether the signature is valid for the nonce and its parity is the claimed bit.
+///
+/// Instead of checking every signature separately, the valid-looking entries are combined into a
+/// single check with random coefficients from `rng`, which takes two pairings in total. If that
+/// fails, the entries are checked one by one to find the invalid ones, so a tampered log costs as
+/// much as verifying each entry with `verify_coin`. The chance that a batch containing an invalid
+/// signature passes is negligible, as long as the coefficients are unpredictable to whoever
+/// produced the log.
+pub fn verify_outputs_batch<M, R>(
+    pk_set: &PublicKeySet,
+    entries: &[(M, bool, Signature)],
+    rng: &mut R,
+) -> Vec<bool>
+where
+    M: AsRef<[u8]>,
+    R: Rng,
+{
+    let pk = pk_set.public_key();
+    let mut results: Vec<bool> = entries
+        .iter()
+        .map(|(_, bit, sig)| sig.parity() == *bit)
+        .collect();
+    let pk_point = match decode_g1(&pk) {
+        Some(point) => point,
+        None => return vec![false; entries.len()],
+    };
+    // The random linear combinations of the signatures and of the nonces' hashes.
+    let mut sig_sum = G2::zero();
+    let mut hash_sum = G2::zero();
+    for (i, (nonce, _, sig)) in entries.iter().enumerate() {
+        if !results[i] {
+            continue;
+        }
+        let mut sig_point = match decode_g2(sig) {
+            Some(point) => point,
+            None => {
+                results[i] = false;
+                continue;
+            }
+        };
+        let coeff = loop {
+            let coeff = rng.gen::<u64>().into_fr();
+            if !coeff.is_zero() {
+                break coeff;
+            }
+        };
+        sig_point.mul_assign(coeff);
+        sig_sum.add_assign(&sig_point);
+        let mut hash = hash_g2(nonce);
+        hash.mul_assign(coeff);
+        hash_sum.add_assign(&hash);
+    }
+    if PEngine::pairing(pk_point, hash_sum) == PEngine::pairing(G1Affine::one(), sig_sum) {
+        return results;
+    }
+    for (result, (nonce, _, sig)) in results.iter_mut().zip(entries) {
+        *result = *result && pk.verify(sig, nonce);
+    }
+    results
+}
+
+/// Returns the point in _G1_ of the public key.
+fn decode_g1(pk: &PublicKey) -> Option<G1Affine> {
+    let mut compressed = <G1Affine as CurveAffine>::Compressed::empty();
+    compressed.as_mut().copy_from_slice(&pk.to_bytes());
+    compressed.into_affine().ok()
+}
+
+/// Returns the point in _G2_ of the signature.
+fn decode_g2(sig: &Signature) -> Option<G2> {
+    let mut compressed = <G2Affine as CurveAffine>::Compressed::empty();
+    compressed.as_mut().copy_from_slice(&sig.to_bytes());
+    compressed
+        .into_affine()
+        .ok()
+        .map(|point| point.into_projective())
+}
+
+/// Counts of coin values, to detect a coin that is biased, e.g. due to a broken key set.
+///
+/// The driver records the value of every coin flip. `bias` then returns Pearson's chi-square
+/// statistic of the counts against the expected 50/50 distribution. For a fair coin, it follows
+/// the chi-square distribution with one degree of freedom: Values above `3.84` only occur with
+/// probability 5%, and above `6.63` with probability 1%. The statistic is only meaningful after
+/// a reasonable number of flips, at least a few dozen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CoinStats {
+    /// The number of `true` values.
+    trues: u64,
+    /// The number of `false` values.
+    falses: u64,
+}
+
+impl CoinStats {
+    /// Returns new, empty statistics.
+    pub fn new() -> Self {
+        CoinStats::default()
+    }
+
+    /// Records a coin value.
+    pub fn record(&mut self, bit: bool) {
+        if bit {
+            self.trues += 1;
+        } else {
+            self.falses += 1;
+        }
+    }
+
+    /// Returns the number of recorded `true` values.
+    pub fn trues(&self) -> u64 {
+        self.trues
+    }
+
+    /// Returns the number of recorded `false` values.
+    pub fn falses(&self) -> u64 {
+        self.falses
+    }
+
+    /// Returns the total number of recorded values.
+    pub fn count(&self) -> u64 {
+        self.trues + self.falses
+    }
+
+    /// Returns the chi-square statistic of the recorded values against a fair coin, or `0.0` if
+    /// nothing has been recorded yet. With _t_ `true` and _f_ `false` values, this is
+    /// _(t - f)² / (t + f)_.
+    pub fn bias(&self) -> f64 {
+        let count = self.count();
+        if count == 0 {
+            return 0.0;
+        }
+        let diff = self.trues as f64 - self.falses as f64;
+        diff * diff / count as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_coin, verify_outputs_batch, CoinStats};
+    use crate::crypto::{SecretKeySet, Signature};
+
+    #[test]
+    fn test_verify_coin() {
+        let sk_set = SecretKeySet::random(1, &mut rand::thread_rng());
+        let pk = sk_set.public_keys().public_key();
+        let nonce = b"coin";
+        let shares: Vec<_> = (0..2)
+            .map(|i| (i, sk_set.secret_key_share(i).sign(nonce)))
+            .collect();
+        let sig = sk_set
+            .public_keys()
+            .combine_signatures(shares.iter().map(|(i, share)| (*i, share)))
+            .expect("combine signatures");
+        assert_eq!(Some(sig.parity()), verify_coin(&pk, &sig, nonce));
+        assert_eq!(None, verify_coin(&pk, &sig, b"other coin"));
+    }
+
+    #[test]
+    fn test_verify_outputs_batch() {
+        let mut rng = rand::thread_rng();
+        let sk_set = SecretKeySet::random(2, &mut rng);
+        let pk_set = sk_set.public_keys();
+        let sign = |nonce: &str| -> Signature {
+            let shares: Vec<_> = (0..3)
+                .map(|i| (i, sk_set.secret_key_share(i).sign(nonce)))
+                .collect();
+            pk_set
+                .combine_signatures(shares.iter().map(|(i, share)| (*i, share)))
+                .expect("combine signatures")
+        };
+        let mut entries: Vec<(String, bool, Signature)> = (0..10)
+            .map(|epoch| {
+                let nonce = format!("epoch {}", epoch);
+                let sig = sign(&nonce);
+                (nonce, sig.parity(), sig)
+            })
+            .collect();
+        assert_eq!(
+            vec![true; 10],
+            verify_outputs_batch(&pk_set, &entries, &mut rng)
+        );
+        let empty: Vec<(String, bool, Signature)> = Vec::new();
+        assert!(verify_outputs_batch(&pk_set, &empty, &mut rng).is_empty());
+
+        // Replace one signature with that of a different nonce, and claim the wrong bit for
+        // another entry.
+        let forged = sign("forged");
+        entries[3].1 = forged.parity();
+        entries[3].2 = forged;
+        entries[7].1 = !entries[7].1;
+        let mut expected = vec![true; 10];
+        expected[3] = false;
+        expected[7] = false;
+        assert_eq!(expected, verify_outputs_batch(&pk_set, &entries, &mut rng));
+    }
+
+    #[test]
+    fn test_coin_stats_bias() {
+        let mut stats = CoinStats::new();
+        assert!(stats.bias().abs() < 1e-9);
+        // A balanced sequence has no bias.
+        for i in 0..100 {
+            stats.record(i % 2 == 0);
+        }
+        assert!(stats.bias().abs() < 1e-9);
+        // 70 out of 100 is far beyond the 1% significance level.
+        let mut skewed = CoinStats::new();
+        for i in 0..100 {
+            skewed.record(i % 10 < 7);
+        }
+        assert_eq!(
+            (70, 30, 100),
+            (skewed.trues(), skewed.falses(), skewed.count())
+        );
+        assert!((skewed.bias() - 16.0).abs() < 1e-9);
+        assert!(skewed.bias() > 6.63);
+    }
+}
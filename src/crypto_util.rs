@@ -0,0 +1,51 @@
+//! # Cryptographic utilities
+//!
+//! Checks of keys and signature shares that complement `threshold_crypto`.
+
+use crate::crypto::{G2Affine, PublicKeySet, SecretKeyShare, SignatureShare};
+use crate::pairing::CurveAffine;
+
+/// Returns `false` if the signature share is malformed, i.e. certain to fail verification, using a
+/// check that is much cheaper than the pairings of a full verification.
+///
+/// Deserializing a share already rejects bytes of the wrong length and points that are not in
+/// the group, so the only malformed share left to detect is the group's identity element: It is a
+/// valid encoding, but never a valid signature share of a nonzero key share.
+pub fn is_well_formed(share: &SignatureShare) -> bool {
+    share.to_bytes()[..] != *G2Affine::zero().into_compressed().as_ref()
+}
+
+/// Verification of a secret key share against a `PublicKeySet`, e.g. to detect swapped key files
+/// when a validator starts.
+pub trait VerifySecretShare {
+    /// Returns `true` if `sks` is the secret key share with the given index, i.e. if its public
+    /// key share is the one with that index.
+    fn verify_secret_share(&self, index: u64, sks: &SecretKeyShare) -> bool;
+}
+
+impl VerifySecretShare for PublicKeySet {
+    fn verify_secret_share(&self, index: u64, sks: &SecretKeyShare) -> bool {
+        sks.public_key_share() == self.public_key_share(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VerifySecretShare;
+    use crate::crypto::SecretKeySet;
+
+    #[test]
+    fn test_verify_secret_share() {
+        let mut rng = rand::thread_rng();
+        let sk_set = SecretKeySet::random(2, &mut rng);
+        let pk_set = sk_set.public_keys();
+        for i in 0..4 {
+            assert!(pk_set.verify_secret_share(i, &sk_set.secret_key_share(i)));
+        }
+        // A share with a different index, e.g. after a configuration swap, is rejected.
+        assert!(!pk_set.verify_secret_share(1, &sk_set.secret_key_share(2)));
+        // So is the share with the right index from a different key set.
+        let other_sk_set = SecretKeySet::random(2, &mut rng);
+        assert!(!pk_set.verify_secret_share(1, &other_sk_set.secret_key_share(1)));
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
 
 use super::{ChangeState, JoinPlan, Params};
@@ -16,6 +16,9 @@ pub struct Batch<C, N: Ord> {
     /// The current state of adding or removing a node: whether any is in progress, or completed
     /// this epoch.
     pub(super) change: ChangeState<N>,
+    /// The nodes that became validators in this epoch and will participate starting with the
+    /// next one.
+    pub(super) joined: BTreeSet<N>,
     /// The network info that applies to the _next_ epoch.
     pub(super) netinfo: Arc<NetworkInfo<N>>,
     /// Parameters controlling Honey Badger's behavior and performance.
@@ -39,6 +42,17 @@ impl<C, N: NodeIdT> Batch<C, N> {
         &self.change
     }
 
+    /// Returns the IDs of the nodes that are validators starting with the _next_ epoch, but were
+    /// not validators in this one.
+    ///
+    /// This is the exact point at which the new validators join: all correct nodes output the
+    /// same set in the same epoch. Since `DynamicHoneyBadger` only provides the new nodes with
+    /// their key shares, this is where the application should send them a snapshot of its state
+    /// as of the end of this epoch, if they need it.
+    pub fn joined_nodes(&self) -> impl Iterator<Item = &N> {
+        self.joined.iter()
+    }
+
     /// Returns the `NetworkInfo` containing the information about the validators that will produce
     /// the _next_ epoch after this one.
     pub fn network_info(&self) -> &Arc<NetworkInfo<N>> {
@@ -97,6 +111,7 @@ impl<C, N: NodeIdT> Batch<C, N> {
         }
         Some(JoinPlan {
             era: self.epoch + 1,
+            epoch: self.epoch + 1,
             change: self.change.clone(),
             pub_key_set: self.netinfo.public_key_set().clone(),
             pub_keys: self.netinfo.public_key_map().clone(),
@@ -114,6 +129,7 @@ impl<C, N: NodeIdT> Batch<C, N> {
             && self.era == other.era
             && self.contributions == other.contributions
             && self.change == other.change
+            && self.joined == other.joined
             && self.netinfo.public_key_set() == other.netinfo.public_key_set()
             && self.netinfo.public_key_map() == other.netinfo.public_key_map()
             && self.params == other.params
@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
 use std::{fmt, result};
 
@@ -20,7 +20,9 @@ use crate::honey_badger::{self, HoneyBadger, Message as HbMessage};
 
 use crate::sync_key_gen::{Ack, AckOutcome, Part, PartOutcome, SyncKeyGen};
 use crate::util;
-use crate::{Contribution, DistAlgorithm, Epoched, NetworkInfo, NodeIdT, Target};
+use crate::{
+    Contribution, Diagnose, DiagnosticTree, DistAlgorithm, Epoched, NetworkInfo, NodeIdT, Target,
+};
 
 /// A Honey Badger instance that can handle adding and removing nodes.
 #[derive(Derivative)]
@@ -104,10 +106,16 @@ where
             secret_key,
             join_plan.pub_keys,
         );
+        // The plan comes from another node, so its epoch can't be trusted to be in its era.
+        let hb_epoch = join_plan
+            .epoch
+            .checked_sub(join_plan.era)
+            .ok_or(Error::InvalidJoinPlan)?;
         let max_future_epochs = join_plan.params.max_future_epochs;
         let arc_netinfo = Arc::new(netinfo.clone());
         let honey_badger = HoneyBadger::builder(arc_netinfo.clone())
             .session_id(join_plan.era)
+            .epoch(hb_epoch)
             .params(join_plan.params)
             .build();
         let mut dhb = DynamicHoneyBadger {
@@ -129,6 +137,29 @@ where
         Ok((dhb, step))
     }
 
+    /// Returns a `JoinPlan` for a new observer that starts in the next epoch, or `None` if votes
+    /// have been committed or key generation is in progress in the current era: The observer would
+    /// not know about them, so it can only join once the next era begins, using `Batch::join_plan`.
+    ///
+    /// Unlike `Batch::join_plan`, this doesn't require a change: It can be used to let observers
+    /// follow the network without a vote, e.g. for analytics. Such an observer outputs the batches
+    /// and sees all membership changes, but never becomes a validator unless it is voted in. All
+    /// `Target::All` messages from the plan's `next_epoch` on must be sent to it, so the plan should
+    /// be created right after outputting a batch.
+    pub fn observer_join_plan(&self) -> Option<JoinPlan<N>> {
+        if self.key_gen_state.is_some() || self.vote_counter.has_committed_votes() {
+            return None;
+        }
+        Some(JoinPlan {
+            era: self.era,
+            epoch: self.next_epoch(),
+            change: ChangeState::None,
+            pub_key_set: self.netinfo.public_key_set().clone(),
+            pub_keys: self.netinfo.public_key_map().clone(),
+            params: self.honey_badger.params().clone(),
+        })
+    }
+
     /// Returns `true` if input for the current epoch has already been provided.
     pub fn has_input(&self) -> bool {
         self.honey_badger.has_input()
@@ -252,7 +283,7 @@ where
 
     /// The epoch of the next batch that will be output.
     pub fn next_epoch(&self) -> u64 {
-        self.era + self.honey_badger.next_epoch()
+        self.era.saturating_add(self.honey_badger.next_epoch())
     }
 
     /// Handles a message for the `HoneyBadger` instance.
@@ -314,7 +345,11 @@ where
         let output = step.extend_with(hb_step, |hb_msg| Message::HoneyBadger(self.era, hb_msg));
         for hb_batch in output {
             let batch_era = self.era;
-            let batch_epoch = hb_batch.epoch + batch_era;
+            let batch_epoch = hb_batch
+                .epoch
+                .checked_add(batch_era)
+                .ok_or(Error::EpochOverflow)?;
+            let next_epoch = batch_epoch.checked_add(1).ok_or(Error::EpochOverflow)?;
             let mut batch_contributions = BTreeMap::new();
 
             // Add the user transactions to `batch` and handle votes and DKG messages.
@@ -344,21 +379,29 @@ where
                     }
                 }
             }
+            let mut joined = BTreeSet::new();
             let change = if let Some(kgs) = self.take_ready_key_gen() {
                 // If DKG completed, apply the change, restart Honey Badger, and inform the user.
                 debug!("{}: DKG for complete for: {:?}", self, kgs.public_keys());
+                let old_netinfo = &self.netinfo;
+                joined = kgs
+                    .public_keys()
+                    .keys()
+                    .filter(|id| !old_netinfo.is_node_validator(id))
+                    .cloned()
+                    .collect();
                 self.netinfo = kgs.key_gen.into_network_info().map_err(Error::SyncKeyGen)?;
                 let params = self.honey_badger.params().clone();
-                self.restart_honey_badger(batch_epoch + 1, params);
+                self.restart_honey_badger(next_epoch, params);
                 ChangeState::Complete(Change::NodeChange(self.netinfo.public_key_map().clone()))
             } else if let Some(change) = self.vote_counter.compute_winner().cloned() {
                 // If there is a new change, restart DKG. Inform the user about the current change.
                 match change {
                     Change::NodeChange(ref pub_keys) => {
-                        step.extend(self.update_key_gen(batch_epoch + 1, pub_keys, rng)?);
+                        step.extend(self.update_key_gen(next_epoch, pub_keys, rng)?);
                     }
                     Change::EncryptionSchedule(schedule) => {
-                        self.update_encryption_schedule(batch_epoch + 1, schedule);
+                        self.update_encryption_schedule(next_epoch, schedule);
                     }
                 }
                 match change {
@@ -372,6 +415,7 @@ where
                 epoch: batch_epoch,
                 era: batch_era,
                 change,
+                joined,
                 netinfo: Arc::new(self.netinfo.clone()),
                 contributions: batch_contributions,
                 params: self.honey_badger.params().clone(),
@@ -538,6 +582,26 @@ where
     }
 }
 
+impl<C, N> Diagnose for DynamicHoneyBadger<C, N>
+where
+    C: Contribution + Serialize + DeserializeOwned,
+    N: NodeIdT + Serialize + DeserializeOwned,
+{
+    /// Returns the era and the key generation progress, with the `HoneyBadger` instance as child.
+    fn diagnose(&self) -> DiagnosticTree {
+        let key_gen = match self.key_gen_state {
+            Some(ref kgs) => format!(
+                "key generation {} of {} complete",
+                kgs.key_gen.count_complete(),
+                kgs.public_keys().len()
+            ),
+            None => "no key generation".to_string(),
+        };
+        DiagnosticTree::new(format!("DynamicHoneyBadger era {}: {}", self.era, key_gen))
+            .with_child(self.honey_badger.diagnose())
+    }
+}
+
 impl<C, N> Epoched for DynamicHoneyBadger<C, N>
 where
     C: Contribution + Serialize + DeserializeOwned,
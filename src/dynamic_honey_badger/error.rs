@@ -15,16 +15,22 @@ pub enum Error {
     SerializeVote(bincode::ErrorKind),
     /// Failed to propose a contribution in `HoneyBadger`.
     #[fail(display = "Error proposing a contribution in HoneyBadger: {}", _0)]
-    ProposeHoneyBadger(honey_badger::Error),
+    ProposeHoneyBadger(#[cause] honey_badger::Error),
     /// Failed to handle a `HoneyBadger` message.
     #[fail(display = "Error handling a HoneyBadger message: {}", _0)]
-    HandleHoneyBadgerMessage(honey_badger::Error),
+    HandleHoneyBadgerMessage(#[cause] honey_badger::Error),
     /// Failed to handle a `SyncKeyGen` message.
     #[fail(display = "Error handling SyncKeyGen message: {}", _0)]
-    SyncKeyGen(sync_key_gen::Error),
+    SyncKeyGen(#[cause] sync_key_gen::Error),
     /// Unknown sender
     #[fail(display = "Unknown sender")]
     UnknownSender,
+    /// The epoch number would overflow.
+    #[fail(display = "Epoch number overflow")]
+    EpochOverflow,
+    /// The join plan's epoch is before the beginning of its era.
+    #[fail(display = "Join plan starts before its era")]
+    InvalidJoinPlan,
 }
 
 /// The result of `DynamicHoneyBadger` handling an input or message.
@@ -37,6 +37,11 @@
 //! following epoch. All `Target::All` messages from that and later epochs must be sent to the new
 //! node.
 //!
+//! Observers that only follow the network, and are not meant to become validators, don't need to
+//! wait for a change: `DynamicHoneyBadger::observer_join_plan` returns a `JoinPlan` for the next
+//! epoch whenever no votes are committed and no key generation is in progress in the current era.
+//! A validator can create it and send it to the observer out-of-band, without a vote.
+//!
 //! Observer nodes can leave the network at any time.
 //!
 //! These mechanisms create a dynamic network where you can:
@@ -137,8 +142,10 @@ impl<N: Ord> Message<N> {
 /// joins in the next one.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct JoinPlan<N: Ord> {
-    /// The first epoch the new node will observe.
+    /// The era of the first epoch the new node will observe.
     era: u64,
+    /// The first epoch the new node will observe.
+    epoch: u64,
     /// The current change. If `InProgress`, key generation for it is beginning at `epoch`.
     change: ChangeState<N>,
     /// The current public key set for threshold cryptography.
@@ -152,7 +159,7 @@ pub struct JoinPlan<N: Ord> {
 impl<N: Ord> JoinPlan<N> {
     /// The epoch of the first batch the new node will observe.
     pub fn next_epoch(&self) -> u64 {
-        self.era
+        self.epoch
     }
 }
 
@@ -133,6 +133,11 @@ where
         Ok(FaultLog::new())
     }
 
+    /// Returns `true` if any votes have been committed in the current era.
+    pub fn has_committed_votes(&self) -> bool {
+        !self.committed.is_empty()
+    }
+
     /// Returns the change that has at least _f + 1_ votes, if any.
     pub fn compute_winner(&self) -> Option<&Change<N>> {
         let mut vote_counts: HashMap<&Change<N>, usize> = HashMap::new();
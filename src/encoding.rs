@@ -0,0 +1,285 @@
+//! # Text encodings
+//!
+//! Hexadecimal encoding and validated decoding of keys and signatures, e.g. to store them in
+//! configuration files, and the base64 codec used by the `pem` containers.
+
+use bincode;
+use failure::Fail;
+
+use crate::crypto::serde_impl::SerdeSecret;
+use crate::crypto::{PublicKey, PublicKeySet, SecretKeyShare, Signature, PK_SIZE, SIG_SIZE};
+
+/// An error decoding a value from a hexadecimal string.
+#[derive(Clone, Eq, PartialEq, Debug, Fail)]
+pub enum HexError {
+    /// The string contains a character that is not a hexadecimal digit.
+    #[fail(display = "Invalid hexadecimal digit at position {}", _0)]
+    InvalidDigit(usize),
+    /// The string has an odd number of digits.
+    #[fail(display = "Odd number of hexadecimal digits")]
+    OddLength,
+    /// The number of bytes doesn't match the type.
+    #[fail(display = "Expected {} bytes, found {}", _0, _1)]
+    InvalidLength(usize, usize),
+    // String because `bincode` and `threshold_crypto` errors lack `Eq` and `Clone`.
+    /// The bytes don't represent a valid value, e.g. not a valid group element.
+    #[fail(display = "Invalid value: {}", _0)]
+    InvalidValue(String),
+}
+
+/// Conversion to and from hexadecimal strings, e.g. to store keys in configuration files.
+///
+/// Decoding validates the value, in particular that group elements are on the curve.
+pub trait Hex: Sized {
+    /// Returns the value encoded as a lowercase hexadecimal string.
+    fn to_hex(&self) -> String;
+
+    /// Decodes a value from a hexadecimal string. Upper and lowercase digits are accepted.
+    fn from_hex(hex: &str) -> Result<Self, HexError>;
+}
+
+impl Hex for PublicKey {
+    fn to_hex(&self) -> String {
+        encode_hex(&self.to_bytes()[..])
+    }
+
+    fn from_hex(hex: &str) -> Result<Self, HexError> {
+        let mut bytes = [0u8; PK_SIZE];
+        copy_bytes(&decode_hex(hex)?, &mut bytes)?;
+        PublicKey::from_bytes(bytes).map_err(|err| HexError::InvalidValue(err.to_string()))
+    }
+}
+
+impl Hex for Signature {
+    fn to_hex(&self) -> String {
+        encode_hex(&self.to_bytes()[..])
+    }
+
+    fn from_hex(hex: &str) -> Result<Self, HexError> {
+        let mut bytes = [0u8; SIG_SIZE];
+        copy_bytes(&decode_hex(hex)?, &mut bytes)?;
+        Signature::from_bytes(bytes).map_err(|err| HexError::InvalidValue(err.to_string()))
+    }
+}
+
+impl Hex for PublicKeySet {
+    fn to_hex(&self) -> String {
+        encode_hex(&bincode::serialize(self).expect("serialize public key set"))
+    }
+
+    fn from_hex(hex: &str) -> Result<Self, HexError> {
+        bincode::deserialize(&decode_hex(hex)?)
+            .map_err(|err| HexError::InvalidValue(err.to_string()))
+    }
+}
+
+impl Hex for SecretKeyShare {
+    fn to_hex(&self) -> String {
+        encode_hex(&bincode::serialize(&SerdeSecret(self)).expect("serialize secret key share"))
+    }
+
+    fn from_hex(hex: &str) -> Result<Self, HexError> {
+        bincode::deserialize(&decode_hex(hex)?)
+            .map_err(|err| HexError::InvalidValue(err.to_string()))
+    }
+}
+
+/// Encodes the bytes as a lowercase hexadecimal string.
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decodes a hexadecimal string into bytes.
+pub(crate) fn decode_hex(hex: &str) -> Result<Vec<u8>, HexError> {
+    let digits = hex
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            c.to_digit(16)
+                .map(|d| d as u8)
+                .ok_or(HexError::InvalidDigit(i))
+        })
+        .collect::<Result<Vec<u8>, HexError>>()?;
+    if digits.len() % 2 != 0 {
+        return Err(HexError::OddLength);
+    }
+    Ok(digits
+        .chunks(2)
+        .map(|pair| pair[0] << 4 | pair[1])
+        .collect())
+}
+
+/// Copies `src` into `dest`, or returns an error if their lengths differ.
+fn copy_bytes(src: &[u8], dest: &mut [u8]) -> Result<(), HexError> {
+    if src.len() != dest.len() {
+        return Err(HexError::InvalidLength(dest.len(), src.len()));
+    }
+    dest.copy_from_slice(src);
+    Ok(())
+}
+
+/// The base64 alphabet.
+const BASE64_CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes the bytes as base64, with padding.
+pub(crate) fn encode_base64(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).cloned().unwrap_or(0),
+            chunk.get(2).cloned().unwrap_or(0),
+        ];
+        let indices = [
+            b[0] >> 2,
+            (b[0] & 0x03) << 4 | b[1] >> 4,
+            (b[1] & 0x0f) << 2 | b[2] >> 6,
+            b[2] & 0x3f,
+        ];
+        for (i, index) in indices.iter().enumerate() {
+            if i <= chunk.len() {
+                result.push(BASE64_CHARS[*index as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+/// Decodes padded base64, or returns `None` if it is not the canonical encoding of any bytes.
+///
+/// Only the exact output of `encode_base64` is accepted: Padding must complete the last group of
+/// four characters, and the unused low bits of the last character before the padding must be zero.
+/// Otherwise several strings would decode to the same bytes.
+pub(crate) fn decode_base64(base64: &str) -> Option<Vec<u8>> {
+    let chars = base64.as_bytes();
+    if chars.len() % 4 != 0 {
+        return None;
+    }
+    let padding = chars.iter().rev().take_while(|c| **c == b'=').count();
+    if padding > 2 {
+        return None;
+    }
+    let sextets = chars[..chars.len() - padding]
+        .iter()
+        .map(|c| {
+            BASE64_CHARS
+                .iter()
+                .position(|b| b == c)
+                .map(|pos| pos as u8)
+        })
+        .collect::<Option<Vec<u8>>>()?;
+    // With one padding character, the last two bits are unused, with two the last four.
+    let unused_bits = match padding {
+        0 => 0,
+        1 => 0x03,
+        _ => 0x0f,
+    };
+    if sextets.last().map_or(false, |s| s & unused_bits != 0) {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(sextets.len() * 3 / 4);
+    for chunk in sextets.chunks(4) {
+        let s = |i: usize| chunk.get(i).cloned().unwrap_or(0);
+        let group = [
+            s(0) << 2 | s(1) >> 4,
+            s(1) << 4 | s(2) >> 2,
+            s(2) << 6 | s(3),
+        ];
+        // A chunk of `k` sextets encodes `k - 1` bytes.
+        bytes.extend(&group[..chunk.len() - 1]);
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_base64, encode_base64, Hex, HexError};
+    use crate::crypto::{
+        PublicKey, PublicKeySet, SecretKey, SecretKeySet, SecretKeyShare, Signature,
+    };
+
+    #[test]
+    fn test_hex_round_trip() {
+        let mut rng = rand::thread_rng();
+        let sk_set = SecretKeySet::random(2, &mut rng);
+        let pk_set = sk_set.public_keys();
+        let sk_share = sk_set.secret_key_share(1);
+        let pk = pk_set.public_key();
+        let sk = SecretKey::random();
+        let sig = sk.sign("config");
+
+        assert_eq!(Ok(pk), PublicKey::from_hex(&pk.to_hex()));
+        assert_eq!(Ok(pk), PublicKey::from_hex(&pk.to_hex().to_uppercase()));
+        assert_eq!(Ok(pk_set.clone()), PublicKeySet::from_hex(&pk_set.to_hex()));
+        assert_eq!(
+            Ok(sk_share.clone()),
+            SecretKeyShare::from_hex(&sk_share.to_hex())
+        );
+        assert_eq!(Ok(sig.clone()), Signature::from_hex(&sig.to_hex()));
+        assert!(sk.public_key().verify(&sig, "config"));
+    }
+
+    #[test]
+    fn test_hex_malformed() {
+        let pk_hex = SecretKeySet::random(0, &mut rand::thread_rng())
+            .public_keys()
+            .public_key()
+            .to_hex();
+        assert_eq!(
+            Err(HexError::InvalidDigit(3)),
+            PublicKey::from_hex("abcxef")
+        );
+        assert_eq!(Err(HexError::OddLength), PublicKey::from_hex(&pk_hex[1..]));
+        match PublicKey::from_hex(&pk_hex[2..]) {
+            Err(HexError::InvalidLength(_, _)) => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        // The right length, but not a valid group element. Mock crypto can't detect this.
+        if cfg!(not(feature = "use-insecure-test-only-mock-crypto")) {
+            let invalid = format!("ff{}", &pk_hex[2..]);
+            match PublicKey::from_hex(&invalid) {
+                Err(HexError::InvalidValue(_)) => (),
+                result => panic!("unexpected result: {:?}", result),
+            }
+        }
+        match PublicKeySet::from_hex("00") {
+            Err(HexError::InvalidValue(_)) => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_base64() {
+        let cases: &[(&[u8], &str)] = &[
+            (b"", ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"fooba", "Zm9vYmE="),
+            (b"foobar", "Zm9vYmFy"),
+        ];
+        for (bytes, base64) in cases {
+            assert_eq!(*base64, encode_base64(bytes));
+            assert_eq!(Some(bytes.to_vec()), decode_base64(base64));
+        }
+        assert_eq!(None, decode_base64("Zm9"));
+        assert_eq!(None, decode_base64("Zm9*"));
+        assert_eq!(None, decode_base64("Z==="));
+        assert_eq!(None, decode_base64("Zm=v"));
+        assert_eq!(None, decode_base64("Zg==Zg=="));
+    }
+
+    #[test]
+    fn test_base64_non_canonical() {
+        // These decode to the same bytes as "Zg==" and "Zm8=", but have nonzero unused bits.
+        assert_eq!(None, decode_base64("Zh=="));
+        assert_eq!(None, decode_base64("Zm9="));
+        assert_eq!(None, decode_base64("Zm9vYh=="));
+        // Padding is only allowed if the bytes don't fill the last group.
+        assert_eq!(None, decode_base64("===="));
+        assert_eq!(None, decode_base64("Zm9vZg="));
+    }
+}
@@ -0,0 +1,288 @@
+//! A common error type for all algorithms.
+
+use failure::Fail;
+
+use crate::binary_agreement;
+#[cfg(feature = "broadcast")]
+use crate::broadcast;
+#[cfg(feature = "dynamic")]
+use crate::dynamic_honey_badger;
+#[cfg(feature = "honey-badger")]
+use crate::honey_badger;
+#[cfg(feature = "dynamic")]
+use crate::queueing_honey_badger;
+#[cfg(feature = "honey-badger")]
+use crate::subset;
+#[cfg(feature = "key-gen")]
+use crate::sync_key_gen;
+use crate::threshold_decrypt;
+use crate::threshold_sign;
+
+/// The category of an error, independent of the algorithm in which it occurred.
+///
+/// For nested errors, e.g. a `ThresholdSign` error inside a `BinaryAgreement` error, this is the
+/// category of the innermost one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// A message from a node that is not in the network was handled.
+    UnknownSender,
+    /// The input was invalid or redundant, or the instance was not ready for it.
+    InvalidInput,
+    /// A cryptographic operation failed, e.g. combining shares or verifying the result.
+    Crypto,
+    /// A value could not be serialized.
+    Serialization,
+    /// The instance was created with invalid parameters.
+    Configuration,
+    /// An epoch number would overflow.
+    EpochOverflow,
+}
+
+/// An error returned by any of the algorithms.
+///
+/// Each module has its own error type, and all of them convert into this one. The original error
+/// is kept as the cause, so the chain of nested errors can be traversed with `Fail::cause`, and
+/// `kind` categorizes it without matching on the module-specific variants.
+#[derive(Debug, Fail)]
+pub enum HbbftError {
+    /// A `Broadcast` error.
+    #[cfg(feature = "broadcast")]
+    #[fail(display = "Broadcast error: {}", _0)]
+    Broadcast(#[cause] broadcast::Error),
+    /// A `BinaryAgreement` error.
+    #[fail(display = "BinaryAgreement error: {}", _0)]
+    BinaryAgreement(#[cause] binary_agreement::Error),
+    /// A `ThresholdSign` error.
+    #[fail(display = "ThresholdSign error: {}", _0)]
+    ThresholdSign(#[cause] threshold_sign::Error),
+    /// A `ThresholdDecrypt` error.
+    #[fail(display = "ThresholdDecrypt error: {}", _0)]
+    ThresholdDecrypt(#[cause] threshold_decrypt::Error),
+    /// A `Subset` error.
+    #[cfg(feature = "honey-badger")]
+    #[fail(display = "Subset error: {}", _0)]
+    Subset(#[cause] subset::Error),
+    /// A `HoneyBadger` error.
+    #[cfg(feature = "honey-badger")]
+    #[fail(display = "HoneyBadger error: {}", _0)]
+    HoneyBadger(#[cause] honey_badger::Error),
+    /// A `DynamicHoneyBadger` error.
+    #[cfg(feature = "dynamic")]
+    #[fail(display = "DynamicHoneyBadger error: {}", _0)]
+    DynamicHoneyBadger(#[cause] dynamic_honey_badger::Error),
+    /// A `QueueingHoneyBadger` error.
+    #[cfg(feature = "dynamic")]
+    #[fail(display = "QueueingHoneyBadger error: {}", _0)]
+    QueueingHoneyBadger(#[cause] queueing_honey_badger::Error),
+    /// A `SyncKeyGen` error.
+    #[cfg(feature = "key-gen")]
+    #[fail(display = "SyncKeyGen error: {}", _0)]
+    SyncKeyGen(#[cause] sync_key_gen::Error),
+}
+
+impl HbbftError {
+    /// Returns the category of the innermost error.
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            #[cfg(feature = "broadcast")]
+            HbbftError::Broadcast(ref err) => broadcast_kind(err),
+            HbbftError::BinaryAgreement(ref err) => binary_agreement_kind(err),
+            HbbftError::ThresholdSign(ref err) => threshold_sign_kind(err),
+            HbbftError::ThresholdDecrypt(ref err) => threshold_decrypt_kind(err),
+            #[cfg(feature = "honey-badger")]
+            HbbftError::Subset(ref err) => subset_kind(err),
+            #[cfg(feature = "honey-badger")]
+            HbbftError::HoneyBadger(ref err) => honey_badger_kind(err),
+            #[cfg(feature = "dynamic")]
+            HbbftError::DynamicHoneyBadger(ref err) => dynamic_honey_badger_kind(err),
+            #[cfg(feature = "dynamic")]
+            HbbftError::QueueingHoneyBadger(ref err) => queueing_honey_badger_kind(err),
+            #[cfg(feature = "key-gen")]
+            HbbftError::SyncKeyGen(ref err) => sync_key_gen_kind(err),
+        }
+    }
+}
+
+#[cfg(feature = "broadcast")]
+impl From<broadcast::Error> for HbbftError {
+    fn from(err: broadcast::Error) -> HbbftError {
+        HbbftError::Broadcast(err)
+    }
+}
+
+impl From<binary_agreement::Error> for HbbftError {
+    fn from(err: binary_agreement::Error) -> HbbftError {
+        HbbftError::BinaryAgreement(err)
+    }
+}
+
+impl From<threshold_sign::Error> for HbbftError {
+    fn from(err: threshold_sign::Error) -> HbbftError {
+        HbbftError::ThresholdSign(err)
+    }
+}
+
+impl From<threshold_decrypt::Error> for HbbftError {
+    fn from(err: threshold_decrypt::Error) -> HbbftError {
+        HbbftError::ThresholdDecrypt(err)
+    }
+}
+
+#[cfg(feature = "honey-badger")]
+impl From<subset::Error> for HbbftError {
+    fn from(err: subset::Error) -> HbbftError {
+        HbbftError::Subset(err)
+    }
+}
+
+#[cfg(feature = "honey-badger")]
+impl From<honey_badger::Error> for HbbftError {
+    fn from(err: honey_badger::Error) -> HbbftError {
+        HbbftError::HoneyBadger(err)
+    }
+}
+
+#[cfg(feature = "dynamic")]
+impl From<dynamic_honey_badger::Error> for HbbftError {
+    fn from(err: dynamic_honey_badger::Error) -> HbbftError {
+        HbbftError::DynamicHoneyBadger(err)
+    }
+}
+
+#[cfg(feature = "dynamic")]
+impl From<queueing_honey_badger::Error> for HbbftError {
+    fn from(err: queueing_honey_badger::Error) -> HbbftError {
+        HbbftError::QueueingHoneyBadger(err)
+    }
+}
+
+#[cfg(feature = "key-gen")]
+impl From<sync_key_gen::Error> for HbbftError {
+    fn from(err: sync_key_gen::Error) -> HbbftError {
+        HbbftError::SyncKeyGen(err)
+    }
+}
+
+#[cfg(feature = "broadcast")]
+fn broadcast_kind(err: &broadcast::Error) -> ErrorKind {
+    use crate::broadcast::Error::*;
+    match *err {
+        InvalidNodeCount | InvalidShardCount | Encode(_) => ErrorKind::Configuration,
+        InstanceCannotPropose | MultipleInputs => ErrorKind::InvalidInput,
+        ProofConstructionFailed => ErrorKind::Crypto,
+        UnknownSender => ErrorKind::UnknownSender,
+    }
+}
+
+fn binary_agreement_kind(err: &binary_agreement::Error) -> ErrorKind {
+    use crate::binary_agreement::Error::*;
+    match *err {
+        HandleThresholdSign(ref err) | InvokeCoin(ref err) => threshold_sign_kind(err),
+        Serialize(_) => ErrorKind::Serialization,
+        EpochOverflow => ErrorKind::EpochOverflow,
+    }
+}
+
+fn threshold_sign_kind(err: &threshold_sign::Error) -> ErrorKind {
+    use crate::threshold_sign::Error::*;
+    match *err {
+        MultipleMessagesToSign | DocumentHashIsNone | NotTerminated | UnreleasedOutput => {
+            ErrorKind::InvalidInput
+        }
+        CombineAndVerifySigCrypto(_) | VerificationFailed => ErrorKind::Crypto,
+        UnknownSender => ErrorKind::UnknownSender,
+    }
+}
+
+fn threshold_decrypt_kind(err: &threshold_decrypt::Error) -> ErrorKind {
+    use crate::threshold_decrypt::Error::*;
+    match *err {
+        MultipleInputs(_) | InvalidCiphertext(_) | CiphertextIsNone => ErrorKind::InvalidInput,
+        Decryption(_) => ErrorKind::Crypto,
+        UnknownSender => ErrorKind::UnknownSender,
+    }
+}
+
+#[cfg(feature = "honey-badger")]
+fn subset_kind(err: &subset::Error) -> ErrorKind {
+    use crate::subset::Error::*;
+    match *err {
+        NewAgreement(ref err) | HandleAgreement(ref err) => binary_agreement_kind(err),
+        NewBroadcast(ref err) | HandleBroadcast(ref err) => broadcast_kind(err),
+        UnknownProposer => ErrorKind::UnknownSender,
+    }
+}
+
+#[cfg(feature = "honey-badger")]
+fn honey_badger_kind(err: &honey_badger::Error) -> ErrorKind {
+    use crate::honey_badger::Error::*;
+    match *err {
+        ProposeBincode(_) => ErrorKind::Serialization,
+        CreateSubset(ref err) | InputSubset(ref err) | HandleSubsetMessage(ref err) => {
+            subset_kind(err)
+        }
+        ThresholdDecrypt(ref err) => threshold_decrypt_kind(err),
+        UnknownSender => ErrorKind::UnknownSender,
+        EpochOverflow => ErrorKind::EpochOverflow,
+    }
+}
+
+#[cfg(feature = "dynamic")]
+fn dynamic_honey_badger_kind(err: &dynamic_honey_badger::Error) -> ErrorKind {
+    use crate::dynamic_honey_badger::Error::*;
+    match *err {
+        SerializeKeyGen(_) | SerializeVote(_) => ErrorKind::Serialization,
+        ProposeHoneyBadger(ref err) | HandleHoneyBadgerMessage(ref err) => honey_badger_kind(err),
+        SyncKeyGen(ref err) => sync_key_gen_kind(err),
+        UnknownSender => ErrorKind::UnknownSender,
+        EpochOverflow => ErrorKind::EpochOverflow,
+        InvalidJoinPlan => ErrorKind::Configuration,
+    }
+}
+
+#[cfg(feature = "dynamic")]
+fn queueing_honey_badger_kind(err: &queueing_honey_badger::Error) -> ErrorKind {
+    use crate::queueing_honey_badger::Error::*;
+    match *err {
+        Input(ref err) | HandleMessage(ref err) | Propose(ref err) | NewJoining(ref err) => {
+            dynamic_honey_badger_kind(err)
+        }
+    }
+}
+
+#[cfg(feature = "key-gen")]
+fn sync_key_gen_kind(err: &sync_key_gen::Error) -> ErrorKind {
+    use crate::sync_key_gen::Error::*;
+    match *err {
+        Creation(_) | Generation(_) => ErrorKind::Crypto,
+        UnknownSender => ErrorKind::UnknownSender,
+        InvalidDealers => ErrorKind::Configuration,
+        Serialize(_) => ErrorKind::Serialization,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use failure::Fail;
+
+    use super::{ErrorKind, HbbftError};
+    use crate::{binary_agreement, threshold_sign};
+
+    #[test]
+    fn test_threshold_sign_error_kind() {
+        // The top-level error has the same kind, whether the coin failed directly or inside Binary
+        // Agreement, and the original error is its cause.
+        let err = threshold_sign::Error::VerificationFailed;
+        assert_eq!(ErrorKind::Crypto, HbbftError::from(err.clone()).kind());
+        let ba_err = binary_agreement::Error::HandleThresholdSign(err.clone());
+        let top_err = HbbftError::from(ba_err.clone());
+        assert_eq!(ErrorKind::Crypto, top_err.kind());
+        let cause = top_err.cause().expect("cause");
+        assert_eq!(
+            Some(&ba_err),
+            cause.downcast_ref::<binary_agreement::Error>()
+        );
+        let root = cause.cause().expect("root cause");
+        assert_eq!(Some(&err), root.downcast_ref::<threshold_sign::Error>());
+    }
+}
@@ -0,0 +1,80 @@
+//! # Structured step events
+//!
+//! A `StepEvent` summarizes a `Step` for logging and monitoring: the outputs produced, the
+//! messages sent and the faults logged, tagged with the algorithm's name and the node's ID. It
+//! implements `Serialize`, so it can be converted to JSON or any other format supported by serde.
+//!
+//! Node IDs, messages and outputs are only required to implement `Debug`, so they are included in
+//! their debug representation.
+
+use std::fmt::Debug;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{Step, Target};
+
+/// A summary of a `Step`, suitable for structured logging.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepEvent {
+    /// The name of the algorithm that produced the step.
+    pub algorithm: String,
+    /// The ID of the node that produced the step.
+    pub node_id: String,
+    /// The outputs in the step.
+    pub outputs: Vec<String>,
+    /// The messages in the step.
+    pub messages: Vec<MessageEvent>,
+    /// The faults in the step's fault log.
+    pub faults: Vec<FaultEvent>,
+}
+
+/// A message that is sent as part of a step.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageEvent {
+    /// The recipient's ID, or `None` if the message is sent to all nodes.
+    pub target: Option<String>,
+    /// The message.
+    pub message: String,
+}
+
+/// A fault that is logged as part of a step.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FaultEvent {
+    /// The faulty node's ID.
+    pub node_id: String,
+    /// The kind of fault.
+    pub kind: String,
+}
+
+impl<M: Debug, O: Debug, N: Debug> Step<M, O, N> {
+    /// Returns a summary of this step, produced by the given algorithm in node `node_id`.
+    pub fn to_event(&self, algorithm: &str, node_id: &N) -> StepEvent {
+        let messages = self
+            .messages
+            .iter()
+            .map(|tm| MessageEvent {
+                target: match tm.target {
+                    Target::All => None,
+                    Target::Node(ref id) => Some(format!("{:?}", id)),
+                },
+                message: format!("{:?}", tm.message),
+            })
+            .collect();
+        let faults = self
+            .fault_log
+            .0
+            .iter()
+            .map(|fault| FaultEvent {
+                node_id: format!("{:?}", fault.node_id),
+                kind: format!("{:?}", fault.kind),
+            })
+            .collect();
+        StepEvent {
+            algorithm: algorithm.to_string(),
+            node_id: format!("{:?}", node_id),
+            outputs: self.output.iter().map(|o| format!("{:?}", o)).collect(),
+            messages,
+            faults,
+        }
+    }
+}
@@ -5,13 +5,19 @@
 //! calling algorithm via `DistAlgorihm`'s `.handle_input()` and
 //! `.handle_message()` trait methods.
 
+use std::collections::BTreeMap;
+
+#[cfg(feature = "key-gen")]
 pub use crate::sync_key_gen::{AckFault, PartFault};
+use crate::NodeIdT;
 
 /// Represents each reason why a node could be considered faulty.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum FaultKind {
     /// `Coin` received a signature share from an unverified sender.
     UnverifiedSignatureShareSender,
+    /// `Coin` received a malformed signature share, which was rejected without verifying it.
+    MalformedSignatureShare,
     /// `HoneyBadger` received a decryption share from an unverified sender.
     UnverifiedDecryptionShareSender,
     /// `HoneyBadger` received a decryption share for an unaccepted proposer.
@@ -36,6 +42,9 @@ pub enum FaultKind {
     InvalidProof,
     /// `Broadcast` received shards with valid proofs, that couldn't be decoded.
     BroadcastDecoding,
+    /// `Broadcast` received `Echo`s with two different root hashes from more than _f_ nodes each,
+    /// so the proposer sent `Value`s of different trees to correct nodes.
+    ProposerEquivocation,
     /// `HoneyBadger` could not deserialize bytes (i.e. a serialized Batch)
     /// from a given proposer into a vector of transactions.
     BatchDeserializationFailed,
@@ -56,8 +65,10 @@ pub enum FaultKind {
     /// with an invalid signature.
     IncorrectPayloadSignature,
     /// `DynamicHoneyBadger`/`SyncKeyGen` received an invalid `Ack` message.
+    #[cfg(feature = "key-gen")]
     SyncKeyGenAck(AckFault),
     /// `DynamicHoneyBadger`/`SyncKeyGen` received an invalid `Part` message.
+    #[cfg(feature = "key-gen")]
     SyncKeyGenPart(PartFault),
     /// `DynamicHoneyBadger` received a change vote with an invalid signature.
     InvalidVoteSignature,
@@ -75,6 +86,10 @@ pub enum FaultKind {
     MultipleTerm,
     /// `BinaryAgreement` received a message with an epoch too far ahead.
     AgreementEpoch,
+    /// A fault defined by an application that runs its own sub-protocols on top of this crate,
+    /// and logs their faults in the same `FaultLog`. The meaning of the code is up to the
+    /// application. None of the algorithms in this crate report this kind.
+    Application(u32),
 }
 
 /// A structure representing the context of a faulty node. This structure
@@ -144,3 +159,176 @@ impl<N> Default for FaultLog<N> {
         FaultLog(vec![])
     }
 }
+
+/// A running tally of the faults attributed to each node, e.g. over a whole session.
+///
+/// The user feeds it the fault logs of all steps. Once a node has been blamed for `threshold`
+/// faults, it is reported, so that e.g. a vote to remove it via `DynamicHoneyBadger` can be cast.
+#[derive(Debug, Clone)]
+pub struct FaultCounter<N> {
+    /// The number of faults per node.
+    counts: BTreeMap<N, usize>,
+    /// The number of faults at which a node is reported.
+    threshold: usize,
+    /// The maximum number _f_ of faulty nodes the network can tolerate, if known.
+    max_faulty: Option<usize>,
+}
+
+impl<N: NodeIdT> FaultCounter<N> {
+    /// Creates a new counter that reports nodes as soon as they are blamed for `threshold`
+    /// faults.
+    pub fn new(threshold: usize) -> Self {
+        FaultCounter {
+            counts: BTreeMap::new(),
+            threshold,
+            max_faulty: None,
+        }
+    }
+
+    /// Creates a new counter that reports nodes as soon as they are blamed for `threshold`
+    /// faults, in a network that tolerates up to `max_faulty` faulty nodes, usually
+    /// `NetworkInfo::num_faulty`. This enables `near_threshold`.
+    pub fn new_with_max_faulty(threshold: usize, max_faulty: usize) -> Self {
+        FaultCounter {
+            max_faulty: Some(max_faulty),
+            ..FaultCounter::new(threshold)
+        }
+    }
+
+    /// Adds the faults in the log to the tally, and calls `on_threshold` with the node ID and its
+    /// number of faults for every node that reached the threshold. Each node is only reported
+    /// once.
+    pub fn add_faults<F>(&mut self, fault_log: &FaultLog<N>, mut on_threshold: F)
+    where
+        F: FnMut(&N, usize),
+    {
+        for fault in &fault_log.0 {
+            let count = self.counts.entry(fault.node_id.clone()).or_insert(0);
+            *count += 1;
+            if *count == self.threshold {
+                on_threshold(&fault.node_id, *count);
+            }
+        }
+    }
+
+    /// Returns the number of faults the given node has been blamed for.
+    pub fn faults_for(&self, id: &N) -> usize {
+        self.counts.get(id).cloned().unwrap_or(0)
+    }
+
+    /// Resets the given node's count to zero, e.g. after it has been removed.
+    pub fn reset(&mut self, id: &N) {
+        self.counts.remove(id);
+    }
+
+    /// Returns the number of nodes that are suspected to be faulty, i.e. that have been blamed for
+    /// at least `threshold` faults and not been reset.
+    pub fn suspected_faulty(&self) -> usize {
+        self.counts
+            .values()
+            .filter(|count| **count >= self.threshold)
+            .count()
+    }
+
+    /// Returns `true` if the number of suspected nodes has reached the maximum number _f_ of
+    /// faulty nodes the network tolerates. Any further faulty node could then break the
+    /// algorithms' guarantees, so applications may want to react, e.g. with more conservative
+    /// timeouts, or by voting to remove the suspected nodes.
+    ///
+    /// If the network doesn't tolerate any faulty nodes, i.e. _f = 0_, a single suspected node is
+    /// required: Otherwise this would always be `true`. Always returns `false` if the counter was
+    /// created without `new_with_max_faulty`.
+    pub fn near_threshold(&self) -> bool {
+        self.max_faulty.map_or(false, |max_faulty| {
+            self.suspected_faulty() >= max_faulty.max(1)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fault, FaultCounter, FaultKind, FaultLog};
+    use crate::{Step, Target};
+
+    #[test]
+    fn test_fault_counter() {
+        let mut counter = FaultCounter::new(3);
+        let mut reported = Vec::new();
+        let steps: Vec<Step<(), (), usize>> = vec![
+            FaultLog::init(1, FaultKind::InvalidProof).into(),
+            Target::All.message(()).into(),
+            FaultLog(vec![
+                Fault::new(2, FaultKind::MultipleEchos),
+                Fault::new(1, FaultKind::MultipleReadys),
+            ])
+            .into(),
+            FaultLog::init(1, FaultKind::UnverifiedSignatureShareSender).into(),
+            FaultLog::init(1, FaultKind::DuplicateBVal).into(),
+        ];
+        for step in &steps {
+            counter.add_faults(&step.fault_log, |id, count| reported.push((*id, count)));
+        }
+        assert_eq!(4, counter.faults_for(&1));
+        assert_eq!(1, counter.faults_for(&2));
+        assert_eq!(0, counter.faults_for(&3));
+        assert_eq!(vec![(1, 3)], reported);
+
+        counter.reset(&1);
+        assert_eq!(0, counter.faults_for(&1));
+    }
+
+    #[test]
+    fn test_fault_counter_near_threshold() {
+        // A network of seven nodes tolerates two faulty ones.
+        let mut counter = FaultCounter::new_with_max_faulty(2, 2);
+        for &(id, suspected, near) in &[
+            (1, 0, false),
+            (1, 1, false),
+            (2, 1, false),
+            (1, 1, false),
+            (2, 2, true),
+            (3, 2, true),
+        ] {
+            counter.add_faults(&FaultLog::init(id, FaultKind::InvalidProof), |_, _| ());
+            assert_eq!(suspected, counter.suspected_faulty());
+            assert_eq!(near, counter.near_threshold());
+        }
+
+        // Once a suspected node has been removed, the network is below the threshold again.
+        counter.reset(&2);
+        assert_eq!(1, counter.suspected_faulty());
+        assert!(!counter.near_threshold());
+
+        // If no faulty nodes are tolerated, the first suspected one is already too many.
+        let mut counter = FaultCounter::new_with_max_faulty(1, 0);
+        assert!(!counter.near_threshold());
+        counter.add_faults(&FaultLog::init(1, FaultKind::InvalidProof), |_, _| ());
+        assert!(counter.near_threshold());
+
+        // Without a known tolerance, the counter never reports being near the threshold.
+        let mut counter = FaultCounter::new(1);
+        counter.add_faults(&FaultLog::init(1, FaultKind::InvalidProof), |_, _| ());
+        assert_eq!(1, counter.suspected_faulty());
+        assert!(!counter.near_threshold());
+    }
+
+    #[test]
+    fn test_application_fault() {
+        /// An application-defined fault: the node sent an invalid request in the application's own
+        /// sub-protocol.
+        const INVALID_REQUEST: u32 = 7;
+
+        // The application adds its own fault to an algorithm's, and counts them together.
+        let mut fault_log = FaultLog::init(1, FaultKind::UnverifiedSignatureShareSender);
+        fault_log.append(2, FaultKind::Application(INVALID_REQUEST));
+        let expected = vec![
+            Fault::new(1, FaultKind::UnverifiedSignatureShareSender),
+            Fault::new(2, FaultKind::Application(INVALID_REQUEST)),
+        ];
+        assert_eq!(expected, fault_log.0);
+        let mut counter = FaultCounter::new(1);
+        let mut reported = Vec::new();
+        counter.add_faults(&fault_log, |id, _| reported.push(*id));
+        assert_eq!(vec![1, 2], reported);
+    }
+}
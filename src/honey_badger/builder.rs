@@ -1,10 +1,10 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
 use std::marker::PhantomData;
 use std::sync::Arc;
 
 use serde::{de::DeserializeOwned, Serialize};
 
-use super::{EncryptionSchedule, HoneyBadger, Params, SubsetHandlingStrategy};
+use super::{EncryptionSchedule, FairnessPolicy, HoneyBadger, Params, SubsetHandlingStrategy};
 use crate::{Contribution, NetworkInfo, NodeIdT};
 
 /// A Honey Badger builder, to configure the parameters and create new instances of `HoneyBadger`.
@@ -18,6 +18,8 @@ pub struct HoneyBadgerBuilder<C, N> {
     epoch: u64,
     /// Parameters controlling Honey Badger's behavior and performance.
     params: Params,
+    /// The policy that decides which agreed contributions are included in the batches.
+    fairness: Option<Arc<dyn FairnessPolicy<N>>>,
     _phantom: PhantomData<C>,
 }
 
@@ -34,6 +36,7 @@ where
             session_id: 0,
             epoch: 0,
             params: Params::default(),
+            fairness: None,
             _phantom: PhantomData,
         }
     }
@@ -54,6 +57,10 @@ where
     }
 
     /// Sets the maximum number of future epochs for which we handle messages simultaneously.
+    ///
+    /// This bounds the memory used for buffering: Messages for later epochs are discarded and
+    /// their sender is reported as faulty. Messages for past epochs are discarded, too, so the
+    /// state of an epoch is dropped as soon as it has output its batch.
     pub fn max_future_epochs(&mut self, max_future_epochs: u64) -> &mut Self {
         self.params.max_future_epochs = max_future_epochs;
         self
@@ -80,6 +87,16 @@ where
         self
     }
 
+    /// Sets the policy that decides which of an epoch's agreed contributions are included in its
+    /// batch. All nodes must use the same policy, and start with the same epoch, since the policy
+    /// takes into account the batches output by this instance.
+    ///
+    /// By default, all agreed contributions are included.
+    pub fn fairness_policy<P: FairnessPolicy<N> + 'static>(&mut self, policy: P) -> &mut Self {
+        self.fairness = Some(Arc::new(policy));
+        self
+    }
+
     /// Creates a new Honey Badger instance.
     pub fn build(&mut self) -> HoneyBadger<C, N> {
         HoneyBadger {
@@ -89,6 +106,8 @@ where
             has_input: false,
             epochs: BTreeMap::new(),
             params: self.params.clone(),
+            fairness: self.fairness.clone(),
+            fairness_history: VecDeque::new(),
         }
     }
 }
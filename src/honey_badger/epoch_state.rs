@@ -17,7 +17,7 @@ use super::{Batch, Error, MessageContent, Result, Step};
 use crate::fault_log::{Fault, FaultKind, FaultLog};
 use crate::subset::{self as cs, Subset, SubsetOutput};
 use crate::threshold_decrypt::{self as td, ThresholdDecrypt};
-use crate::{Contribution, NetworkInfo, NodeIdT};
+use crate::{Contribution, Diagnose, DiagnosticTree, NetworkInfo, NodeIdT};
 
 type CsStep<N> = cs::Step<N>;
 
@@ -190,6 +190,8 @@ pub struct EpochState<C, N> {
     subset_handler: SubsetHandler<N>,
     /// Whether contributions should be encrypted in this epoch.
     require_decryption: bool,
+    /// The number of messages handled in this epoch.
+    messages_received: usize,
     _phantom: PhantomData<C>,
 }
 
@@ -216,6 +218,7 @@ where
             accepted_proposers: Default::default(),
             subset_handler: subset_handling_strategy.into(),
             require_decryption,
+            messages_received: 0,
             _phantom: PhantomData,
         })
     }
@@ -242,12 +245,48 @@ where
         self.subset.received_proposals()
     }
 
+    /// Returns the number of messages handled in this epoch.
+    pub fn messages_received(&self) -> usize {
+        self.messages_received
+    }
+
+    /// Returns the state of the epoch's `Subset` instance, while it is running, and the number of
+    /// completed decryptions.
+    pub fn diagnose(&self) -> DiagnosticTree {
+        let decrypted = self
+            .decryption
+            .values()
+            .filter(|state| match state {
+                DecryptionState::Ongoing(_) => false,
+                DecryptionState::Complete(_) => true,
+            })
+            .count();
+        let label = |subset_state: String| {
+            DiagnosticTree::new(format!(
+                "epoch {}: {}, {} of {} decryptions complete",
+                self.epoch,
+                subset_state,
+                decrypted,
+                self.decryption.len()
+            ))
+        };
+        match self.subset {
+            SubsetState::Ongoing(ref cs) => {
+                label("subset ongoing".to_string()).with_child(cs.diagnose())
+            }
+            SubsetState::Complete(ref ids) => {
+                label(format!("subset complete with {} proposers", ids.len()))
+            }
+        }
+    }
+
     /// Handles a message for the Subset or a Threshold Decrypt instance.
     pub fn handle_message_content(
         &mut self,
         sender_id: &N,
         content: MessageContent<N>,
     ) -> Result<Step<C, N>> {
+        self.messages_received += 1;
         match content {
             MessageContent::Subset(cs_msg) => {
                 let cs_step = self.subset.handle_message(sender_id, cs_msg)?;
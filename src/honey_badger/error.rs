@@ -12,19 +12,22 @@ pub enum Error {
     ProposeBincode(bincode::ErrorKind),
     /// Failed to instantiate `Subset`.
     #[fail(display = "Failed to instantiate Subset: {}", _0)]
-    CreateSubset(subset::Error),
+    CreateSubset(#[cause] subset::Error),
     /// Failed to input contribution to `Subset`.
     #[fail(display = "Failed to input contribution to Subset: {}", _0)]
-    InputSubset(subset::Error),
+    InputSubset(#[cause] subset::Error),
     /// Failed to handle `Subset` message.
     #[fail(display = "Failed to handle Subset message: {}", _0)]
-    HandleSubsetMessage(subset::Error),
+    HandleSubsetMessage(#[cause] subset::Error),
     /// Failed to decrypt a contribution.
     #[fail(display = "Threshold decryption error: {}", _0)]
-    ThresholdDecrypt(threshold_decrypt::Error),
+    ThresholdDecrypt(#[cause] threshold_decrypt::Error),
     /// Unknown sender
     #[fail(display = "Unknown sender")]
     UnknownSender,
+    /// The epoch number would overflow.
+    #[fail(display = "Epoch number overflow")]
+    EpochOverflow,
 }
 
 /// The result of `HoneyBadger` handling an input or a message.
@@ -0,0 +1,84 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::Debug;
+
+/// A policy that decides which of an epoch's agreed contributions are included in its batch.
+///
+/// The policy is applied after `Subset` and decryption, so all correct nodes call it with the same
+/// arguments. It must be deterministic, i.e. only depend on these arguments and its own
+/// configuration, so that they all output the same batch. The contributions that are not
+/// selected are dropped; a proposer that keeps its transactions queued until they are output, like
+/// `QueueingHoneyBadger`, will propose them again in a later epoch.
+pub trait FairnessPolicy<N>: Debug + Send + Sync {
+    /// Returns the number of past batches whose contribution sizes are passed to `select`.
+    fn window(&self) -> usize;
+
+    /// Returns the IDs of the proposers whose contributions are included in the batch.
+    ///
+    /// `sizes` contains the serialized size in bytes of each proposer's contribution in the current
+    /// epoch. `history` contains the sizes of the contributions that were _included_ in the up to
+    /// `window()` preceding batches, oldest first.
+    fn select(
+        &self,
+        history: &VecDeque<BTreeMap<N, usize>>,
+        sizes: &BTreeMap<N, usize>,
+    ) -> BTreeSet<N>;
+}
+
+/// A policy that caps each proposer's share of the batches over a window of epochs.
+///
+/// A contribution is included if its proposer's total included bytes in the window, together with
+/// the contribution itself, would be at most `max_ratio` times the smallest such total among the
+/// current epoch's proposers. So if one proposer keeps proposing large contributions, it is only
+/// included in some epochs, until the others have caught up.
+///
+/// A contribution that is more than `max_ratio` times larger than what any other proposer
+/// contributes within the whole window is never included.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ByteShareCap {
+    /// The number of past batches that are taken into account.
+    window: usize,
+    /// The maximum ratio between the totals of two proposers.
+    max_ratio: usize,
+}
+
+impl ByteShareCap {
+    /// Creates a policy that considers the last `window` batches and allows every proposer at most
+    /// `max_ratio` times the bytes of the least represented one. A `max_ratio` of `0` is treated
+    /// as `1`.
+    pub fn new(window: usize, max_ratio: usize) -> Self {
+        ByteShareCap {
+            window,
+            max_ratio: max_ratio.max(1),
+        }
+    }
+}
+
+impl<N: Ord + Clone + Debug + Send + Sync> FairnessPolicy<N> for ByteShareCap {
+    fn window(&self) -> usize {
+        self.window
+    }
+
+    fn select(
+        &self,
+        history: &VecDeque<BTreeMap<N, usize>>,
+        sizes: &BTreeMap<N, usize>,
+    ) -> BTreeSet<N> {
+        let totals: BTreeMap<&N, usize> = sizes
+            .iter()
+            .map(|(id, size)| {
+                let past: usize = history.iter().filter_map(|batch| batch.get(id)).sum();
+                (id, past.saturating_add(*size))
+            })
+            .collect();
+        let min_total = match totals.values().min() {
+            Some(min_total) => *min_total,
+            None => return BTreeSet::new(),
+        };
+        let cap = min_total.saturating_mul(self.max_ratio);
+        totals
+            .into_iter()
+            .filter(|(_, total)| *total <= cap)
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+}
@@ -1,15 +1,22 @@
 use std::collections::btree_map::Entry;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
+use std::mem;
 use std::sync::Arc;
 
 use derivative::Derivative;
+use log::debug;
 use rand::Rng;
 use serde::{de::DeserializeOwned, Serialize};
 use serde_derive::{Deserialize, Serialize};
 
 use super::epoch_state::EpochState;
-use super::{Batch, Error, HoneyBadgerBuilder, Message, Result};
-use crate::{Contribution, DistAlgorithm, Fault, FaultKind, NetworkInfo, NodeIdT};
+use super::{Batch, Error, FairnessPolicy, HoneyBadgerBuilder, Message, Result};
+use crate::crypto::{PK_SIZE, SIG_SIZE};
+use crate::util::RoundCost;
+use crate::{binary_agreement, broadcast};
+use crate::{
+    Contribution, Diagnose, DiagnosticTree, DistAlgorithm, Fault, FaultKind, NetworkInfo, NodeIdT,
+};
 
 use super::Params;
 
@@ -30,6 +37,11 @@ pub struct HoneyBadger<C, N> {
     pub(super) epochs: BTreeMap<u64, EpochState<C, N>>,
     /// Parameters controlling Honey Badger's behavior and performance.
     pub(super) params: Params,
+    /// The policy that decides which agreed contributions are included in the batches.
+    pub(super) fairness: Option<Arc<dyn FairnessPolicy<N>>>,
+    /// The sizes of the contributions included in the most recent batches, oldest first, as far
+    /// as the `fairness` policy takes them into account.
+    pub(super) fairness_history: VecDeque<BTreeMap<N, usize>>,
 }
 
 /// A `HoneyBadger` step, possibly containing multiple outputs.
@@ -68,6 +80,30 @@ where
     }
 }
 
+impl<C, N> Diagnose for HoneyBadger<C, N>
+where
+    C: Contribution + Serialize + DeserializeOwned,
+    N: NodeIdT,
+{
+    /// Returns the current epoch, with one child for each epoch that is in progress.
+    fn diagnose(&self) -> DiagnosticTree {
+        let mut tree = DiagnosticTree::new(format!(
+            "HoneyBadger epoch {}: {}, {} epochs in progress",
+            self.epoch,
+            if self.has_input {
+                "proposed"
+            } else {
+                "not proposed"
+            },
+            self.epochs.len()
+        ));
+        for epoch_state in self.epochs.values() {
+            tree = tree.with_child(epoch_state.diagnose());
+        }
+        tree
+    }
+}
+
 impl<C, N> HoneyBadger<C, N>
 where
     C: Contribution + Serialize + DeserializeOwned,
@@ -102,7 +138,7 @@ where
             return Err(Error::UnknownSender);
         }
         let Message { epoch, content } = message;
-        if epoch > self.epoch + self.params.max_future_epochs {
+        if epoch > self.epoch.saturating_add(self.params.max_future_epochs) {
             Ok(Fault::new(sender_id.clone(), FaultKind::UnexpectedHbMessageEpoch).into())
         } else if epoch < self.epoch {
             // The message is late; discard it.
@@ -131,6 +167,24 @@ where
         self.epoch
     }
 
+    /// Returns the number of epochs for which state is currently kept in memory. This is at most
+    /// `max_future_epochs + 1`: the current epoch and the future ones for which messages are
+    /// accepted. The state of an epoch is dropped as soon as its batch has been output, since all
+    /// later messages for that epoch are discarded.
+    pub fn epochs_in_memory(&self) -> usize {
+        self.epochs.len()
+    }
+
+    /// Returns the number of messages received for epochs whose batch hasn't been output yet,
+    /// i.e. the current epoch and the future ones. Their effects are kept in memory until the
+    /// epoch's batch is output, at which point they are dropped, together with the epoch's state.
+    pub fn buffered_message_count(&self) -> usize {
+        self.epochs
+            .values()
+            .map(EpochState::messages_received)
+            .sum()
+    }
+
     /// Returns the number of validators from which we have already received a proposal for the
     /// current epoch.
     ///
@@ -146,29 +200,73 @@ where
     }
 
     /// Increments the epoch number and clears any state that is local to the finished epoch.
-    fn update_epoch(&mut self) {
-        // Clear the state of the old epoch.
-        self.epochs.remove(&self.epoch);
-        self.epoch += 1;
+    ///
+    /// Returns an error if the epoch number would overflow.
+    fn update_epoch(&mut self) -> Result<()> {
+        let next_epoch = self.epoch.checked_add(1).ok_or(Error::EpochOverflow)?;
+        // Clear the state of the old epoch, and of any earlier ones: Their messages are discarded
+        // from now on, so a state that was left behind could never be dropped otherwise.
+        self.epochs = self.epochs.split_off(&next_epoch);
+        self.epoch = next_epoch;
         self.has_input = false;
+        Ok(())
     }
 
     /// Tries to decrypt contributions from all proposers and output those in a batch.
     fn try_output_batches(&mut self) -> Result<Step<C, N>> {
         let mut step = Step::default();
-        while let Some((batch, fault_log)) = self
+        while let Some((mut batch, fault_log)) = self
             .epochs
             .get(&self.epoch)
             .and_then(EpochState::try_output_batch)
         {
-            // Queue the output and advance the epoch.
+            self.apply_fairness(&mut batch);
+            // Advance the epoch and queue the output.
+            self.update_epoch()?;
             step.output.push(batch);
             step.fault_log.extend(fault_log);
-            self.update_epoch();
         }
         Ok(step)
     }
 
+    /// Removes the contributions that the fairness policy doesn't select, if there is a policy,
+    /// and records the sizes of the remaining ones.
+    fn apply_fairness(&mut self, batch: &mut Batch<C, N>) {
+        let policy = match self.fairness {
+            Some(ref policy) => policy.clone(),
+            None => return,
+        };
+        let sizes: BTreeMap<N, usize> = batch
+            .contributions
+            .iter()
+            .map(|(id, contrib)| {
+                let size = bincode::serialized_size(contrib).unwrap_or(0) as usize;
+                (id.clone(), size)
+            })
+            .collect();
+        let selected = policy.select(&self.fairness_history, &sizes);
+        let contributions = mem::replace(&mut batch.contributions, BTreeMap::new());
+        batch.contributions = contributions
+            .into_iter()
+            .filter(|(id, _)| selected.contains(id))
+            .collect();
+        debug!(
+            "{:?} Epoch {}: Fairness policy selected {} of {} contributions.",
+            self.our_id(),
+            batch.epoch,
+            batch.contributions.len(),
+            sizes.len()
+        );
+        let included = sizes
+            .into_iter()
+            .filter(|(id, _)| selected.contains(id))
+            .collect();
+        self.fairness_history.push_back(included);
+        while self.fairness_history.len() > policy.window() {
+            self.fairness_history.pop_front();
+        }
+    }
+
     /// Returns a mutable reference to the state of the given `epoch`. Initializes a new one, if it
     /// doesn't exist yet.
     fn epoch_state_mut(&mut self, epoch: u64) -> Result<&mut EpochState<C, N>> {
@@ -220,3 +318,28 @@ impl EncryptionSchedule {
         }
     }
 }
+
+/// Returns an estimate of the traffic in an epoch among `num_nodes` validators, up to `num_faulty`
+/// of which can be faulty, if each validator's contribution serializes to `contribution_len`
+/// bytes and contributions are encrypted.
+///
+/// This is the sum of one `Broadcast` of each encrypted contribution, one `BinaryAgreement`
+/// instance for each that decides in the first round, and a decryption share for each
+/// contribution from each validator. The wrapping of the messages, which adds the epoch and
+/// proposer IDs, isn't included. Instances that need more rounds to decide cause additional
+/// traffic, see `binary_agreement::estimated_messages`.
+///
+/// Returns `None` if the arguments are not valid for `Broadcast`, i.e. if `num_nodes` is not
+/// greater than `2 * num_faulty`.
+pub fn estimated_messages(
+    num_nodes: usize,
+    num_faulty: usize,
+    contribution_len: usize,
+) -> Option<RoundCost> {
+    // A ciphertext consists of a `G1` element, the serialized encrypted bytes and a `G2` element.
+    let ciphertext_len = PK_SIZE + 8 + contribution_len + SIG_SIZE;
+    let broadcasts = broadcast::estimated_messages(num_nodes, num_faulty, ciphertext_len)?;
+    let agreements = binary_agreement::estimated_messages(num_nodes, 1);
+    let decryption = RoundCost::multicasts(num_nodes, num_nodes, PK_SIZE);
+    Some((broadcasts + agreements) * num_nodes + decryption)
+}
@@ -17,6 +17,12 @@
 //! proposers must be faulty -, and the remaining ones are output as the new batch. The next epoch
 //! begins as soon as the validators propose new contributions again.
 //!
+//! Honey Badger never starts an epoch by itself: If no validator proposes and no messages arrive,
+//! it sends no messages at all. So an idle network is quiescent as long as the application only
+//! proposes when it has content, or when `received_proposals` shows that others want to move on.
+//! The price for that is liveness: A batch is only guaranteed to be output once at least _N - f_
+//! validators have proposed in that epoch. `QueueingHoneyBadger` follows exactly this policy.
+//!
 //! So it is essentially an endlessly repeating `Subset`, but with the proposed values
 //! encrypted. The encryption makes it harder for an attacker to try and censor a particular value
 //! by influencing the set of proposals that make it into the subset, because they don't
@@ -26,6 +32,7 @@ mod batch;
 mod builder;
 mod epoch_state;
 mod error;
+mod fairness;
 mod honey_badger;
 mod message;
 mod params;
@@ -34,6 +41,7 @@ pub use self::batch::Batch;
 pub use self::builder::HoneyBadgerBuilder;
 pub use self::epoch_state::SubsetHandlingStrategy;
 pub use self::error::{Error, Result};
-pub use self::honey_badger::{EncryptionSchedule, HoneyBadger, Step};
+pub use self::fairness::{ByteShareCap, FairnessPolicy};
+pub use self::honey_badger::{estimated_messages, EncryptionSchedule, HoneyBadger, Step};
 pub use self::message::{Message, MessageContent};
 pub use self::params::Params;
@@ -0,0 +1,160 @@
+//! # Lagrange interpolation (low-level)
+//!
+//! _This is an advanced API for implementing new threshold protocols, e.g. proactive secret
+//! sharing or resharing a key set to a new committee. Applications that only sign, decrypt or
+//! run consensus never need it._
+//!
+//! The threshold schemes of `threshold_crypto` are based on polynomials: A key set with threshold
+//! _t_ is a polynomial _p_ of degree _t_, whose coefficients are multiplied with a group
+//! generator. The master key is the value at `0`, and the key share with index `i` is the value
+//! at `i + 1`. Combining signature or decryption shares just interpolates such a polynomial's
+//! value at `0` from _t + 1_ of its values.
+//!
+//! `interpolate_at` exposes that computation for any group element type and any evaluation point.
+//! The values of a committed polynomial at arbitrary points can be computed directly with
+//! `crypto::poly::Commitment::evaluate`.
+//!
+//! Note that the points here are the raw evaluation points, so a share with index `i` must be
+//! passed at `i + 1`. The interpolation doesn't know the polynomial's degree: Given _k_ points,
+//! it computes the unique polynomial of degree less than _k_ through them. If fewer than _t + 1_
+//! values of a polynomial of degree _t_ are passed, the result is well-defined but wrong.
+
+use failure::Fail;
+
+use crate::crypto::{Fr, IntoFr};
+use crate::pairing::{CurveProjective, Field};
+
+/// An interpolation error.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Fail)]
+pub enum Error {
+    /// No points were given.
+    #[fail(display = "No points to interpolate")]
+    NoPoints,
+    /// The same evaluation point was given twice.
+    #[fail(display = "Duplicate evaluation point {}", _0)]
+    DuplicatePoint(u64),
+}
+
+/// An interpolation result.
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+/// Returns the value at `x` of the unique polynomial of degree less than `points.len()` that has
+/// the value `y` at each of the `(x, y)` pairs in `points`.
+pub fn interpolate_at<C>(points: &[(u64, C)], x: u64) -> Result<C>
+where
+    C: CurveProjective<Scalar = Fr>,
+{
+    if points.is_empty() {
+        return Err(Error::NoPoints);
+    }
+    for (i, (xi, _)) in points.iter().enumerate() {
+        if points[..i].iter().any(|(xj, _)| xj == xi) {
+            return Err(Error::DuplicatePoint(*xi));
+        }
+    }
+    let x_fr = x.into_fr();
+    let mut result = C::zero();
+    for (i, (xi, yi)) in points.iter().enumerate() {
+        // The Lagrange basis polynomial that is `1` at `xi` and `0` at the other points.
+        let xi_fr = xi.into_fr();
+        let mut numer = Fr::one();
+        let mut denom = Fr::one();
+        for (j, (xj, _)) in points.iter().enumerate() {
+            if i == j {
+                continue;
+            }
+            let xj_fr = xj.into_fr();
+            let mut diff = x_fr;
+            diff.sub_assign(&xj_fr);
+            numer.mul_assign(&diff);
+            let mut diff = xi_fr;
+            diff.sub_assign(&xj_fr);
+            denom.mul_assign(&diff);
+        }
+        // The points are distinct, so `denom` is nonzero, unless two of them are congruent
+        // modulo the field's order. That can only happen with the mock crypto's tiny field.
+        let denom_inv = denom.inverse().ok_or(Error::DuplicatePoint(*xi))?;
+        numer.mul_assign(&denom_inv);
+        let mut summand = *yi;
+        summand.mul_assign(numer);
+        result.add_assign(&summand);
+    }
+    Ok(result)
+}
+
+/// Returns the value at `0` of the unique polynomial of degree less than `points.len()` that has
+/// the value `y` at each of the `(x, y)` pairs in `points`. For key shares, this is the value
+/// corresponding to the master key.
+pub fn interpolate<C>(points: &[(u64, C)]) -> Result<C>
+where
+    C: CurveProjective<Scalar = Fr>,
+{
+    interpolate_at(points, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{interpolate, interpolate_at, Error};
+    use crate::crypto::poly::Poly;
+    use crate::crypto::{hash_g2, SecretKeySet, G1, G2};
+    use crate::pairing::{CurveAffine, CurveProjective};
+
+    #[test]
+    fn test_interpolate_commitment() {
+        let mut rng = rand::thread_rng();
+        let commit = Poly::random(3, &mut rng).commitment();
+        let points: Vec<(u64, G1)> = [2, 3, 5, 7]
+            .iter()
+            .map(|&x| (x, commit.evaluate(x)))
+            .collect();
+        for &x in &[0, 1, 2, 4, 100, u64::max_value()] {
+            let value = interpolate_at(&points, x).expect("interpolate");
+            assert_eq!(commit.evaluate(x), value, "x = {}", x);
+        }
+        assert_eq!(
+            commit.evaluate(0),
+            interpolate(&points).expect("interpolate")
+        );
+        // More points than necessary give the same result, too few a different one.
+        let more_points: Vec<(u64, G1)> = (10..20).map(|x| (x, commit.evaluate(x))).collect();
+        assert_eq!(Ok(commit.evaluate(0)), interpolate(&more_points));
+        assert_ne!(Ok(commit.evaluate(0)), interpolate(&points[..3]));
+    }
+
+    #[test]
+    fn test_interpolate_signature_shares() {
+        // A signature share is the document's hash times the key share, so the shares are values
+        // at `index + 1` of a polynomial in G2, whose value at `0` is the group signature.
+        let mut rng = rand::thread_rng();
+        let poly = Poly::random(2, &mut rng);
+        let doc = b"interpolated";
+        let hash = hash_g2(doc);
+        let points: Vec<(u64, G2)> = [0u64, 4, 6]
+            .iter()
+            .map(|&i| {
+                let mut share = hash;
+                share.mul_assign(poly.evaluate(i + 1));
+                (i + 1, share)
+            })
+            .collect();
+        let sk_set = SecretKeySet::from(poly);
+        let shares: Vec<_> = points
+            .iter()
+            .map(|(x, _)| (x - 1, sk_set.secret_key_share(x - 1).sign(doc)))
+            .collect();
+        let sig = sk_set
+            .public_keys()
+            .combine_signatures(shares.iter().map(|(i, share)| (*i, share)))
+            .expect("combine signatures");
+        let value = interpolate(&points).expect("interpolate").into_affine();
+        assert_eq!(sig.to_bytes()[..], *value.into_compressed().as_ref());
+    }
+
+    #[test]
+    fn test_interpolate_errors() {
+        let commit = Poly::random(1, &mut rand::thread_rng()).commitment();
+        assert_eq!(Err(Error::NoPoints), interpolate::<G1>(&[]));
+        let points = [(3, commit.evaluate(3)), (3, commit.evaluate(3))];
+        assert_eq!(Err(Error::DuplicatePoint(3)), interpolate(&points));
+    }
+}
@@ -31,7 +31,11 @@
 //! serialize, sign and send the messages. The application is required to call `handle_message` for
 //! every correctly signed message from a peer. Methods return a [Step](struct.Step.html) data
 //! structure, which contain messages that need to be sent, fault logs indicating misbehaving
-//! peers, and outputs.
+//! peers, and outputs. Alternatively, the application can implement the
+//! [Transport](trait.Transport.html) trait for its network layer and let [run](fn.run.html) pass
+//! the messages between the algorithm and the transport. Algorithms that must run one after the
+//! other, e.g. key generation followed by consensus, can be chained into a single one with
+//! [Sequence](struct.Sequence.html).
 //!
 //! The network must contain a number of nodes that are known to each other by some unique
 //! identifiers (IDs), which is a generic type argument to the algorithms. Where applicable, the
@@ -108,6 +112,22 @@
 //! Unlike the other algorithms, this one is _not_ asynchronous: All nodes must handle the same
 //! messages, in the same order.
 //!
+//! ## Features
+//!
+//! The algorithms that depend on each other are grouped into Cargo features, all of which are
+//! enabled by default. Applications that only need some of them can use `default-features = false`
+//! to shrink their builds. Binary Agreement, Threshold Sign and Threshold Decrypt are always
+//! available.
+//!
+//! * `broadcast`: Broadcast, and the Reed-Solomon erasure coding dependency.
+//! * `honey-badger`: Subset and Honey Badger. Implies `broadcast`.
+//! * `key-gen`: Synchronous Key Generation.
+//! * `dynamic`: Dynamic Honey Badger and Queueing Honey Badger. Implies `honey-badger` and
+//!   `key-gen`.
+//!
+//! Light clients that only need to check a coin, i.e. a combined signature, against the public
+//! master key can use the functions in [coin](coin/index.html) with `default-features = false`.
+//!
 //! ## Serialization
 //!
 //! `hbbft` supports [serde](https://serde.rs/): All message types implement the `Serialize` and
@@ -121,28 +141,53 @@
 
 pub extern crate threshold_crypto as crypto;
 
+mod error;
 mod fault_log;
 mod messaging;
 mod network_info;
 mod traits;
 
+pub mod beacon;
 pub mod binary_agreement;
+#[cfg(feature = "broadcast")]
 pub mod broadcast;
+pub mod coin;
+pub mod crypto_util;
+#[cfg(feature = "dynamic")]
 pub mod dynamic_honey_badger;
+pub mod encoding;
+pub mod event;
+#[cfg(feature = "honey-badger")]
 pub mod honey_badger;
+pub mod interpolation;
+pub mod pem;
+#[cfg(feature = "dynamic")]
 pub mod queueing_honey_badger;
+pub mod recorder;
 pub mod sender_queue;
+pub mod strict;
+#[cfg(feature = "honey-badger")]
 pub mod subset;
+#[cfg(feature = "key-gen")]
 pub mod sync_key_gen;
+pub mod test_vectors;
+#[cfg(any(test, feature = "testing"))]
+#[doc(hidden)]
+pub mod testing;
 pub mod threshold_decrypt;
 pub mod threshold_sign;
 pub mod transaction_queue;
 pub mod util;
 
 pub use crate::crypto::pairing;
-pub use crate::fault_log::{Fault, FaultKind, FaultLog};
-pub use crate::messaging::{SourcedMessage, Target, TargetedMessage};
-pub use crate::network_info::NetworkInfo;
+pub use crate::error::{ErrorKind, HbbftError};
+pub use crate::fault_log::{Fault, FaultCounter, FaultKind, FaultLog};
+pub use crate::messaging::{
+    frame, run, InstanceMessage, MemoryTransport, Sequence, SequenceError, SequenceMessage,
+    SequenceResult, SequenceStep, SourcedMessage, Target, TargetedMessage, Transport,
+};
+pub use crate::network_info::{NetworkInfo, NetworkInfoError};
 pub use crate::traits::{
-    Contribution, DaStep, DistAlgorithm, Epoched, Message, NodeIdT, SessionIdT, Step,
+    AlgoMetrics, Contribution, DaStep, Diagnose, DiagnosticTree, DistAlgorithm, Epoched,
+    InstanceId, Instrumented, Message, NodeIdT, SessionIdT, Step,
 };
@@ -1,3 +1,16 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::convert;
+use std::fmt::{self, Debug, Display};
+use std::sync::{Arc, Mutex};
+
+use failure::Fail;
+use rand::Rng;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{DaStep, Diagnose, DiagnosticTree, DistAlgorithm, NodeIdT, Step};
+
+pub mod frame;
+
 /// Message sent by a given source.
 #[derive(Clone, Debug)]
 pub struct SourcedMessage<M, N> {
@@ -8,7 +21,10 @@ pub struct SourcedMessage<M, N> {
 }
 
 /// The intended recipient(s) of a message.
-#[derive(Clone, Debug, PartialEq, Eq)]
+///
+/// `All` is serialized as just the variant's tag, without a list of recipients, so its size
+/// doesn't depend on the number of nodes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Target<N> {
     /// The message must be sent to all remote nodes.
     All,
@@ -27,7 +43,7 @@ impl<N> Target<N> {
 }
 
 /// Message with a designated target.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct TargetedMessage<M, N> {
     /// The node or nodes that this message must be delivered to.
     pub target: Target<N>,
@@ -44,3 +60,404 @@ impl<M, N> TargetedMessage<M, N> {
         }
     }
 }
+
+impl<M, N> TargetedMessage<M, N> {
+    /// Tags the message with the identifier of the instance that sent it, preserving the target.
+    pub fn with_instance_id(self, instance_id: &[u8]) -> TargetedMessage<InstanceMessage<M>, N> {
+        self.map(|message| InstanceMessage {
+            instance_id: instance_id.to_vec(),
+            message,
+        })
+    }
+}
+
+/// A message tagged with the identifier of the algorithm instance it belongs to, as returned by
+/// `InstanceId::instance_id`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstanceMessage<M> {
+    /// The identifier of the sending and receiving instances.
+    pub instance_id: Vec<u8>,
+    /// The instance's message.
+    pub message: M,
+}
+
+impl<M, N: PartialEq> TargetedMessage<M, N> {
+    /// Coalesces the messages with the same target into a single batch each, so that they can be
+    /// serialized and sent to the target as one payload. Transports can additionally compress the
+    /// serialized batch.
+    ///
+    /// The messages in each batch keep their relative order, and the batches are ordered by the
+    /// first occurrence of their target. The order of messages with _different_ targets is not
+    /// preserved, which is fine since none of the algorithms rely on it. The recipient passes the
+    /// batch to `DistAlgorithm::handle_message_batch`.
+    pub fn batch<I>(msgs: I) -> Vec<TargetedMessage<Vec<M>, N>>
+    where
+        I: IntoIterator<Item = Self>,
+    {
+        let mut batches: Vec<TargetedMessage<Vec<M>, N>> = Vec::new();
+        for msg in msgs {
+            match batches.iter_mut().find(|batch| batch.target == msg.target) {
+                Some(batch) => batch.message.push(msg.message),
+                None => batches.push(msg.target.message(vec![msg.message])),
+            }
+        }
+        batches
+    }
+}
+
+/// A network layer that delivers an algorithm's messages to their targets, and receives the
+/// messages other nodes sent to us.
+///
+/// The transport is responsible for serializing, signing and authenticating the messages: `recv`
+/// must only return messages that were really sent by the given node.
+pub trait Transport<N, M> {
+    /// Sends the message to the given target.
+    fn send(&mut self, target: Target<N>, msg: M);
+
+    /// Returns the next received message together with its sender's ID, or `None` if no message
+    /// is available at the moment.
+    fn recv(&mut self) -> Option<(N, M)>;
+}
+
+/// Sends the messages of `step` via the transport, then handles received messages until the
+/// algorithm terminates or the transport has no more messages, sending all messages the algorithm
+/// produces in response.
+///
+/// `step` is usually the result of the algorithm's `handle_input` call, or an empty step. The
+/// returned step contains all outputs and faults, but no messages, since those have already been
+/// sent. If the algorithm has not terminated yet, `run` can be called again with an empty step
+/// once new messages have arrived.
+pub fn run<D, T, R>(
+    algo: &mut D,
+    mut step: DaStep<D>,
+    transport: &mut T,
+    rng: &mut R,
+) -> Result<DaStep<D>, D::Error>
+where
+    D: DistAlgorithm,
+    T: Transport<D::NodeId, D::Message>,
+    R: Rng,
+{
+    let mut result = Step::default();
+    loop {
+        for msg in step.messages.drain(..) {
+            transport.send(msg.target, msg.message);
+        }
+        result.extend(step);
+        if algo.terminated() {
+            return Ok(result);
+        }
+        let (sender_id, msg) = match transport.recv() {
+            Some(received) => received,
+            None => return Ok(result),
+        };
+        step = algo.handle_message(&sender_id, msg, rng)?;
+    }
+}
+
+/// The message queues of a network of `MemoryTransport`s, by recipient.
+type Queues<N, M> = Arc<Mutex<BTreeMap<N, VecDeque<(N, M)>>>>;
+
+/// A transport that delivers messages to other nodes in the same process, in the order they were
+/// sent. This is intended for tests and examples.
+#[derive(Debug)]
+pub struct MemoryTransport<N, M> {
+    /// Our node ID.
+    our_id: N,
+    /// The queues of all nodes in the network.
+    queues: Queues<N, M>,
+}
+
+impl<N: NodeIdT, M: Clone> MemoryTransport<N, M> {
+    /// Returns connected transports for a network with the given node IDs.
+    pub fn network<I>(ids: I) -> BTreeMap<N, Self>
+    where
+        I: IntoIterator<Item = N>,
+    {
+        let queue_map: BTreeMap<N, VecDeque<(N, M)>> =
+            ids.into_iter().map(|id| (id, VecDeque::new())).collect();
+        let ids: Vec<N> = queue_map.keys().cloned().collect();
+        let queues = Arc::new(Mutex::new(queue_map));
+        ids.into_iter()
+            .map(|our_id| {
+                let transport = MemoryTransport {
+                    our_id: our_id.clone(),
+                    queues: queues.clone(),
+                };
+                (our_id, transport)
+            })
+            .collect()
+    }
+
+    /// Returns `true` if no messages are waiting to be received by any node.
+    pub fn is_idle(&self) -> bool {
+        let queues = self.queues.lock().expect("poisoned transport queues");
+        queues.values().all(VecDeque::is_empty)
+    }
+}
+
+impl<N: NodeIdT, M: Clone> Transport<N, M> for MemoryTransport<N, M> {
+    fn send(&mut self, target: Target<N>, msg: M) {
+        let mut queues = self.queues.lock().expect("poisoned transport queues");
+        match target {
+            Target::All => {
+                for (id, queue) in queues.iter_mut() {
+                    if *id != self.our_id {
+                        queue.push_back((self.our_id.clone(), msg.clone()));
+                    }
+                }
+            }
+            Target::Node(id) => {
+                if let Some(queue) = queues.get_mut(&id) {
+                    queue.push_back((self.our_id.clone(), msg));
+                }
+            }
+        }
+    }
+
+    fn recv(&mut self) -> Option<(N, M)> {
+        let mut queues = self.queues.lock().expect("poisoned transport queues");
+        queues.get_mut(&self.our_id)?.pop_front()
+    }
+}
+
+/// A message of a `Sequence`, tagged with the algorithm it belongs to.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SequenceMessage<MA, MB> {
+    /// A message of the first algorithm.
+    First(MA),
+    /// A message of the second algorithm.
+    Second(MB),
+}
+
+/// An error returned by one of the algorithms in a `Sequence`.
+#[derive(Debug)]
+pub enum SequenceError<EA, EB> {
+    /// The first algorithm returned an error.
+    First(EA),
+    /// The second algorithm returned an error.
+    Second(EB),
+}
+
+impl<EA: Display, EB: Display> Display for SequenceError<EA, EB> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SequenceError::First(ref err) => write!(f, "First algorithm error: {}", err),
+            SequenceError::Second(ref err) => write!(f, "Second algorithm error: {}", err),
+        }
+    }
+}
+
+impl<EA: Fail, EB: Fail> Fail for SequenceError<EA, EB> {
+    fn cause(&self) -> Option<&dyn Fail> {
+        match *self {
+            SequenceError::First(ref err) => Some(err),
+            SequenceError::Second(ref err) => Some(err),
+        }
+    }
+}
+
+/// Two algorithms that run one after the other: The input is passed to the first one, and its
+/// first output is converted into the second one's input. The second algorithm's outputs are the
+/// outputs of the sequence, and it terminates when the second algorithm does.
+///
+/// Both instances are created upfront, so that messages for the second algorithm from nodes that
+/// are already further ahead are not lost. Messages are wrapped in `SequenceMessage` and routed to
+/// the algorithm they belong to, also after the handoff, since other nodes may still need ours to
+/// complete the first algorithm.
+pub struct Sequence<A, B, F> {
+    /// The first algorithm.
+    first: A,
+    /// The second algorithm.
+    second: B,
+    /// The conversion from the first algorithm's output to the second one's input.
+    map_output: F,
+    /// Whether the first algorithm has output, and the second one has received its input.
+    handed_off: bool,
+}
+
+impl<A: Debug, B: Debug, F> Debug for Sequence<A, B, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Sequence")
+            .field("first", &self.first)
+            .field("second", &self.second)
+            .field("handed_off", &self.handed_off)
+            .finish()
+    }
+}
+
+/// A `Sequence` step.
+pub type SequenceStep<A, B> = Step<
+    SequenceMessage<<A as DistAlgorithm>::Message, <B as DistAlgorithm>::Message>,
+    <B as DistAlgorithm>::Output,
+    <A as DistAlgorithm>::NodeId,
+>;
+
+/// A `Sequence` result.
+pub type SequenceResult<A, B> = Result<
+    SequenceStep<A, B>,
+    SequenceError<<A as DistAlgorithm>::Error, <B as DistAlgorithm>::Error>,
+>;
+
+impl<A, B, F> Sequence<A, B, F>
+where
+    A: DistAlgorithm,
+    B: DistAlgorithm<NodeId = A::NodeId>,
+    F: FnMut(A::Output) -> B::Input + Send + Sync,
+{
+    /// Creates a sequence that runs `first`, then passes its output, converted by `map_output`, to
+    /// `second`.
+    pub fn new(first: A, second: B, map_output: F) -> Self {
+        Sequence {
+            first,
+            second,
+            map_output,
+            handed_off: false,
+        }
+    }
+
+    /// Returns a reference to the first algorithm.
+    pub fn first(&self) -> &A {
+        &self.first
+    }
+
+    /// Returns a reference to the second algorithm.
+    pub fn second(&self) -> &B {
+        &self.second
+    }
+
+    /// Returns `true` if the first algorithm has output, and its output was passed to the second.
+    pub fn handed_off(&self) -> bool {
+        self.handed_off
+    }
+
+    /// Converts a step of the first algorithm and, on its first output, hands off to the second.
+    fn process_first<R: Rng>(&mut self, step: DaStep<A>, rng: &mut R) -> SequenceResult<A, B> {
+        let mut seq_step = Step::default();
+        let outputs = seq_step.extend_with(step, SequenceMessage::First);
+        if self.handed_off {
+            return Ok(seq_step);
+        }
+        if let Some(output) = outputs.into_iter().next() {
+            self.handed_off = true;
+            let input = (self.map_output)(output);
+            let step = self
+                .second
+                .handle_input(input, rng)
+                .map_err(SequenceError::Second)?;
+            seq_step.extend(Self::convert_second(step));
+        }
+        Ok(seq_step)
+    }
+
+    /// Converts a step of the second algorithm.
+    fn convert_second(step: DaStep<B>) -> SequenceStep<A, B> {
+        step.map(convert::identity, SequenceMessage::Second)
+    }
+}
+
+impl<A: Diagnose, B: Diagnose, F> Diagnose for Sequence<A, B, F> {
+    /// Returns whether the first algorithm has handed off, with both algorithms as children.
+    fn diagnose(&self) -> DiagnosticTree {
+        let state = if self.handed_off {
+            "handed off"
+        } else {
+            "running the first algorithm"
+        };
+        DiagnosticTree::new(format!("Sequence: {}", state))
+            .with_child(self.first.diagnose())
+            .with_child(self.second.diagnose())
+    }
+}
+
+impl<A, B, F> DistAlgorithm for Sequence<A, B, F>
+where
+    A: DistAlgorithm,
+    B: DistAlgorithm<NodeId = A::NodeId>,
+    F: FnMut(A::Output) -> B::Input + Send + Sync,
+{
+    type NodeId = A::NodeId;
+    type Input = A::Input;
+    type Output = B::Output;
+    type Message = SequenceMessage<A::Message, B::Message>;
+    type Error = SequenceError<A::Error, B::Error>;
+
+    fn handle_input<R: Rng>(&mut self, input: Self::Input, rng: &mut R) -> SequenceResult<A, B> {
+        let step = self
+            .first
+            .handle_input(input, rng)
+            .map_err(SequenceError::First)?;
+        self.process_first(step, rng)
+    }
+
+    fn handle_message<R: Rng>(
+        &mut self,
+        sender_id: &Self::NodeId,
+        message: Self::Message,
+        rng: &mut R,
+    ) -> SequenceResult<A, B> {
+        match message {
+            SequenceMessage::First(msg) => {
+                let step = self
+                    .first
+                    .handle_message(sender_id, msg, rng)
+                    .map_err(SequenceError::First)?;
+                self.process_first(step, rng)
+            }
+            SequenceMessage::Second(msg) => {
+                let step = self
+                    .second
+                    .handle_message(sender_id, msg, rng)
+                    .map_err(SequenceError::Second)?;
+                Ok(Self::convert_second(step))
+            }
+        }
+    }
+
+    fn terminated(&self) -> bool {
+        self.second.terminated()
+    }
+
+    fn our_id(&self) -> &Self::NodeId {
+        self.first.our_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{Target, TargetedMessage};
+    use crate::testing;
+    use crate::threshold_sign::{Message, ThresholdSign};
+
+    #[test]
+    fn test_target_all_serialized_size() {
+        // Only the variant's four-byte tag is serialized, regardless of the node ID type.
+        let all_ids = bincode::serialize(&Target::<usize>::All).expect("serialize");
+        let all_names = bincode::serialize(&Target::<String>::All).expect("serialize");
+        assert_eq!(4, all_ids.len());
+        assert_eq!(all_ids, all_names);
+        let node = bincode::serialize(&Target::Node(7usize)).expect("serialize");
+        assert_eq!(4 + 8, node.len());
+
+        // A multicast signature share has the same size in networks of any size.
+        let sizes: Vec<usize> = [(4, 1), (16, 5), (64, 21)]
+            .iter()
+            .map(|&(num_nodes, num_faulty)| {
+                let (_, netinfos) = testing::setup(num_nodes, num_faulty);
+                let netinfo = Arc::new(netinfos[0].clone());
+                let mut ts = ThresholdSign::new_with_document(netinfo, "All").expect("document");
+                let step = ts.sign().expect("sign");
+                assert_eq!(1, step.messages.len());
+                assert_eq!(Target::All, step.messages[0].target);
+                let bytes = bincode::serialize(&step.messages[0]).expect("serialize");
+                let decoded: TargetedMessage<Message, usize> =
+                    bincode::deserialize(&bytes).expect("deserialize");
+                assert_eq!(step.messages[0], decoded);
+                bytes.len()
+            })
+            .collect();
+        assert_eq!(vec![sizes[0]; 3], sizes);
+    }
+}
@@ -0,0 +1,141 @@
+//! # Length-delimited message framing
+//!
+//! Helpers for sending messages over a byte stream, e.g. a TCP connection. Each frame consists of
+//! the length of the serialized message as a big-endian `u32`, followed by the message itself,
+//! serialized with `bincode`.
+//!
+//! Both sides enforce a maximum frame length: A peer could otherwise announce a huge frame and
+//! make the recipient allocate memory for it. The limit must be chosen large enough for the
+//! largest legitimate message, e.g. a `Broadcast` shard of the largest expected contribution.
+
+use std::io::{self, Read, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use failure::Fail;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// The default maximum length of a frame's payload: 16 MiB.
+pub const DEFAULT_MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// An error writing or reading a frame.
+#[derive(Debug, Fail)]
+pub enum Error {
+    /// The underlying reader or writer failed. Reaching the end of the stream inside a frame is
+    /// reported as `io::ErrorKind::UnexpectedEof`.
+    #[fail(display = "I/O error: {}", _0)]
+    Io(#[cause] io::Error),
+    /// The message could not be serialized or deserialized.
+    #[fail(display = "Serialization error: {}", _0)]
+    Bincode(bincode::ErrorKind),
+    /// The frame's payload length exceeds the maximum.
+    #[fail(display = "Frame of {} bytes exceeds the maximum of {}", _0, _1)]
+    FrameTooLarge(usize, usize),
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<bincode::Error> for Error {
+    fn from(err: bincode::Error) -> Error {
+        Error::Bincode(*err)
+    }
+}
+
+/// A framing result.
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+/// Serializes the message and writes it to `writer` as a single frame. Returns an error and writes
+/// nothing if the serialized message is longer than `max_len` bytes.
+pub fn write_message<W: Write, M: Serialize>(
+    writer: &mut W,
+    message: &M,
+    max_len: usize,
+) -> Result<()> {
+    let bytes = bincode::serialize(message)?;
+    let max_len = max_len.min(u32::max_value() as usize);
+    if bytes.len() > max_len {
+        return Err(Error::FrameTooLarge(bytes.len(), max_len));
+    }
+    writer.write_u32::<BigEndian>(bytes.len() as u32)?;
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+/// Reads a single frame from `reader` and deserializes the message.
+///
+/// The length prefix is checked before the payload is read, so if it exceeds `max_len`, an error
+/// is returned without allocating any memory for it. In that case, the stream cannot be
+/// resynchronized and the connection should be closed.
+pub fn read_message<R: Read, M: DeserializeOwned>(reader: &mut R, max_len: usize) -> Result<M> {
+    let len = reader.read_u32::<BigEndian>()? as usize;
+    if len > max_len {
+        return Err(Error::FrameTooLarge(len, max_len));
+    }
+    let mut bytes = vec![0; len];
+    reader.read_exact(&mut bytes)?;
+    Ok(bincode::deserialize(&bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor};
+
+    use super::{read_message, write_message, Error, DEFAULT_MAX_FRAME_LEN};
+    use crate::binary_agreement::{bool_set, MessageContent, SbvMessage};
+
+    #[test]
+    fn test_frame_round_trip() {
+        let messages = vec![
+            MessageContent::Term(true).with_epoch(3),
+            MessageContent::SbvBroadcast(SbvMessage::Aux(false)).with_epoch(0),
+            MessageContent::Conf(bool_set::BOTH).with_epoch(u64::max_value()),
+        ];
+        let mut stream = Vec::new();
+        for msg in &messages {
+            write_message(&mut stream, msg, DEFAULT_MAX_FRAME_LEN).expect("write");
+        }
+        let mut reader = Cursor::new(stream);
+        for msg in &messages {
+            let read = read_message(&mut reader, DEFAULT_MAX_FRAME_LEN).expect("read");
+            assert_eq!(*msg, read);
+        }
+        // At the end of the stream, there is no further frame.
+        match read_message::<_, Vec<u8>>(&mut reader, DEFAULT_MAX_FRAME_LEN) {
+            Err(Error::Io(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_frame_too_large() {
+        let msg = vec![0u8; 100];
+        // The serialized vector has an eight-byte length prefix.
+        let mut stream = Vec::new();
+        match write_message(&mut stream, &msg, 107) {
+            Err(Error::FrameTooLarge(108, 107)) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(stream.is_empty());
+        write_message(&mut stream, &msg, 108).expect("write");
+        assert_eq!(4 + 108, stream.len());
+
+        // The reader rejects the frame based on its header.
+        match read_message::<_, Vec<u8>>(&mut Cursor::new(&stream), 107) {
+            Err(Error::FrameTooLarge(108, 107)) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+        // A header announcing a huge frame is rejected without trying to read it.
+        let bomb = [0xff, 0xff, 0xff, 0xff];
+        match read_message::<_, Vec<u8>>(&mut Cursor::new(&bomb[..]), DEFAULT_MAX_FRAME_LEN) {
+            Err(Error::FrameTooLarge(len, DEFAULT_MAX_FRAME_LEN)) => {
+                assert_eq!(u32::max_value() as usize, len)
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        let read: Vec<u8> = read_message(&mut Cursor::new(&stream), 108).expect("read");
+        assert_eq!(msg, read);
+    }
+}
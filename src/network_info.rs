@@ -1,9 +1,29 @@
 use std::collections::{BTreeMap, BTreeSet};
 
 use crate::crypto::{self, PublicKey, PublicKeySet, PublicKeyShare, SecretKey, SecretKeyShare};
+use failure::Fail;
 use rand;
 
-use crate::{util, NodeIdT};
+use crate::crypto_util::VerifySecretShare;
+use crate::util;
+use crate::NodeIdT;
+
+/// An error creating a `NetworkInfo`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Fail)]
+pub enum NetworkInfoError {
+    /// Our secret key share does not match the public key share at our node index.
+    #[fail(
+        display = "Secret key share does not match the public key share with index {}",
+        _0
+    )]
+    KeyShareMismatch(usize),
+    /// We have a secret key share but are not a validator.
+    #[fail(display = "Secret key share given to a node that is not a validator")]
+    UnexpectedSecretKeyShare,
+    /// The given node index is not our position in the sorted list of validator IDs.
+    #[fail(display = "Node index {} given, but our ID has index {}", _0, _1)]
+    IndexMismatch(usize, usize),
+}
 
 /// Common data shared between algorithms: the nodes' IDs and key shares.
 #[derive(Debug, Clone)]
@@ -37,6 +57,12 @@ impl<N: NodeIdT> NetworkInfo<N> {
     /// keys in the `public_keys` map, and their secret key share must match their share in the
     /// `public_key_set`.
     ///
+    /// Networks with fewer than four validators cannot tolerate any faulty node: `num_faulty()` is
+    /// `0`, and every algorithm is only correct if _all_ validators are. In particular a single
+    /// validator's threshold share suffices to sign or decrypt, so e.g. the common coin is known to
+    /// each validator as soon as it has signed. This is fine for tests and trivial single-node
+    /// setups, where consensus is trivial, but provides no Byzantine fault tolerance.
+    ///
     /// # Panics
     ///
     /// Panics if `public_keys` is empty.
@@ -74,6 +100,68 @@ impl<N: NodeIdT> NetworkInfo<N> {
         }
     }
 
+    /// Creates a new `NetworkInfo` like `new`, but verifies that our secret key share matches the
+    /// public key share with our node index.
+    ///
+    /// Node indices are assigned in the order of the IDs in `public_keys`, and signature and
+    /// decryption shares are combined using these indices. If the key set was dealt in a different
+    /// order, combining would fail only once the shares are used, e.g. for the common coin. This
+    /// catches the mismatch at construction instead.
+    pub fn try_new<SKS: Into<Option<SecretKeyShare>>>(
+        our_id: N,
+        secret_key_share: SKS,
+        public_key_set: PublicKeySet,
+        secret_key: SecretKey,
+        public_keys: BTreeMap<N, PublicKey>,
+    ) -> Result<Self, NetworkInfoError> {
+        let netinfo = NetworkInfo::new(
+            our_id,
+            secret_key_share,
+            public_key_set,
+            secret_key,
+            public_keys,
+        );
+        if let Some(ref sks) = netinfo.secret_key_share {
+            let idx = netinfo
+                .node_index(&netinfo.our_id)
+                .ok_or(NetworkInfoError::UnexpectedSecretKeyShare)?;
+            if !netinfo.public_key_set.verify_secret_share(idx as u64, sks) {
+                return Err(NetworkInfoError::KeyShareMismatch(idx));
+            }
+        }
+        Ok(netinfo)
+    }
+
+    /// Creates a new validator's `NetworkInfo` from the shared public key set and our own secret
+    /// key share with index `our_index`, e.g. if the key set was fetched from a registry.
+    ///
+    /// The validators' public key shares are derived from `public_key_set` by index, so only the
+    /// validators' public keys are needed, which `DynamicHoneyBadger` uses to sign votes and to
+    /// encrypt key generation messages. Returns an error if `our_index` is not our position in
+    /// the sorted IDs in `public_keys`, which is how the algorithms assign indices, or if our
+    /// secret key share is not the one with that index in the set.
+    pub fn from_key_set(
+        our_id: N,
+        our_index: usize,
+        our_secret_share: SecretKeyShare,
+        public_key_set: PublicKeySet,
+        secret_key: SecretKey,
+        public_keys: BTreeMap<N, PublicKey>,
+    ) -> Result<Self, NetworkInfoError> {
+        if let Some(idx) = public_keys.keys().position(|id| *id == our_id) {
+            if idx != our_index {
+                return Err(NetworkInfoError::IndexMismatch(our_index, idx));
+            }
+        }
+        NetworkInfo::try_new(
+            our_id,
+            our_secret_share,
+            public_key_set,
+            secret_key,
+            public_keys,
+        )
+    }
+
     /// The ID of the node the algorithm runs on.
     #[inline]
     pub fn our_id(&self) -> &N {
@@ -93,7 +181,7 @@ impl<N: NodeIdT> NetworkInfo<N> {
     }
 
     /// The maximum number _f_ of faulty, Byzantine nodes up to which Honey Badger is guaranteed to
-    /// be correct.
+    /// be correct. This is `0` if there are fewer than four validators.
     #[inline]
     pub fn num_faulty(&self) -> usize {
         self.num_faulty
@@ -119,6 +207,34 @@ impl<N: NodeIdT> NetworkInfo<N> {
         &self.secret_key
     }
 
+    /// Returns the threshold _t_ of the public key set: any _t + 1_ signature or decryption shares
+    /// can be combined. In a correctly set up network this is equal to `num_faulty()`.
+    #[inline]
+    pub fn threshold(&self) -> usize {
+        self.public_key_set.threshold()
+    }
+
+    /// Returns `true` if `count` signature or decryption shares are enough to combine them, i.e. if
+    /// `count` exceeds the `threshold()`.
+    #[inline]
+    pub fn has_coin_threshold(&self, count: usize) -> bool {
+        count > self.threshold()
+    }
+
+    /// Returns `true` if `count` is at least _2 f + 1_: Among that many nodes, the correct ones
+    /// form a majority.
+    #[inline]
+    pub fn has_strong_quorum(&self, count: usize) -> bool {
+        count > 2 * self.num_faulty
+    }
+
+    /// Returns `true` if `count` is at least _f + 1_: Among that many nodes, at least one is
+    /// correct.
+    #[inline]
+    pub fn has_weak_quorum(&self, count: usize) -> bool {
+        count > self.num_faulty
+    }
+
     /// Returns the public key set for threshold cryptography.
     #[inline]
     pub fn public_key_set(&self) -> &PublicKeySet {
@@ -131,6 +247,13 @@ impl<N: NodeIdT> NetworkInfo<N> {
         self.public_key_shares.get(id)
     }
 
+    /// Returns the public key share of the validator with the given index, or `None` if there is
+    /// no such validator.
+    pub fn public_key_share_by_index(&self, idx: u64) -> Option<&PublicKeyShare> {
+        let id = self.public_keys.keys().nth(idx as usize)?;
+        self.public_key_shares.get(id)
+    }
+
     /// Returns a map of all node IDs to their public key shares.
     #[inline]
     pub fn public_key_share_map(&self) -> &BTreeMap<N, PublicKeyShare> {
@@ -214,3 +337,148 @@ impl<N: NodeIdT> NetworkInfo<N> {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+
+    use rand::Rng;
+
+    use super::{NetworkInfo, NetworkInfoError};
+    use crate::crypto::{PublicKey, SecretKey, SecretKeySet};
+    use crate::threshold_sign::ThresholdSign;
+
+    #[test]
+    fn test_quorums() {
+        let mut rng = rand::thread_rng();
+        // Network sizes and the corresponding number of faulty nodes.
+        for &(num_nodes, f) in &[(1, 0), (3, 0), (4, 1), (6, 1), (7, 2), (10, 3)] {
+            let netinfos = NetworkInfo::generate_map(0..num_nodes, &mut rng).expect("netinfos");
+            let netinfo = &netinfos[&0];
+            assert_eq!(f, netinfo.num_faulty());
+            assert!(!netinfo.has_weak_quorum(f));
+            assert!(netinfo.has_weak_quorum(f + 1));
+            assert!(!netinfo.has_strong_quorum(2 * f));
+            assert!(netinfo.has_strong_quorum(2 * f + 1));
+            assert!(!netinfo.has_coin_threshold(f));
+            assert!(netinfo.has_coin_threshold(f + 1));
+            assert!(netinfo.has_strong_quorum(netinfo.num_correct()));
+        }
+    }
+
+    #[test]
+    fn test_try_new_index_mismatch() {
+        let mut rng = rand::thread_rng();
+        let sk_set = SecretKeySet::random(1, &mut rng);
+        let sec_keys: BTreeMap<usize, SecretKey> = (0..4).map(|id| (id, rng.gen())).collect();
+        let pub_keys: BTreeMap<usize, PublicKey> = sec_keys
+            .iter()
+            .map(|(id, sk)| (*id, sk.public_key()))
+            .collect();
+        let try_new = |id: usize, share_idx: usize| {
+            NetworkInfo::try_new(
+                id,
+                sk_set.secret_key_share(share_idx),
+                sk_set.public_keys(),
+                sec_keys[&id].clone(),
+                pub_keys.clone(),
+            )
+        };
+
+        // Correctly dealt shares are accepted.
+        for id in 0..4 {
+            assert!(try_new(id, id).is_ok());
+        }
+        // Shares dealt in a permuted order are rejected.
+        for id in 0..4 {
+            let err = try_new(id, (id + 1) % 4).expect_err("permuted share accepted");
+            assert_eq!(NetworkInfoError::KeyShareMismatch(id), err);
+        }
+        // A node that is not in `public_keys` must not have a share.
+        let observer = NetworkInfo::try_new(
+            4,
+            sk_set.secret_key_share(4),
+            sk_set.public_keys(),
+            rng.gen(),
+            pub_keys.clone(),
+        );
+        assert_eq!(
+            NetworkInfoError::UnexpectedSecretKeyShare,
+            observer.expect_err("observer with share accepted")
+        );
+    }
+
+    #[test]
+    fn test_from_key_set() {
+        let mut rng = rand::thread_rng();
+        let sk_set = SecretKeySet::random(1, &mut rng);
+        // IDs whose sorted order determines the node indices.
+        let ids = ["alice", "bob", "carol", "dave"];
+        let sec_keys: BTreeMap<&str, SecretKey> = ids.iter().map(|id| (*id, rng.gen())).collect();
+        let pub_keys: BTreeMap<&str, PublicKey> = sec_keys
+            .iter()
+            .map(|(id, sk)| (*id, sk.public_key()))
+            .collect();
+        let from_key_set = |i: usize, share_idx: usize| {
+            NetworkInfo::from_key_set(
+                ids[i],
+                i,
+                sk_set.secret_key_share(share_idx),
+                sk_set.public_keys(),
+                sec_keys[ids[i]].clone(),
+                pub_keys.clone(),
+            )
+        };
+        assert_eq!(
+            NetworkInfoError::KeyShareMismatch(1),
+            from_key_set(1, 2).expect_err("wrong share accepted")
+        );
+        let wrong_index = NetworkInfo::from_key_set(
+            "bob",
+            2,
+            sk_set.secret_key_share(2),
+            sk_set.public_keys(),
+            sec_keys["bob"].clone(),
+            pub_keys.clone(),
+        );
+        assert_eq!(
+            NetworkInfoError::IndexMismatch(2, 1),
+            wrong_index.expect_err("wrong index accepted")
+        );
+        let not_validator = NetworkInfo::from_key_set(
+            "eve",
+            4,
+            sk_set.secret_key_share(4),
+            sk_set.public_keys(),
+            rng.gen(),
+            pub_keys.clone(),
+        );
+        assert_eq!(
+            NetworkInfoError::UnexpectedSecretKeyShare,
+            not_validator.expect_err("non-validator accepted")
+        );
+
+        // A coin share signed by one node verifies, and is accepted by another.
+        let netinfos: Vec<_> = (0..4)
+            .map(|i| from_key_set(i, i).expect("netinfo"))
+            .collect();
+        let pks = netinfos[0]
+            .public_key_share(&"carol")
+            .expect("public key share");
+        assert_eq!(sk_set.public_keys().public_key_share(2), *pks);
+        let doc = "from key set";
+        let mut coin =
+            ThresholdSign::new_with_document(Arc::new(netinfos[2].clone()), doc).expect("document");
+        let step = coin.sign().expect("sign");
+        let share = step.messages[0].message.share.clone();
+        assert!(pks.verify(&share, doc));
+        let mut other =
+            ThresholdSign::new_with_document(Arc::new(netinfos[0].clone()), doc).expect("document");
+        let step = other
+            .handle_message(&"carol", step.messages[0].message.clone())
+            .expect("share");
+        assert!(step.fault_log.is_empty());
+        assert!(!other.missing_contributors().contains(&"carol"));
+    }
+}
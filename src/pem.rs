@@ -0,0 +1,307 @@
+//! # PEM-style key containers
+//!
+//! Keys can be exported into a labeled, base64-encoded text container, similar to PEM, to move
+//! them between tools, e.g. into a hardware security module or a key management system:
+//!
+//! ```text
+//! -----BEGIN HBBFT PUBLIC KEY SET-----
+//! AQAAAGgBAAAAAAAAAJ4xkJtB...
+//! -----END HBBFT PUBLIC KEY SET-----
+//! ```
+//!
+//! The base64-encoded payload consists of a version byte, the length of the key as a big-endian
+//! 32-bit integer, and the key itself. Decoding checks the label, version and length, and
+//! validates the key, in particular that group elements are on the curve.
+
+use byteorder::{BigEndian, ByteOrder};
+use failure::Fail;
+
+use crate::crypto::serde_impl::SerdeSecret;
+use crate::crypto::{PublicKey, PublicKeySet, SecretKey, SecretKeyShare, PK_SIZE};
+use crate::encoding::{decode_base64, encode_base64};
+
+/// The current version of the container format.
+const VERSION: u8 = 1;
+/// The size of the header: the version byte and the key length.
+const HEADER_SIZE: usize = 5;
+/// The number of base64 characters per line.
+const LINE_LENGTH: usize = 64;
+
+/// An error decoding a key from a container.
+#[derive(Clone, Eq, PartialEq, Debug, Fail)]
+pub enum Error {
+    /// The `BEGIN` or `END` line is missing or malformed.
+    #[fail(display = "Missing or malformed BEGIN or END line")]
+    InvalidFraming,
+    /// The container's label doesn't match the type of key.
+    #[fail(display = "Expected label {}, found {}", expected, found)]
+    InvalidLabel {
+        /// The label of the requested key type.
+        expected: &'static str,
+        /// The label in the container.
+        found: String,
+    },
+    /// The payload is not valid, canonically padded base64.
+    #[fail(display = "Invalid base64 payload")]
+    InvalidBase64,
+    /// The container format version is not supported.
+    #[fail(display = "Unsupported version {}", _0)]
+    UnsupportedVersion(u8),
+    /// The length in the header doesn't match the payload.
+    #[fail(display = "Expected {} bytes, found {}", _0, _1)]
+    InvalidLength(usize, usize),
+    // String because `bincode` and `threshold_crypto` errors lack `Eq` and `Clone`.
+    /// The bytes don't represent a valid key, e.g. not a valid group element.
+    #[fail(display = "Invalid key: {}", _0)]
+    InvalidKey(String),
+}
+
+/// A `pem` result.
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+/// A key that can be stored in a container.
+pub trait PemKey: Sized {
+    /// The label in the container's `BEGIN` and `END` lines.
+    const LABEL: &'static str;
+
+    /// Returns the key's binary representation.
+    fn to_key_bytes(&self) -> Vec<u8>;
+
+    /// Decodes and validates a key from its binary representation.
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self>;
+}
+
+impl PemKey for PublicKey {
+    const LABEL: &'static str = "HBBFT PUBLIC KEY";
+
+    fn to_key_bytes(&self) -> Vec<u8> {
+        self.to_bytes().to_vec()
+    }
+
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != PK_SIZE {
+            return Err(Error::InvalidLength(PK_SIZE, bytes.len()));
+        }
+        let mut pk_bytes = [0u8; PK_SIZE];
+        pk_bytes.copy_from_slice(bytes);
+        PublicKey::from_bytes(pk_bytes).map_err(|err| Error::InvalidKey(err.to_string()))
+    }
+}
+
+impl PemKey for PublicKeySet {
+    const LABEL: &'static str = "HBBFT PUBLIC KEY SET";
+
+    fn to_key_bytes(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("serialize public key set")
+    }
+
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self> {
+        bincode::deserialize(bytes).map_err(|err| Error::InvalidKey(err.to_string()))
+    }
+}
+
+impl PemKey for SecretKey {
+    const LABEL: &'static str = "HBBFT SECRET KEY";
+
+    fn to_key_bytes(&self) -> Vec<u8> {
+        bincode::serialize(&SerdeSecret(self)).expect("serialize secret key")
+    }
+
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self> {
+        bincode::deserialize(bytes).map_err(|err| Error::InvalidKey(err.to_string()))
+    }
+}
+
+impl PemKey for SecretKeyShare {
+    const LABEL: &'static str = "HBBFT SECRET KEY SHARE";
+
+    fn to_key_bytes(&self) -> Vec<u8> {
+        bincode::serialize(&SerdeSecret(self)).expect("serialize secret key share")
+    }
+
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self> {
+        bincode::deserialize(bytes).map_err(|err| Error::InvalidKey(err.to_string()))
+    }
+}
+
+/// Returns the key in a container labeled with the key type.
+pub fn encode<K: PemKey>(key: &K) -> String {
+    let key_bytes = key.to_key_bytes();
+    let mut payload = vec![VERSION, 0, 0, 0, 0];
+    BigEndian::write_u32(&mut payload[1..HEADER_SIZE], key_bytes.len() as u32);
+    payload.extend(key_bytes);
+    let mut pem = format!("-----BEGIN {}-----\n", K::LABEL);
+    for line in encode_base64(&payload).as_bytes().chunks(LINE_LENGTH) {
+        pem.push_str(&String::from_utf8_lossy(line));
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", K::LABEL));
+    pem
+}
+
+/// Decodes a key from a container, and checks that the container's label matches the key type.
+pub fn decode<K: PemKey>(pem: &str) -> Result<K> {
+    let mut lines = pem.trim().lines().map(str::trim);
+    let label = lines
+        .next()
+        .and_then(|line| framed_label(line, "BEGIN"))
+        .ok_or(Error::InvalidFraming)?;
+    if label != K::LABEL {
+        return Err(Error::InvalidLabel {
+            expected: K::LABEL,
+            found: label.to_string(),
+        });
+    }
+    let mut base64 = String::new();
+    let mut end_found = false;
+    for line in lines {
+        if end_found {
+            return Err(Error::InvalidFraming); // Content after the `END` line.
+        }
+        match framed_label(line, "END") {
+            Some(end_label) if end_label == label => end_found = true,
+            Some(_) => return Err(Error::InvalidFraming),
+            None => base64.push_str(line),
+        }
+    }
+    if !end_found {
+        return Err(Error::InvalidFraming);
+    }
+    let payload = decode_base64(&base64).ok_or(Error::InvalidBase64)?;
+    if payload.len() < HEADER_SIZE {
+        return Err(Error::InvalidLength(HEADER_SIZE, payload.len()));
+    }
+    if payload[0] != VERSION {
+        return Err(Error::UnsupportedVersion(payload[0]));
+    }
+    let key_len = BigEndian::read_u32(&payload[1..HEADER_SIZE]) as usize;
+    let key_bytes = &payload[HEADER_SIZE..];
+    if key_len != key_bytes.len() {
+        return Err(Error::InvalidLength(key_len, key_bytes.len()));
+    }
+    K::from_key_bytes(key_bytes)
+}
+
+/// Returns the label if the line has the form `-----<kind> <label>-----`.
+fn framed_label<'a>(line: &'a str, kind: &str) -> Option<&'a str> {
+    let inner = line.trim_start_matches("-----").trim_end_matches("-----");
+    if inner.len() + 10 != line.len() || !inner.starts_with(kind) {
+        return None;
+    }
+    let label = &inner[kind.len()..];
+    if label.starts_with(' ') {
+        Some(&label[1..])
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, Error};
+    use crate::crypto::{PublicKey, PublicKeySet, SecretKey, SecretKeySet};
+    use crate::encoding::{decode_base64, encode_base64};
+
+    #[test]
+    fn test_pem_round_trip() {
+        let mut rng = rand::thread_rng();
+        let sk_set = SecretKeySet::random(3, &mut rng);
+        let pk_set = sk_set.public_keys();
+        let sk_share = sk_set.secret_key_share(2);
+        let sk: SecretKey = rand::random();
+        let pk = sk.public_key();
+
+        let pk_set_pem = encode(&pk_set);
+        assert!(pk_set_pem.starts_with("-----BEGIN HBBFT PUBLIC KEY SET-----\n"));
+        assert!(pk_set_pem.ends_with("-----END HBBFT PUBLIC KEY SET-----\n"));
+        assert!(pk_set_pem.lines().all(|line| line.len() <= 64));
+        assert_eq!(Ok(pk_set.clone()), decode(&pk_set_pem));
+        assert_eq!(Ok(pk), decode(&encode(&pk)));
+        assert_eq!(Ok(sk_share.clone()), decode(&encode(&sk_share)));
+        assert_eq!(Ok(sk.clone()), decode(&encode(&sk)));
+
+        // Surrounding whitespace and CRLF line endings are accepted.
+        let crlf_pem = format!("\n  {}  \n", pk_set_pem.replace("\n", "\r\n"));
+        assert_eq!(Ok(pk_set), decode(&crlf_pem));
+    }
+
+    #[test]
+    fn test_pem_invalid() {
+        let sk: SecretKey = rand::random();
+        let pk_pem = encode(&sk.public_key());
+
+        // The label must match the requested type.
+        let expected_err = Error::InvalidLabel {
+            expected: "HBBFT PUBLIC KEY SET",
+            found: "HBBFT PUBLIC KEY".to_string(),
+        };
+        assert_eq!(Err(expected_err), decode::<PublicKeySet>(&pk_pem));
+        let share_pem =
+            encode(&SecretKeySet::random(1, &mut rand::thread_rng()).secret_key_share(0));
+        match decode::<SecretKey>(&share_pem) {
+            Err(Error::InvalidLabel { .. }) => (),
+            result => panic!("expected an invalid label, got {:?}", result),
+        }
+
+        // The framing must be complete and consistent.
+        let no_end: String = pk_pem.lines().take(2).collect::<Vec<_>>().join("\n");
+        assert_eq!(Err(Error::InvalidFraming), decode::<PublicKey>(&no_end));
+        let wrong_end = pk_pem.replace("END HBBFT PUBLIC KEY", "END HBBFT SECRET KEY");
+        assert_eq!(Err(Error::InvalidFraming), decode::<PublicKey>(&wrong_end));
+        assert_eq!(Err(Error::InvalidFraming), decode::<PublicKey>("Zm9v"));
+
+        // The version and length in the header are checked.
+        let body: String = pk_pem
+            .lines()
+            .filter(|line| !line.starts_with('-'))
+            .collect();
+        let mut payload = decode_base64(&body).expect("base64");
+        let wrap = |payload: &[u8]| {
+            format!(
+                "-----BEGIN HBBFT PUBLIC KEY-----\n{}\n-----END HBBFT PUBLIC KEY-----\n",
+                encode_base64(payload)
+            )
+        };
+        payload[0] = 2;
+        assert_eq!(
+            Err(Error::UnsupportedVersion(2)),
+            decode::<PublicKey>(&wrap(&payload))
+        );
+        payload[0] = 1;
+        let truncated = &payload[..payload.len() - 1];
+        match decode::<PublicKey>(&wrap(truncated)) {
+            Err(Error::InvalidLength(_, _)) => (),
+            result => panic!("expected an invalid length, got {:?}", result),
+        }
+        assert_eq!(Ok(sk.public_key()), decode::<PublicKey>(&wrap(&payload)));
+
+        // Setting the unused bits before the padding doesn't change the payload, but is rejected.
+        // Without padding, there are no unused bits.
+        let padding = (3 - payload.len() % 3) % 3;
+        if padding > 0 {
+            let mut base64 = encode_base64(&payload).into_bytes();
+            assert_eq!(padding, base64.iter().filter(|c| **c == b'=').count());
+            let last = base64.len() - 1 - padding;
+            base64[last] += 1; // The last character's unused bits are zero.
+            let non_canonical = format!(
+                "-----BEGIN HBBFT PUBLIC KEY-----\n{}\n-----END HBBFT PUBLIC KEY-----\n",
+                String::from_utf8(base64).expect("base64 is ASCII")
+            );
+            assert_eq!(
+                Err(Error::InvalidBase64),
+                decode::<PublicKey>(&non_canonical)
+            );
+        }
+
+        // Invalid group elements are rejected. (The mock implementation doesn't detect that.)
+        if cfg!(not(feature = "use-insecure-test-only-mock-crypto")) {
+            for byte in payload[5..].iter_mut() {
+                *byte = 0xff;
+            }
+            match decode::<PublicKey>(&wrap(&payload)) {
+                Err(Error::InvalidKey(_)) => (),
+                result => panic!("expected an invalid key, got {:?}", result),
+            }
+        }
+    }
+}
@@ -21,6 +21,12 @@
 //! roughly the same entries in their queues. By selecting a random fraction of the first _B_
 //! entries, any two nodes will likely make almost disjoint contributions instead of proposing
 //! the same transaction multiple times.
+//!
+//! If each node has its own transactions instead, e.g. because clients submit to a single node,
+//! the builder's `ordered_proposals` option makes a node always propose the first _B / N_
+//! entries of its queue. Everything else is carried over to the following epochs, so the
+//! transactions are proposed in the order in which they were queued, and `carryover_len` tells
+//! how many are still waiting. This requires a queue that implements `OrderedTransactionQueue`.
 
 use std::marker::PhantomData;
 use std::{cmp, iter};
@@ -35,8 +41,8 @@ use crate::crypto::{PublicKey, SecretKey};
 use crate::dynamic_honey_badger::{
     self, Batch as DhbBatch, DynamicHoneyBadger, JoinPlan, Message, Step as DhbStep,
 };
-use crate::transaction_queue::TransactionQueue;
-use crate::{Contribution, DistAlgorithm, NetworkInfo, NodeIdT};
+use crate::transaction_queue::{OrderedTransactionQueue, TransactionQueue};
+use crate::{Contribution, Diagnose, DiagnosticTree, DistAlgorithm, NetworkInfo, NodeIdT};
 
 pub use crate::dynamic_honey_badger::{Change, ChangeState, Input};
 
@@ -45,16 +51,16 @@ pub use crate::dynamic_honey_badger::{Change, ChangeState, Input};
 pub enum Error {
     /// Failed to handle input.
     #[fail(display = "Input error: {}", _0)]
-    Input(dynamic_honey_badger::Error),
+    Input(#[cause] dynamic_honey_badger::Error),
     /// Failed to handle a message.
     #[fail(display = "Handle message error: {}", _0)]
-    HandleMessage(dynamic_honey_badger::Error),
+    HandleMessage(#[cause] dynamic_honey_badger::Error),
     /// Failed to propose a contribution.
     #[fail(display = "Propose error: {}", _0)]
-    Propose(dynamic_honey_badger::Error),
+    Propose(#[cause] dynamic_honey_badger::Error),
     /// Failed to create a Dynamic Honey Badger instance according to a join plan.
     #[fail(display = "New joining error: {}", _0)]
-    NewJoining(dynamic_honey_badger::Error),
+    NewJoining(#[cause] dynamic_honey_badger::Error),
 }
 
 /// The result of `QueueingHoneyBadger` handling an input or message.
@@ -73,6 +79,9 @@ where
     batch_size: usize,
     /// The queue of pending transactions that haven't been output in a batch yet.
     queue: Q,
+    /// Returns the first transactions in the queue, if we propose them instead of a random
+    /// choice.
+    first_transactions: Option<FirstTransactions<T, Q>>,
     /// The initial step of the managed `DynamicHoneyBadger` instance.
     step: Option<DhbStep<Vec<T>, N>>,
     _phantom: PhantomData<T>,
@@ -80,6 +89,9 @@ where
 
 type QueueingHoneyBadgerWithStep<T, N, Q> = (QueueingHoneyBadger<T, N, Q>, Step<T, N>);
 
+/// `OrderedTransactionQueue::first` of the queue type, if `ordered_proposals` is set.
+type FirstTransactions<T, Q> = fn(&Q, usize) -> Vec<T>;
+
 impl<T, N, Q> QueueingHoneyBadgerBuilder<T, N, Q>
 where
     T: Contribution + Serialize + DeserializeOwned + Clone,
@@ -95,6 +107,7 @@ where
             dyn_hb,
             batch_size: 100,
             queue: Default::default(),
+            first_transactions: None,
             step: None,
             _phantom: PhantomData,
         }
@@ -139,6 +152,8 @@ where
             dyn_hb: self.dyn_hb,
             batch_size: self.batch_size,
             queue: self.queue,
+            first_transactions: self.first_transactions,
+            proposed: 0,
         };
         let mut step = qhb.propose(rng)?;
         if let Some(dhb_step) = self.step {
@@ -148,6 +163,26 @@ where
     }
 }
 
+impl<T, N, Q> QueueingHoneyBadgerBuilder<T, N, Q>
+where
+    T: Contribution + Serialize + DeserializeOwned + Clone,
+    N: NodeIdT + Serialize + DeserializeOwned,
+    Q: OrderedTransactionQueue<T>,
+    Standard: Distribution<N>,
+{
+    /// Sets whether each proposal consists of the first _B / N_ transactions in the queue, in
+    /// order, instead of a random choice among the first _B_. The default is `false`.
+    ///
+    /// With this, the transactions are proposed in a stable order, and those that don't fit into
+    /// an epoch's proposal are carried over to the next ones. If several nodes have the same
+    /// transactions in their queues, however, they will all propose them, so this should only be
+    /// used if each node queues different transactions.
+    pub fn ordered_proposals(mut self, ordered: bool) -> Self {
+        self.first_transactions = if ordered { Some(Q::first) } else { None };
+        self
+    }
+}
+
 /// A Honey Badger instance that can handle adding and removing nodes and manages a transaction
 /// queue.
 #[derive(Derivative)]
@@ -159,6 +194,12 @@ pub struct QueueingHoneyBadger<T, N: Ord, Q> {
     dyn_hb: DynamicHoneyBadger<Vec<T>, N>,
     /// The queue of pending transactions that haven't been output in a batch yet.
     queue: Q,
+    /// Returns the first transactions in the queue, if we propose them instead of a random
+    /// choice.
+    #[derivative(Debug = "ignore")]
+    first_transactions: Option<FirstTransactions<T, Q>>,
+    /// The number of transactions in our proposal for the current epoch.
+    proposed: usize,
 }
 
 /// A `QueueingHoneyBadger` step, possibly containing multiple outputs.
@@ -205,6 +246,27 @@ where
     }
 }
 
+impl<T, N, Q> Diagnose for QueueingHoneyBadger<T, N, Q>
+where
+    T: Contribution + Serialize + DeserializeOwned + Clone,
+    N: NodeIdT + Serialize + DeserializeOwned,
+    Q: TransactionQueue<T>,
+{
+    /// Returns whether transactions are queued, with the `DynamicHoneyBadger` instance as child.
+    fn diagnose(&self) -> DiagnosticTree {
+        let label = format!(
+            "QueueingHoneyBadger: batch size {}, queue {}",
+            self.batch_size,
+            if self.queue.is_empty() {
+                "empty"
+            } else {
+                "not empty"
+            }
+        );
+        DiagnosticTree::new(label).with_child(self.dyn_hb.diagnose())
+    }
+}
+
 impl<T, N, Q> QueueingHoneyBadger<T, N, Q>
 where
     T: Contribution + Serialize + DeserializeOwned + Clone,
@@ -341,7 +403,11 @@ where
         let mut step = Step::default();
         while self.can_propose() {
             let amount = cmp::max(1, self.batch_size / self.dyn_hb.netinfo().num_nodes());
-            let proposal = self.queue.choose(rng, amount, self.batch_size);
+            let proposal = match self.first_transactions {
+                Some(first) => first(&self.queue, amount),
+                None => self.queue.choose(rng, amount, self.batch_size),
+            };
+            self.proposed = proposal.len();
             step.extend(
                 self.dyn_hb
                     .handle_input(Input::User(proposal), rng)
@@ -352,5 +418,23 @@ where
     }
 }
 
+impl<T, N, Q> QueueingHoneyBadger<T, N, Q>
+where
+    T: Contribution + Serialize + DeserializeOwned + Clone,
+    N: NodeIdT + Serialize + DeserializeOwned,
+    Q: OrderedTransactionQueue<T>,
+    Standard: Distribution<N>,
+{
+    /// Returns the number of queued transactions that are not part of our proposal for the
+    /// current epoch. They are carried over to the following epochs.
+    pub fn carryover_len(&self) -> usize {
+        if self.dyn_hb.has_input() {
+            self.queue.len().saturating_sub(self.proposed)
+        } else {
+            self.queue.len()
+        }
+    }
+}
+
 /// A batch containing a list of transactions from at least two thirds of the validators.
 pub type Batch<T, N> = DhbBatch<Vec<T>, N>;
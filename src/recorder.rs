@@ -0,0 +1,196 @@
+//! # Recording and replaying algorithm runs
+//!
+//! To debug a failure that cannot be reproduced, e.g. in production, it helps to know exactly which
+//! inputs and messages an instance handled, and what it did in response. `Recorder` wraps an
+//! algorithm and logs every `handle_input` and `handle_message` call together with a summary of
+//! the resulting step to a `Trace`. The trace implements `Serialize`, so it can be stored and
+//! analyzed offline.
+//!
+//! `replay` feeds a trace into a fresh instance, and checks that every call produces the same step
+//! as in the recording. This requires the algorithm to behave deterministically: If it uses the
+//! random number generator, `replay` must be given one that produces the same numbers as the one
+//! used while recording.
+
+use std::fmt::Debug;
+
+use derivative::Derivative;
+use failure::Fail;
+use rand::Rng;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::event::StepEvent;
+use crate::{DaStep, DistAlgorithm};
+
+/// An input or message that was handled by a recorded instance.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TraceEvent<N, I, M> {
+    /// An input, passed to `handle_input`.
+    Input(I),
+    /// A message from the given sender, passed to `handle_message`.
+    Message(N, M),
+}
+
+/// A recorded call, and its outcome.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceEntry<N, I, M> {
+    /// The input or message that was handled.
+    pub event: TraceEvent<N, I, M>,
+    /// A summary of the resulting step, or `None` if the call returned an error.
+    pub step: Option<StepEvent>,
+}
+
+/// The list of calls recorded by a `Recorder`, in the order in which they were made.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trace<N, I, M> {
+    /// The algorithm's name, as used in the recorded `StepEvent`s.
+    pub algorithm: String,
+    /// The recorded calls.
+    pub entries: Vec<TraceEntry<N, I, M>>,
+}
+
+/// A trace of the given algorithm.
+pub type DaTrace<D> =
+    Trace<<D as DistAlgorithm>::NodeId, <D as DistAlgorithm>::Input, <D as DistAlgorithm>::Message>;
+
+/// An error replaying a trace.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Fail)]
+pub enum ReplayError {
+    /// The entry with the given index produced a different step than in the recording.
+    #[fail(display = "Trace entry {} produced a different step", _0)]
+    Mismatch(usize),
+}
+
+/// A wrapper that records every input and message the wrapped algorithm handles.
+#[derive(Derivative)]
+#[derivative(Debug(bound = "D: Debug, D::Input: Debug"))]
+pub struct Recorder<D: DistAlgorithm> {
+    /// The wrapped algorithm.
+    algo: D,
+    /// The calls recorded so far.
+    trace: DaTrace<D>,
+}
+
+impl<D> Recorder<D>
+where
+    D: DistAlgorithm,
+    D::Input: Clone,
+    D::Output: Debug,
+{
+    /// Wraps the given algorithm instance. The `algorithm` name is included in the trace.
+    pub fn new(algo: D, algorithm: &str) -> Self {
+        let trace = Trace {
+            algorithm: algorithm.to_string(),
+            entries: Vec::new(),
+        };
+        Recorder { algo, trace }
+    }
+
+    /// Returns a reference to the wrapped algorithm.
+    pub fn inner(&self) -> &D {
+        &self.algo
+    }
+
+    /// Returns the calls recorded so far.
+    pub fn trace(&self) -> &DaTrace<D> {
+        &self.trace
+    }
+
+    /// Returns the wrapped algorithm and the recorded trace.
+    pub fn into_parts(self) -> (D, DaTrace<D>) {
+        (self.algo, self.trace)
+    }
+
+    /// Appends the event and a summary of its result to the trace.
+    fn record(
+        &mut self,
+        event: TraceEvent<D::NodeId, D::Input, D::Message>,
+        result: &Result<DaStep<D>, D::Error>,
+    ) {
+        let step = summarize(&self.trace.algorithm, &self.algo, result);
+        self.trace.entries.push(TraceEntry { event, step });
+    }
+}
+
+impl<D> DistAlgorithm for Recorder<D>
+where
+    D: DistAlgorithm,
+    D::Input: Clone + Send + Sync,
+    D::Output: Debug,
+    D::Message: Clone,
+{
+    type NodeId = D::NodeId;
+    type Input = D::Input;
+    type Output = D::Output;
+    type Message = D::Message;
+    type Error = D::Error;
+
+    fn handle_input<R: Rng>(
+        &mut self,
+        input: Self::Input,
+        rng: &mut R,
+    ) -> Result<DaStep<D>, D::Error> {
+        let result = self.algo.handle_input(input.clone(), rng);
+        self.record(TraceEvent::Input(input), &result);
+        result
+    }
+
+    fn handle_message<R: Rng>(
+        &mut self,
+        sender_id: &Self::NodeId,
+        message: Self::Message,
+        rng: &mut R,
+    ) -> Result<DaStep<D>, D::Error> {
+        let result = self.algo.handle_message(sender_id, message.clone(), rng);
+        self.record(TraceEvent::Message(sender_id.clone(), message), &result);
+        result
+    }
+
+    fn terminated(&self) -> bool {
+        self.algo.terminated()
+    }
+
+    fn our_id(&self) -> &Self::NodeId {
+        self.algo.our_id()
+    }
+}
+
+/// Feeds the recorded inputs and messages into `algo`, which must be in the same state as the
+/// recorded instance was when the recording started, e.g. a fresh instance created with the same
+/// arguments. Returns an error if any call produces a different step than in the recording.
+pub fn replay<D, R>(trace: &DaTrace<D>, algo: &mut D, rng: &mut R) -> Result<(), ReplayError>
+where
+    D: DistAlgorithm,
+    D::Input: Clone,
+    D::Output: Debug,
+    D::Message: Clone,
+    R: Rng,
+{
+    for (index, entry) in trace.entries.iter().enumerate() {
+        let result = match entry.event {
+            TraceEvent::Input(ref input) => algo.handle_input(input.clone(), rng),
+            TraceEvent::Message(ref sender_id, ref msg) => {
+                algo.handle_message(sender_id, msg.clone(), rng)
+            }
+        };
+        if summarize(&trace.algorithm, algo, &result) != entry.step {
+            return Err(ReplayError::Mismatch(index));
+        }
+    }
+    Ok(())
+}
+
+/// Returns a summary of the step, or `None` if the call returned an error.
+fn summarize<D>(
+    algorithm: &str,
+    algo: &D,
+    result: &Result<DaStep<D>, D::Error>,
+) -> Option<StepEvent>
+where
+    D: DistAlgorithm,
+    D::Output: Debug,
+{
+    result
+        .as_ref()
+        .ok()
+        .map(|step| step.to_event(algorithm, algo.our_id()))
+}
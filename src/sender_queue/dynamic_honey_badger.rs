@@ -48,7 +48,8 @@ impl<N: Ord> SenderQueueableMessage for Message<N> {
     fn is_premature(&self, (them_era, them): (u64, u64), max_future_epochs: u64) -> bool {
         match *self {
             Message::HoneyBadger(era, ref msg) => {
-                era > them_era || (era == them_era && msg.epoch() > them + max_future_epochs)
+                era > them_era
+                    || (era == them_era && msg.epoch() > them.saturating_add(max_future_epochs))
             }
             Message::KeyGen(era, _, _) => era > them_era,
             Message::SignedVote(ref signed_vote) => signed_vote.era() > them_era,
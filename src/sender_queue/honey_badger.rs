@@ -24,7 +24,7 @@ impl<N> SenderQueueableMessage for Message<N> {
     type Epoch = u64;
 
     fn is_premature(&self, them: u64, max_future_epochs: u64) -> bool {
-        self.epoch() > them + max_future_epochs
+        self.epoch() > them.saturating_add(max_future_epochs)
     }
 
     fn is_obsolete(&self, them: u64) -> bool {
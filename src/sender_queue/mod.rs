@@ -5,9 +5,12 @@
 //! epoch matches the epoch of the message. Thus no queueing is required for incoming messages since
 //! any incoming messages with non-matching epochs can be safely discarded.
 
+#[cfg(feature = "dynamic")]
 mod dynamic_honey_badger;
+#[cfg(feature = "honey-badger")]
 mod honey_badger;
 mod message;
+#[cfg(feature = "dynamic")]
 mod queueing_honey_badger;
 
 use rand::Rng;
@@ -17,7 +20,7 @@ use std::fmt::Debug;
 use log::debug;
 
 use crate::traits::EpochT;
-use crate::{DaStep, DistAlgorithm, Epoched, NodeIdT, Target};
+use crate::{DaStep, Diagnose, DiagnosticTree, DistAlgorithm, Epoched, NodeIdT, Target};
 
 pub use self::message::Message;
 
@@ -147,6 +150,17 @@ where
     }
 }
 
+impl<D> Diagnose for SenderQueue<D>
+where
+    D: SenderQueueableDistAlgorithm + Debug + Diagnose,
+{
+    /// Returns the number of known peers, with the wrapped algorithm as child.
+    fn diagnose(&self) -> DiagnosticTree {
+        let label = format!("SenderQueue: {} peers", self.peer_epochs.len());
+        DiagnosticTree::new(label).with_child(self.algo.diagnose())
+    }
+}
+
 impl<D> SenderQueue<D>
 where
     D: SenderQueueableDistAlgorithm + Debug,
@@ -0,0 +1,128 @@
+//! # Strict mode for testing
+//!
+//! Algorithms don't fail if they encounter Byzantine behavior: They report the faulty node in the
+//! step's `FaultLog` and carry on. In tests that don't intentionally simulate faulty nodes, any
+//! fault indicates a bug in the test setup or the algorithm, and should not go unnoticed.
+//!
+//! `Strict` wraps an algorithm and turns every non-empty fault log into an error. Since a single
+//! malicious message would then make a correct node fail, this must only be used for testing.
+
+use std::fmt::{self, Debug, Display};
+
+use failure::Fail;
+use rand::Rng;
+
+use crate::{DaStep, DistAlgorithm, FaultLog, NodeIdT};
+
+/// An error returned by an algorithm in strict mode.
+#[derive(Debug)]
+pub enum Error<E, N> {
+    /// The wrapped algorithm returned an error.
+    Algorithm(E),
+    /// The wrapped algorithm reported faulty nodes.
+    Faults(FaultLog<N>),
+}
+
+impl<E: Display, N: Debug> Display for Error<E, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Algorithm(ref err) => write!(f, "Algorithm error: {}", err),
+            Error::Faults(ref fault_log) => write!(f, "Faults reported: {:?}", fault_log.0),
+        }
+    }
+}
+
+impl<E: Fail, N: NodeIdT + 'static> Fail for Error<E, N> {
+    fn cause(&self) -> Option<&dyn Fail> {
+        match *self {
+            Error::Algorithm(ref err) => Some(err),
+            Error::Faults(_) => None,
+        }
+    }
+}
+
+/// A strict mode result.
+pub type Result<T, E, N> = ::std::result::Result<T, Error<E, N>>;
+
+/// A wrapper that returns an error instead of a step whenever the wrapped algorithm reports a
+/// fault. _Only use this for testing._
+#[derive(Debug)]
+pub struct Strict<D> {
+    /// The wrapped algorithm.
+    algo: D,
+}
+
+impl<D> Strict<D>
+where
+    D: DistAlgorithm,
+    D::NodeId: 'static,
+{
+    /// Wraps the given algorithm instance.
+    pub fn new(algo: D) -> Self {
+        Strict { algo }
+    }
+
+    /// Returns a reference to the wrapped algorithm.
+    pub fn inner(&self) -> &D {
+        &self.algo
+    }
+
+    /// Returns a mutable reference to the wrapped algorithm.
+    pub fn inner_mut(&mut self) -> &mut D {
+        &mut self.algo
+    }
+
+    /// Returns the wrapped algorithm.
+    pub fn into_inner(self) -> D {
+        self.algo
+    }
+
+    /// Returns the step unchanged if its fault log is empty, or an error otherwise.
+    fn check(
+        result: ::std::result::Result<DaStep<D>, D::Error>,
+    ) -> Result<DaStep<D>, D::Error, D::NodeId> {
+        let step = result.map_err(Error::Algorithm)?;
+        if step.fault_log.is_empty() {
+            Ok(step)
+        } else {
+            Err(Error::Faults(step.fault_log))
+        }
+    }
+}
+
+impl<D> DistAlgorithm for Strict<D>
+where
+    D: DistAlgorithm,
+    D::NodeId: 'static,
+{
+    type NodeId = D::NodeId;
+    type Input = D::Input;
+    type Output = D::Output;
+    type Message = D::Message;
+    type Error = Error<D::Error, D::NodeId>;
+
+    fn handle_input<R: Rng>(
+        &mut self,
+        input: Self::Input,
+        rng: &mut R,
+    ) -> Result<DaStep<D>, D::Error, D::NodeId> {
+        Self::check(self.algo.handle_input(input, rng))
+    }
+
+    fn handle_message<R: Rng>(
+        &mut self,
+        sender_id: &Self::NodeId,
+        message: Self::Message,
+        rng: &mut R,
+    ) -> Result<DaStep<D>, D::Error, D::NodeId> {
+        Self::check(self.algo.handle_message(sender_id, message, rng))
+    }
+
+    fn terminated(&self) -> bool {
+        self.algo.terminated()
+    }
+
+    fn our_id(&self) -> &Self::NodeId {
+        self.algo.our_id()
+    }
+}
@@ -10,16 +10,16 @@ use crate::broadcast;
 pub enum Error {
     /// Error creating `BinaryAgreement`.
     #[fail(display = "Error creating BinaryAgreement: {}", _0)]
-    NewAgreement(binary_agreement::Error),
+    NewAgreement(#[cause] binary_agreement::Error),
     /// Error creating `Broadcast`.
     #[fail(display = "Error creating Broadcast: {}", _0)]
-    NewBroadcast(broadcast::Error),
+    NewBroadcast(#[cause] broadcast::Error),
     /// Error handling a `Broadcast` input or message.
     #[fail(display = "Error handling Broadcast input/message: {}", _0)]
-    HandleBroadcast(broadcast::Error),
+    HandleBroadcast(#[cause] broadcast::Error),
     /// Error handling a `BinaryAgreement` input or message.
     #[fail(display = "Error handling BinaryAgreement input/message: {}", _0)]
-    HandleAgreement(binary_agreement::Error),
+    HandleAgreement(#[cause] binary_agreement::Error),
     /// Unknown proposer.
     #[fail(display = "Unknown proposer ID")]
     UnknownProposer,
@@ -22,6 +22,15 @@
 //! remaining ones, where we haven't provided input yet.
 //! * Once all `BinaryAgreement` instances have decided, `Subset` returns the set of all proposed
 //! values for which the decision was "yes".
+//!
+//! ## Output order
+//!
+//! Each accepted contribution is output as a separate `SubsetOutput::Contribution` as soon as it
+//! has been received _and_ accepted, followed by a single `SubsetOutput::Done`. The order of the
+//! contributions depends on the order in which the messages arrived, so it can differ between
+//! nodes: Only the _set_ is guaranteed to be the same. Users that need a deterministic order must
+//! sort the contributions by proposer ID, e.g. by collecting them in a `BTreeMap`, as
+//! `HoneyBadger` does for its batches.
 
 mod error;
 mod message;
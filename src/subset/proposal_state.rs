@@ -5,7 +5,7 @@ use super::subset::BaSessionId;
 use super::{Error, MessageContent, Result};
 use crate::binary_agreement;
 use crate::broadcast::{self, Broadcast};
-use crate::{NetworkInfo, NodeIdT, SessionIdT};
+use crate::{Diagnose, DiagnosticTree, NetworkInfo, NodeIdT, SessionIdT};
 
 type BaInstance<N, S> = binary_agreement::BinaryAgreement<N, BaSessionId<S>>;
 type ValueAndStep<N> = (Option<Vec<u8>>, Step<N>);
@@ -63,6 +63,22 @@ impl<N: NodeIdT, S: SessionIdT> ProposalState<N, S> {
         }
     }
 
+    /// Returns the state of the proposal, with the diagnostics of the broadcast and agreement
+    /// instances that are still running.
+    pub fn diagnose(&self, proposer_id: &N) -> DiagnosticTree {
+        let label =
+            |state: &str| DiagnosticTree::new(format!("proposer {:?}: {}", proposer_id, state));
+        match self {
+            ProposalState::Ongoing(bc, ba) => label("ongoing")
+                .with_child(bc.diagnose())
+                .with_child(ba.diagnose()),
+            ProposalState::HasValue(_, ba) => label("has value").with_child(ba.diagnose()),
+            ProposalState::Accepted(bc) => label("accepted").with_child(bc.diagnose()),
+            ProposalState::Complete(true) => label("complete, accepted"),
+            ProposalState::Complete(false) => label("complete, rejected"),
+        }
+    }
+
     /// Makes a proposal by broadcasting a value.
     pub fn propose(&mut self, value: Vec<u8>) -> Result<Step<N>> {
         self.transition(|state| state.handle_broadcast(|bc| bc.broadcast(value)))
@@ -9,13 +9,16 @@ use serde_derive::Serialize;
 
 use super::proposal_state::{ProposalState, Step as ProposalStep};
 use super::{Error, Message, MessageContent, Result};
-use crate::{util, DistAlgorithm, NetworkInfo, NodeIdT, SessionIdT};
+use crate::{util, Diagnose, DiagnosticTree, DistAlgorithm, NetworkInfo, NodeIdT, SessionIdT};
 use rand::Rng;
 
 /// A `Subset` step, possibly containing several outputs.
 pub type Step<N> = crate::Step<Message<N>, SubsetOutput<N>, N>;
 
 /// An output with an accepted contribution or the end of the set.
+///
+/// The contributions are output in the order in which they were accepted, which can differ
+/// between nodes. See the module documentation.
 #[derive(Derivative, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[derivative(Debug)]
 pub enum SubsetOutput<N> {
@@ -70,6 +73,27 @@ impl<N: NodeIdT, S: SessionIdT> DistAlgorithm for Subset<N, S> {
     }
 }
 
+impl<N: NodeIdT, S: SessionIdT> Diagnose for Subset<N, S> {
+    /// Returns the number of accepted and complete proposals, with one child per proposer.
+    fn diagnose(&self) -> DiagnosticTree {
+        let states = self.proposal_states.values();
+        let accepted = states.clone().filter(|state| state.accepted()).count();
+        let complete = states.filter(|state| state.complete()).count();
+        let mut tree = DiagnosticTree::new(format!(
+            "Subset {}: {} of {} proposals accepted, {} complete{}",
+            self.session_id,
+            accepted,
+            self.proposal_states.len(),
+            complete,
+            if self.decided { ", decided" } else { "" }
+        ));
+        for (proposer_id, state) in &self.proposal_states {
+            tree = tree.with_child(state.diagnose(proposer_id));
+        }
+        tree
+    }
+}
+
 impl<N: NodeIdT, S: SessionIdT> Subset<N, S> {
     /// Creates a new `Subset` instance with the given session identifier.
     ///
@@ -192,3 +216,31 @@ impl<S: fmt::Display> fmt::Display for BaSessionId<S> {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::BaSessionId;
+    use crate::binary_agreement::coin_nonce;
+
+    #[test]
+    fn test_coin_nonces_are_distinct() {
+        let mut nonces = BTreeSet::new();
+        let mut count = 0;
+        for subset_id in 0..5u64 {
+            for proposer_idx in 0..7 {
+                let ba_id = BaSessionId {
+                    subset_id,
+                    proposer_idx,
+                };
+                for epoch in 0..12 {
+                    nonces.insert(coin_nonce(&ba_id, epoch).expect("nonce"));
+                    count += 1;
+                }
+            }
+        }
+        // No two `BinaryAgreement` instances or epochs sign the same document.
+        assert_eq!(count, nonces.len());
+    }
+}
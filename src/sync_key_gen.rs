@@ -49,6 +49,10 @@
 //! * Observer nodes can also use `SyncKeyGen`. For observers, no `Part` and `Ack`
 //! messages will be created and they do not need to send anything. On completion, they will only
 //! receive the public key set, but no secret key share.
+//! * Not every validator needs to be a dealer: `SyncKeyGen::new_with_dealers` restricts the nodes
+//! that contribute `Part`s to a subset. All validators still handle and `Ack` the dealers'
+//! `Part`s and receive secret key shares. Since _t + 1_ complete `Part`s are needed, there must
+//! be more than _t_ dealers, and more than _2 t_ if up to _t_ of them can be faulty.
 //! * If a participant crashed and lost its `SyncKeyGen` instance, but still has its original
 //! key pair, and if the key generation messages were committed to some public ledger, it can
 //! create a new `SyncKeyGen`, handle all the messages in order, and compute its secret key share.
@@ -204,6 +208,9 @@ pub enum Error {
     /// Unknown sender.
     #[fail(display = "Unknown sender")]
     UnknownSender,
+    /// The dealers are not a subset of the nodes, or there are not more than `threshold` of them.
+    #[fail(display = "Invalid dealers")]
+    InvalidDealers,
     /// Failed to serialize message.
     #[fail(display = "Serialization error: {}", _0)]
     Serialize(String),
@@ -308,6 +315,8 @@ pub struct SyncKeyGen<N> {
     sec_key: SecretKey,
     /// The public keys of all nodes, by node ID.
     pub_keys: BTreeMap<N, PublicKey>,
+    /// The nodes that contribute a `Part`.
+    dealers: BTreeSet<N>,
     /// Proposed bivariate polynomials.
     parts: BTreeMap<u64, ProposalState>,
     /// The degree of the generated polynomial.
@@ -326,6 +335,40 @@ impl<N: NodeIdT> SyncKeyGen<N> {
         pub_keys: BTreeMap<N, PublicKey>,
         threshold: usize,
         rng: &mut R,
+    ) -> Result<(SyncKeyGen<N>, Option<Part>), Error> {
+        let dealers = pub_keys.keys().cloned().collect();
+        SyncKeyGen::create(our_id, sec_key, pub_keys, dealers, threshold, rng)
+    }
+
+    /// Creates a new `SyncKeyGen` instance in which only the `dealers` contribute `Part`s,
+    /// together with our `Part` message if we are one of them.
+    ///
+    /// All nodes with a public key in `pub_keys` still handle the `Part`s, send `Ack`s and receive
+    /// a secret key share. Returns an error unless `dealers` is a subset of the nodes with more
+    /// than `threshold` members, since otherwise the keys could never be generated safely. If up
+    /// to `threshold` dealers can be faulty, there should be more than `2 * threshold`.
+    pub fn new_with_dealers<R: rand::Rng>(
+        our_id: N,
+        sec_key: SecretKey,
+        pub_keys: BTreeMap<N, PublicKey>,
+        dealers: BTreeSet<N>,
+        threshold: usize,
+        rng: &mut R,
+    ) -> Result<(SyncKeyGen<N>, Option<Part>), Error> {
+        if dealers.len() <= threshold || !dealers.iter().all(|id| pub_keys.contains_key(id)) {
+            return Err(Error::InvalidDealers);
+        }
+        SyncKeyGen::create(our_id, sec_key, pub_keys, dealers, threshold, rng)
+    }
+
+    /// Creates a new `SyncKeyGen` instance with the given dealers, together with our `Part`.
+    fn create<R: rand::Rng>(
+        our_id: N,
+        sec_key: SecretKey,
+        pub_keys: BTreeMap<N, PublicKey>,
+        dealers: BTreeSet<N>,
+        threshold: usize,
+        rng: &mut R,
     ) -> Result<(SyncKeyGen<N>, Option<Part>), Error> {
         let our_idx = pub_keys
             .keys()
@@ -336,12 +379,16 @@ impl<N: NodeIdT> SyncKeyGen<N> {
             our_idx,
             sec_key,
             pub_keys,
+            dealers,
             parts: BTreeMap::new(),
             threshold,
         };
         if our_idx.is_none() {
             return Ok((key_gen, None)); // No part: we are an observer.
         }
+        if !key_gen.dealers.contains(&key_gen.our_id) {
+            return Ok((key_gen, None)); // No part: we are not a dealer.
+        }
 
         let our_part = BivarPoly::random(threshold, rng);
         let commit = our_part.commitment();
@@ -363,6 +410,11 @@ impl<N: NodeIdT> SyncKeyGen<N> {
         &self.pub_keys
     }
 
+    /// Returns the nodes that contribute a `Part`.
+    pub fn dealers(&self) -> &BTreeSet<N> {
+        &self.dealers
+    }
+
     /// Handles a `Part` message. If it is valid, returns an `Ack` message to be broadcast.
     ///
     /// If we are only an observer, `None` is returned instead and no messages need to be sent.
@@ -376,6 +428,9 @@ impl<N: NodeIdT> SyncKeyGen<N> {
         rng: &mut R,
     ) -> Result<PartOutcome, Error> {
         let sender_idx = self.node_index(sender_id).ok_or(Error::UnknownSender)?;
+        if !self.dealers.contains(sender_id) {
+            return Ok(PartOutcome::Invalid(PartFault::NotDealer));
+        }
         let row = match self.handle_part_or_fault(sender_idx, part) {
             Ok(Some(row)) => row,
             Ok(None) => return Ok(PartOutcome::Valid(None)),
@@ -573,6 +628,9 @@ pub enum PartFault {
     /// The number of rows differs from the number of nodes.
     #[fail(display = "The number of rows differs from the number of nodes")]
     RowCount,
+    /// The sender is not a dealer.
+    #[fail(display = "The sender is not a dealer")]
+    NotDealer,
     /// Received multiple different Part messages from the same sender.
     #[fail(display = "Received multiple different Part messages from the same sender")]
     MultipleParts,
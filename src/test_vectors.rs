@@ -0,0 +1,205 @@
+//! # Wire format test vectors
+//!
+//! Canonical serializations of protocol messages, for implementations in other languages that
+//! need to exchange messages with this one. Each `TestVector` contains a message with documented,
+//! fixed contents and its `bincode` serialization as a hexadecimal string, as it would be sent
+//! over the network. An interoperable implementation must decode the bytes to a message with the
+//! same contents, and encode that message to exactly the same bytes.
+//!
+//! All keys are derived from small field elements, and all documents and values are short ASCII
+//! strings, so that the vectors can be reproduced without a random number generator. Vectors that
+//! contain signature shares are only available with the real `threshold_crypto` implementation,
+//! since the mock crypto uses a different format.
+//!
+//! The default `bincode` configuration is used: integers are little-endian with fixed width,
+//! `usize` and sequence lengths are encoded as `u64`, and enum variants are prefixed with their
+//! index as a `u32`.
+
+use crate::binary_agreement::{self, bool_set, MessageContent, SbvMessage};
+#[cfg(feature = "broadcast")]
+use crate::broadcast::{self, merkle::MerkleTree};
+#[cfg(not(feature = "use-insecure-test-only-mock-crypto"))]
+use crate::crypto::{IntoFr, SecretKeyShare};
+use crate::encoding;
+#[cfg(not(feature = "use-insecure-test-only-mock-crypto"))]
+use crate::threshold_sign;
+
+/// A message together with its canonical serialization.
+#[derive(Clone, Debug)]
+pub struct TestVector<M> {
+    /// A short unique name of the vector.
+    pub name: &'static str,
+    /// A description of the message's logical contents.
+    pub description: &'static str,
+    /// The message.
+    pub message: M,
+    /// The serialized message, as a lowercase hexadecimal string.
+    pub hex: &'static str,
+}
+
+impl<M> TestVector<M> {
+    /// Returns the serialized message.
+    pub fn bytes(&self) -> Vec<u8> {
+        encoding::decode_hex(self.hex).expect("test vectors are valid hexadecimal strings")
+    }
+}
+
+/// The field element from which the secret key share in the signing vectors is derived.
+#[cfg(not(feature = "use-insecure-test-only-mock-crypto"))]
+const SECRET_KEY_SHARE_FR: u64 = 7;
+
+/// The document signed in the signing vectors.
+#[cfg(not(feature = "use-insecure-test-only-mock-crypto"))]
+const SIGNED_DOC: &[u8] = b"hbbft test vector";
+
+/// Returns the signature share of the secret key share `SECRET_KEY_SHARE_FR` on `SIGNED_DOC`.
+#[cfg(not(feature = "use-insecure-test-only-mock-crypto"))]
+fn signature_share_message() -> threshold_sign::Message {
+    let mut fr = SECRET_KEY_SHARE_FR.into_fr();
+    let sk_share = SecretKeyShare::from_mut(&mut fr);
+    threshold_sign::Message::new(0, sk_share.sign(SIGNED_DOC))
+}
+
+/// Returns the vectors for `threshold_sign::Message`.
+#[cfg(not(feature = "use-insecure-test-only-mock-crypto"))]
+pub fn threshold_sign_vectors() -> Vec<TestVector<threshold_sign::Message>> {
+    vec![TestVector {
+        name: "threshold_sign_share",
+        description: "The signature share of the secret key share `7` on the document \
+                      `b\"hbbft test vector\"` in round `0`: the wire version `2`, the round, \
+                      and the 96 bytes of the compressed G2 point.",
+        message: signature_share_message(),
+        hex: "020000000000000000807ff4874a595ff6d42aa89e09d559eb30299037955135\
+              cb42837d46e8dfe8dea1f1c34e97f9ae521068a68104d4eb950d2ff87090c9e4\
+              18e621d3e621e0721a7719e8be8afffc3b9229acdb07c6b5c3de9af437a7a5fb\
+              f8e5fa4752a09b8879",
+    }]
+}
+
+/// Returns the vectors for `broadcast::Message`.
+///
+/// All of them refer to the Merkle tree with the four leaves `b"val0"` to `b"val3"`.
+#[cfg(feature = "broadcast")]
+pub fn broadcast_vectors() -> Vec<TestVector<broadcast::Message>> {
+    let values = (0..4).map(|i| format!("val{}", i).into_bytes()).collect();
+    let tree = MerkleTree::from_vec(values);
+    let proof = |i| tree.proof(i).expect("the tree has four leaves");
+    vec![
+        TestVector {
+            name: "broadcast_value",
+            description: "`Value` with the proof for leaf `1`: the variant index `0`, the leaf \
+                          value, the index `1`, the two sibling digests and the root hash.",
+            message: broadcast::Message::Value(proof(1)),
+            hex: "00000000040000000000000076616c3101000000000000000200000000000000\
+                  99f7f4358ca1f3311508bbfe195b4a0f31a7827d2a8f6ad6e0fd112c81c5adb9\
+                  f7ee1f7095155fd7ffb584c3be08eba14dc245c7ebadbb2d05b57a517dfda8f3\
+                  dcd48c521de61b3265d54255d31366958fe4cd3aabfce18aa6db82588c8d319a",
+        },
+        TestVector {
+            name: "broadcast_echo",
+            description: "`Echo` with the proof for leaf `2`: the variant index `1`, the leaf \
+                          value, the index `2`, the two sibling digests and the root hash.",
+            message: broadcast::Message::Echo(proof(2)),
+            hex: "01000000040000000000000076616c3202000000000000000200000000000000\
+                  b9ba6ec83609f45993b8348998579bf1430d038d4b22dadba54cd69a600ac86d\
+                  d1c6dd2835fade0572c5243b9bea4fa1ce804ba30031a64a64664804119cbeed\
+                  dcd48c521de61b3265d54255d31366958fe4cd3aabfce18aa6db82588c8d319a",
+        },
+        TestVector {
+            name: "broadcast_ready",
+            description: "`Ready` with the root hash: the variant index `2`, followed by the 32 \
+                          bytes of the digest.",
+            message: broadcast::Message::Ready(*tree.root_hash()),
+            hex: "02000000dcd48c521de61b3265d54255d31366958fe4cd3aabfce18aa6db8258\
+                  8c8d319a",
+        },
+    ]
+}
+
+/// Returns the vectors for `binary_agreement::Message`.
+pub fn binary_agreement_vectors() -> Vec<TestVector<binary_agreement::Message>> {
+    #[allow(unused_mut)] // The `Coin` vector is not available with mock crypto.
+    let mut vectors = vec![
+        TestVector {
+            name: "binary_agreement_bval",
+            description: "`BVal(true)` in epoch `3`: the epoch, the variant indices `0` of \
+                          `SbvBroadcast` and `0` of `BVal`, and the value `1`.",
+            message: MessageContent::SbvBroadcast(SbvMessage::BVal(true)).with_epoch(3),
+            hex: "0300000000000000000000000000000001",
+        },
+        TestVector {
+            name: "binary_agreement_aux",
+            description: "`Aux(false)` in epoch `3`: the epoch, the variant indices `0` of \
+                          `SbvBroadcast` and `1` of `Aux`, and the value `0`.",
+            message: MessageContent::SbvBroadcast(SbvMessage::Aux(false)).with_epoch(3),
+            hex: "0300000000000000000000000100000000",
+        },
+        TestVector {
+            name: "binary_agreement_conf",
+            description: "`Conf` with both values in epoch `4`: the epoch, the variant index `1` \
+                          and the set as a bit mask, with `1` for `false` and `2` for `true`.",
+            message: MessageContent::Conf(bool_set::BOTH).with_epoch(4),
+            hex: "04000000000000000100000003",
+        },
+        TestVector {
+            name: "binary_agreement_term",
+            description: "`Term(true)` in epoch `5`: the epoch, the variant index `2` and the \
+                          value `1`.",
+            message: MessageContent::Term(true).with_epoch(5),
+            hex: "05000000000000000200000001",
+        },
+    ];
+    #[cfg(not(feature = "use-insecure-test-only-mock-crypto"))]
+    vectors.push(TestVector {
+        name: "binary_agreement_coin",
+        description: "`Coin` in epoch `2`, with the message of the `threshold_sign_share` vector: \
+                      the epoch, the variant index `3` and the serialized threshold signing \
+                      message.",
+        message: MessageContent::Coin(Box::new(signature_share_message())).with_epoch(2),
+        hex: "020000000000000003000000020000000000000000807ff4874a595ff6d42aa8\
+              9e09d559eb30299037955135cb42837d46e8dfe8dea1f1c34e97f9ae521068a6\
+              8104d4eb950d2ff87090c9e418e621d3e621e0721a7719e8be8afffc3b9229ac\
+              db07c6b5c3de9af437a7a5fbf8e5fa4752a09b8879",
+    });
+    vectors
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Debug;
+
+    use serde::{de::DeserializeOwned, Serialize};
+
+    use super::*;
+
+    /// Checks that the vectors' bytes deserialize to their messages and vice versa.
+    fn check_vectors<M>(vectors: &[TestVector<M>])
+    where
+        M: Serialize + DeserializeOwned + PartialEq + Debug,
+    {
+        for vector in vectors {
+            let bytes = vector.bytes();
+            let message: M = bincode::deserialize(&bytes).expect(vector.name);
+            assert_eq!(vector.message, message, "{}", vector.name);
+            let ser_bytes = bincode::serialize(&message).expect(vector.name);
+            assert_eq!(bytes, ser_bytes, "{}", vector.name);
+        }
+    }
+
+    #[cfg(not(feature = "use-insecure-test-only-mock-crypto"))]
+    #[test]
+    fn test_threshold_sign_vectors() {
+        check_vectors(&threshold_sign_vectors());
+    }
+
+    #[cfg(feature = "broadcast")]
+    #[test]
+    fn test_broadcast_vectors() {
+        check_vectors(&broadcast_vectors());
+    }
+
+    #[test]
+    fn test_binary_agreement_vectors() {
+        check_vectors(&binary_agreement_vectors());
+    }
+}
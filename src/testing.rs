@@ -0,0 +1,124 @@
+//! # Test setup
+//!
+//! Helpers for setting up small committees in tests and examples. Unlike
+//! `NetworkInfo::generate_map`, `setup` uses a fixed seed, so every call creates the same keys,
+//! and failures involving threshold signatures or decryption can be reproduced.
+//!
+//! The module is only available with the `testing` feature, which the crate's own tests enable.
+//! Since the keys are predictable, it must not be used in production.
+
+use std::collections::BTreeMap;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::crypto::{PublicKey, PublicKeySet, SecretKey, SecretKeySet};
+use crate::NetworkInfo;
+
+/// The seed of the random number generator used by `setup`.
+const SEED: [u8; 32] = [42; 32];
+
+/// Creates `num_nodes` matching `NetworkInfo`s, with IDs `0` to `num_nodes - 1`, and a key set
+/// with threshold `num_faulty`, i.e. any `num_faulty + 1` shares can be combined. The `NetworkInfo`
+/// with ID `i` is at index `i`, and holds the secret key share with index `i`.
+///
+/// In a correctly set up network, `num_faulty` is `util::max_faulty(num_nodes)`.
+///
+/// # Panics
+///
+/// Panics if `num_nodes` is zero or not greater than `3 * num_faulty`.
+pub fn setup(num_nodes: usize, num_faulty: usize) -> (PublicKeySet, Vec<NetworkInfo<usize>>) {
+    assert!(
+        3 * num_faulty < num_nodes,
+        "{} nodes cannot tolerate {} faulty ones",
+        num_nodes,
+        num_faulty
+    );
+    let mut rng = StdRng::from_seed(SEED);
+    let sk_set = SecretKeySet::random(num_faulty, &mut rng);
+    let pk_set = sk_set.public_keys();
+    let sec_keys: Vec<SecretKey> = (0..num_nodes).map(|_| rng.gen()).collect();
+    let pub_keys: BTreeMap<usize, PublicKey> = sec_keys
+        .iter()
+        .map(SecretKey::public_key)
+        .enumerate()
+        .collect();
+    let netinfos = sec_keys
+        .into_iter()
+        .enumerate()
+        .map(|(id, sk)| {
+            NetworkInfo::try_new(
+                id,
+                sk_set.secret_key_share(id),
+                pk_set.clone(),
+                sk,
+                pub_keys.clone(),
+            )
+            .expect("key shares are dealt in the order of the IDs")
+        })
+        .collect();
+    (pk_set, netinfos)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::setup;
+    use crate::NetworkInfo;
+
+    #[test]
+    fn test_setup() {
+        let doc = b"committee";
+        for &(num_nodes, num_faulty) in &[(1, 0), (4, 1), (7, 2), (7, 1)] {
+            let (pk_set, netinfos) = setup(num_nodes, num_faulty);
+            assert_eq!(num_nodes, netinfos.len());
+            assert_eq!(num_faulty, pk_set.threshold());
+            for (id, netinfo) in netinfos.iter().enumerate() {
+                assert_eq!(id, *netinfo.our_id());
+                assert_eq!(Some(id), netinfo.node_index(&id));
+                assert_eq!(pk_set, *netinfo.public_key_set());
+                // The infos pass the key share validation of `try_new`.
+                let validated = NetworkInfo::try_new(
+                    id,
+                    netinfo.secret_key_share().cloned(),
+                    pk_set.clone(),
+                    netinfo.secret_key().clone(),
+                    netinfo.public_key_map().clone(),
+                );
+                assert!(validated.is_ok());
+            }
+
+            // Every node can verify every other node's signature share, and any `num_faulty + 1`
+            // of them combine to a valid signature.
+            let shares: BTreeMap<_, _> = netinfos
+                .iter()
+                .map(|netinfo| {
+                    let share = netinfo.secret_key_share().expect("share").sign(doc);
+                    (*netinfo.our_id(), share)
+                })
+                .collect();
+            for netinfo in &netinfos {
+                for (id, share) in &shares {
+                    let pk_share = netinfo.public_key_share(id).expect("public key share");
+                    assert!(pk_share.verify(share, doc));
+                }
+            }
+            let sig = pk_set
+                .combine_signatures(shares.iter().take(num_faulty + 1))
+                .expect("combine signatures");
+            assert!(pk_set.public_key().verify(&sig, doc));
+        }
+    }
+
+    #[test]
+    fn test_setup_is_deterministic() {
+        let (pk_set0, netinfos0) = setup(4, 1);
+        let (pk_set1, netinfos1) = setup(4, 1);
+        assert_eq!(pk_set0, pk_set1);
+        for (netinfo0, netinfo1) in netinfos0.iter().zip(&netinfos1) {
+            assert_eq!(netinfo0.public_key_map(), netinfo1.public_key_map());
+            assert_eq!(netinfo0.secret_key_share(), netinfo1.secret_key_share());
+        }
+    }
+}
@@ -215,7 +215,7 @@ impl<N: NodeIdT> ThresholdDecrypt<N> {
 
     /// Outputs the decrypted message, if we have the ciphertext and enough shares.
     fn try_output(&mut self) -> Result<Step<N>> {
-        if self.terminated || self.shares.len() <= self.netinfo.num_faulty() {
+        if self.terminated || !self.netinfo.has_weak_quorum(self.shares.len()) {
             return Ok(Step::default()); // Not enough shares yet, or already terminated.
         }
         let ct = match self.ciphertext {
@@ -6,8 +6,18 @@
 //! threshold signature validation peer. When at least _f + 1_ validators have shared their
 //! signatures in this manner, each node outputs the same, valid signature of the data.
 //!
+//! The document can be any application message, e.g. a checkpoint to be co-signed by the
+//! network: `sign_document` sets it and signs in one call. The output is the group signature,
+//! which can be verified with the master public key of the `PublicKeySet`.
+//!
 //! In addition to signing, this can also be used as a source of pseudorandomness: The signature
-//! cannot be known until more than _f_ validators have contributed their shares.
+//! cannot be known until more than _f_ validators have contributed their shares. The common coin
+//! of `BinaryAgreement` is such a signature, projected to its parity.
+//!
+//! If there are fewer than four validators, _f_ is `0` and every validator outputs the signature
+//! right after signing, based on its own share alone. The coin value is then known to any single
+//! validator, which is only acceptable because such a network does not tolerate faulty nodes
+//! anyway.
 //!
 //! ## How it works
 //!
@@ -15,19 +25,32 @@
 //! key and message, there is exactly one valid signature. This group signature is produced using
 //! signature shares from any combination of _f + 1_ secret key share holders.
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Debug};
+use std::mem;
+use std::result;
 use std::sync::Arc;
-use std::{fmt, result};
+use std::time::Duration;
 
-use crate::crypto::{self, hash_g2, Signature, SignatureShare, G2};
+use crate::crypto::{self, hash_g2, PublicKeySet, SecretKeyShare, Signature, SignatureShare, G2};
+use byteorder::{BigEndian, ByteOrder};
+use derivative::Derivative;
 use failure::Fail;
+use hex_fmt::HexFmt;
 use log::debug;
 use rand::Rng;
 use rand_derive::Rand;
-use serde_derive::{Deserialize, Serialize};
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::{Serialize, Serializer};
+use tiny_keccak::sha3_256;
 
+use crate::crypto_util;
 use crate::fault_log::{Fault, FaultKind, FaultLog};
-use crate::{DistAlgorithm, NetworkInfo, NodeIdT, Target};
+use crate::util::RoundCost;
+use crate::{
+    AlgoMetrics, Diagnose, DiagnosticTree, DistAlgorithm, InstanceId, Instrumented, NetworkInfo,
+    NodeIdT, Target,
+};
 
 /// A threshold signing error.
 #[derive(Clone, Eq, PartialEq, Debug, Fail)]
@@ -47,29 +70,259 @@ pub enum Error {
     /// Document hash is not set, cannot sign or verify signatures.
     #[fail(display = "Document hash is not set, cannot sign or verify signatures")]
     DocumentHashIsNone,
+    /// The network info can only be replaced after the instance has terminated.
+    #[fail(display = "Cannot replace the network info before termination")]
+    NotTerminated,
+    /// The nonce can only be rotated after the deferred signature has been released.
+    #[fail(display = "Cannot rotate the nonce before the deferred signature is released")]
+    UnreleasedOutput,
 }
 
 /// A threshold signing result.
 pub type Result<T> = ::std::result::Result<T, Error>;
 
-/// A threshold signing message, containing a signature share.
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Rand)]
-pub struct Message(pub SignatureShare);
+/// The version of the serialized format of `Message`.
+pub const WIRE_VERSION: u8 = 2;
+
+/// A threshold signing message, containing a signature share for a signing round.
+///
+/// The message is serialized as the `WIRE_VERSION`, the round as a `u64`, and the share in the
+/// format of `threshold_crypto`. If that format ever changes, the version is incremented, so that
+/// a message from a node with an incompatible version fails to deserialize with a clear error,
+/// instead of producing a share that fails verification.
+#[derive(Clone, Debug, PartialEq, Rand)]
+pub struct Message {
+    /// The signing round, i.e. the number of times the sender called `rotate_nonce`.
+    pub round: u64,
+    /// The sender's signature share for that round's document.
+    pub share: SignatureShare,
+}
+
+impl Message {
+    /// Returns a message with the signature share for the given round.
+    pub fn new(round: u64, share: SignatureShare) -> Self {
+        Message { round, share }
+    }
+
+    /// Returns the version of the serialized format of the message.
+    pub fn wire_version() -> u8 {
+        WIRE_VERSION
+    }
+}
+
+impl Serialize for Message {
+    fn serialize<S: Serializer>(&self, serializer: S) -> result::Result<S::Ok, S::Error> {
+        (WIRE_VERSION, self.round, &self.share).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Message {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> result::Result<Self, D::Error> {
+        deserializer.deserialize_tuple(3, MessageVisitor)
+    }
+}
+
+/// A visitor that checks the wire version before deserializing the round and signature share.
+struct MessageVisitor;
+
+impl<'de> Visitor<'de> for MessageVisitor {
+    type Value = Message;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a wire version, a round and a signature share")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> result::Result<Message, A::Error> {
+        let version: u8 = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        if version != WIRE_VERSION {
+            return Err(de::Error::custom(format!(
+                "unsupported threshold signing wire version {}, expected {}",
+                version, WIRE_VERSION
+            )));
+        }
+        let round = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let share = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+        Ok(Message { round, share })
+    }
+}
+
+/// A diagnostic event reported by `ThresholdSign`. Unlike a fault, this doesn't blame any node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The caller's deadline passed before the signature could be output.
+    DeadlineMissed {
+        /// The number of signature shares received so far.
+        received_shares: usize,
+        /// Whether the document to sign was known.
+        has_document: bool,
+    },
+}
+
+/// A record of a successfully computed signature, e.g. a coin flip, for analytics.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoinResult {
+    /// The SHA3-256 hash of the signed document, i.e. of the nonce.
+    pub doc_hash: [u8; 32],
+    /// The coin value, i.e. the signature's parity.
+    pub parity: bool,
+    /// The number of shares that were combined into the signature.
+    pub shares_used: usize,
+    /// The time between setting the document and computing the signature, if a clock was set with
+    /// `ThresholdSignBuilder::clock`.
+    pub elapsed: Option<Duration>,
+}
+
+/// A callback that receives a `CoinResult` whenever an instance computes a signature.
+pub type CoinSink = Arc<dyn Fn(CoinResult) + Send + Sync>;
+
+/// A clock that returns the current time, measured from an arbitrary but fixed point.
+pub type Clock = Arc<dyn Fn() -> Duration + Send + Sync>;
+
+/// A provider of our own signature shares.
+///
+/// By default, `ThresholdSign` signs with the secret key share in our `NetworkInfo`. If the
+/// secret key share is kept elsewhere, e.g. in a hardware security module, an implementation of
+/// this trait can be set with `ThresholdSign::set_share_signer` instead.
+pub trait ShareSigner: Debug + Send + Sync {
+    /// Returns our signature share for the given document.
+    fn sign(&self, doc: &[u8]) -> SignatureShare;
+}
+
+impl ShareSigner for SecretKeyShare {
+    fn sign(&self, doc: &[u8]) -> SignatureShare {
+        SecretKeyShare::sign(self, doc)
+    }
+}
 
 /// A threshold signing algorithm instance. On input, broadcasts our threshold signature share. Upon
 /// receiving at least `num_faulty + 1` shares, attempts to combine them into a signature. If that
 /// signature is valid, the instance outputs it and terminates; otherwise the instance aborts.
-#[derive(Debug)]
+///
+/// A clone is an independent copy of the instance's state, e.g. to try out how it would handle a
+/// message without affecting the original. Cloning copies all received shares, so it takes time
+/// and memory proportional to their number, but the `NetworkInfo` is shared. The share signer,
+/// coin sink and clock are shared, too: If a coin sink is set, a clone reports its signatures to
+/// it as well.
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
 pub struct ThresholdSign<N> {
     netinfo: Arc<NetworkInfo<N>>,
+    /// The state of the current signing round.
+    state: RoundState<N>,
+    /// The provider of our signature shares, if it is not the secret key share in `netinfo`.
+    signer: Option<Arc<dyn ShareSigner>>,
+    /// Whether `shutdown` was called, after which we don't sign anymore.
+    shut_down: bool,
+    /// Whether shares received after termination are verified and kept in `late_shares`.
+    collect_late_shares: bool,
+    /// The failures forced by a test.
+    #[cfg(feature = "fault-injection")]
+    injected_faults: InjectedFaults<N>,
+    /// Whether the signature is kept until `release_output` is called, instead of being output.
+    defer_output: bool,
+    /// The number of messages received from other nodes.
+    messages_received: usize,
+    /// The number of faults we have reported.
+    faults_reported: usize,
+    /// The number of times `rotate_nonce` was called.
+    round: u64,
+    /// The shares for the next round, from nodes that called `rotate_nonce` before us. They are
+    /// verified once we rotate, too.
+    next_round_shares: BTreeMap<N, SignatureShare>,
+    /// The callback that receives a record of every computed signature.
+    #[derivative(Debug = "ignore")]
+    coin_sink: Option<CoinSink>,
+    /// The clock used to measure the time until the signature is computed.
+    #[derivative(Debug = "ignore")]
+    clock: Option<Clock>,
+}
+
+/// The state of a single signing round. `rotate_nonce` replaces it with a new one.
+#[derive(Clone, Debug)]
+struct RoundState<N> {
     /// The hash of the document to be signed.
     doc_hash: Option<G2>,
+    /// A short hash of the document, to identify the signing round in log messages.
+    doc_id: Option<[u8; 32]>,
+    /// The document to be signed, to pass it to the `signer`.
+    doc: Option<Vec<u8>>,
     /// All received threshold signature shares, together with the node index.
     received_shares: BTreeMap<N, (usize, SignatureShare)>,
     /// Whether we already sent our shares.
     had_input: bool,
+    /// Whether we already multicast our share, either via `sign` or `publish_share`. This is the
+    /// only message an instance ever sends, so it sends at most one in total.
+    share_published: bool,
     /// Termination flag.
     terminated: bool,
+    /// The valid shares received after termination, if `collect_late_shares` is set.
+    late_shares: BTreeMap<N, (usize, SignatureShare)>,
+    /// Whether `netinfo` was replaced since the current round terminated. Late shares for that
+    /// round can't be verified with the new keys anymore, so they are ignored.
+    netinfo_rotated: bool,
+    /// The signature, if it was computed with `defer_output` and hasn't been released yet.
+    deferred_output: Option<Signature>,
+    /// The node indices of the shares that were combined into the signature, once it is computed.
+    combining_set: Option<Vec<u64>>,
+    /// The signature, once it is computed. This is kept after termination.
+    signature: Option<Signature>,
+    /// Whether our own share had been received when the signature was computed.
+    contributed: bool,
+    /// The clock's time when the document was set, if there is a clock.
+    started_at: Option<Duration>,
+    /// The clock's time when the last valid share arrived, if there is a clock.
+    last_share_at: Option<Duration>,
+}
+
+impl<N: Ord> RoundState<N> {
+    /// Returns the state of a round without a document or shares. If `terminated` is `true`, the
+    /// round is never signed, e.g. because the instance was shut down.
+    fn new(terminated: bool) -> Self {
+        RoundState {
+            doc_hash: None,
+            doc_id: None,
+            doc: None,
+            received_shares: BTreeMap::new(),
+            had_input: false,
+            share_published: false,
+            terminated,
+            late_shares: BTreeMap::new(),
+            netinfo_rotated: false,
+            deferred_output: None,
+            combining_set: None,
+            signature: None,
+            contributed: false,
+            started_at: None,
+            last_share_at: None,
+        }
+    }
+}
+
+/// Failures that a test can force in a `ThresholdSign` instance, to exercise the error paths that
+/// are hard to trigger with valid cryptographic data.
+#[cfg(feature = "fault-injection")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InjectedFaults<N> {
+    /// If `true`, combining the shares fails with `Error::VerificationFailed`.
+    pub fail_combine: bool,
+    /// The senders whose shares fail verification, even if they are valid.
+    pub invalid_shares: BTreeSet<N>,
+}
+
+#[cfg(feature = "fault-injection")]
+impl<N: Ord> Default for InjectedFaults<N> {
+    fn default() -> Self {
+        InjectedFaults {
+            fail_combine: false,
+            invalid_shares: BTreeSet::new(),
+        }
+    }
 }
 
 /// A step returned from `ThresholdSign`. It contains at most one output.
@@ -97,9 +350,14 @@ impl<N: NodeIdT> DistAlgorithm for ThresholdSign<N> {
         self.handle_message(sender_id, message)
     }
 
+    /// Stops signing and terminates.
+    fn shutdown(&mut self) -> Step<N> {
+        self.shutdown()
+    }
+
     /// Whether the algorithm has terminated.
     fn terminated(&self) -> bool {
-        self.terminated
+        self.state.terminated
     }
 
     fn our_id(&self) -> &Self::NodeId {
@@ -107,18 +365,76 @@ impl<N: NodeIdT> DistAlgorithm for ThresholdSign<N> {
     }
 }
 
+impl<N: NodeIdT> Instrumented for ThresholdSign<N> {
+    /// Returns the metrics. Our own share is counted as sent once it is multicast, but not as
+    /// received.
+    fn metrics(&self) -> AlgoMetrics {
+        AlgoMetrics {
+            messages_sent: if self.state.share_published { 1 } else { 0 },
+            messages_received: self.messages_received,
+            faults: self.faults_reported,
+            epoch: None,
+            terminated: self.state.terminated,
+        }
+    }
+}
+
+impl<N: NodeIdT> InstanceId for ThresholdSign<N> {
+    /// Returns the document to be signed, e.g. the coin's nonce, or an empty slice if it is not
+    /// set yet. After `rotate_nonce`, this is the new round's document.
+    fn instance_id(&self) -> &[u8] {
+        self.state.doc.as_ref().map_or(&[], |doc| &doc[..])
+    }
+}
+
+impl<N: NodeIdT> Diagnose for ThresholdSign<N> {
+    /// Returns a leaf with the number of valid shares received, including our own, out of the
+    /// _f + 1_ required, and what the instance is waiting for.
+    fn diagnose(&self) -> DiagnosticTree {
+        let state = if self.state.terminated {
+            "terminated"
+        } else if self.state.doc_hash.is_none() {
+            "waiting for document"
+        } else if !self.state.had_input {
+            "waiting for input"
+        } else {
+            "waiting for shares"
+        };
+        DiagnosticTree::new(format!(
+            "ThresholdSign: {}/{} shares, {}",
+            self.state.received_shares.len(),
+            self.netinfo.num_faulty() + 1,
+            state
+        ))
+    }
+}
+
 impl<N: NodeIdT> ThresholdSign<N> {
     /// Creates a new instance of `ThresholdSign`, with the goal to collaboratively sign `doc`.
     pub fn new(netinfo: Arc<NetworkInfo<N>>) -> Self {
         ThresholdSign {
             netinfo,
-            doc_hash: None,
-            received_shares: BTreeMap::new(),
-            had_input: false,
-            terminated: false,
+            state: RoundState::new(false),
+            signer: None,
+            defer_output: false,
+            messages_received: 0,
+            faults_reported: 0,
+            round: 0,
+            next_round_shares: BTreeMap::new(),
+            coin_sink: None,
+            clock: None,
+            shut_down: false,
+            collect_late_shares: false,
+            #[cfg(feature = "fault-injection")]
+            injected_faults: InjectedFaults::default(),
         }
     }
 
+    /// Returns a new `ThresholdSignBuilder`, to configure the optional settings of an instance.
+    pub fn builder(netinfo: Arc<NetworkInfo<N>>) -> ThresholdSignBuilder<N> {
+        ThresholdSignBuilder::new(netinfo)
+    }
+
     /// Creates a new instance of `ThresholdSign`, including setting the document to sign.
     pub fn new_with_document<M: AsRef<[u8]>>(netinfo: Arc<NetworkInfo<N>>, doc: M) -> Result<Self> {
         let mut ts = ThresholdSign::new(netinfo);
@@ -128,60 +444,544 @@ impl<N: NodeIdT> ThresholdSign<N> {
 
     /// Sets doc_hash. Signature shares can only be sent after this function is completed.
     pub fn set_document<M: AsRef<[u8]>>(&mut self, doc: M) -> Result<()> {
-        if self.doc_hash.is_some() {
+        if self.state.doc_hash.is_some() {
             return Err(Error::MultipleMessagesToSign);
         }
-        self.doc_hash = Some(hash_g2(doc));
+        self.init_document(doc.as_ref());
         Ok(())
     }
 
+    /// Sets the document, its hash and its ID.
+    fn init_document(&mut self, doc: &[u8]) {
+        self.state.doc_id = Some(sha3_256(doc));
+        self.state.doc = Some(doc.to_vec());
+        self.state.doc_hash = Some(hash_g2(doc));
+        self.state.started_at = self.clock.as_ref().map(|clock| clock());
+    }
+
+    /// Sets the document and sends our signature share. This is equivalent to `set_document`
+    /// followed by `sign`.
+    pub fn sign_document<M: AsRef<[u8]>>(&mut self, doc: M) -> Result<Step<N>> {
+        self.set_document(doc)?;
+        self.sign()
+    }
+
+    /// Abandons the current signing round, and starts a new one with the given nonce. The instance
+    /// can then sign again with `sign`, even if it had already terminated.
+    ///
+    /// All shares received so far are dropped, and the round counter is incremented. The document
+    /// is now `round_document(nonce, round)`, so the counter is signed together with the nonce:
+    /// Shares from an earlier round fail verification, even if that round used the same nonce. All
+    /// nodes must therefore rotate in the same way before they sign again.
+    ///
+    /// Every message is tagged with its round. Messages from earlier rounds are ignored, and the
+    /// shares for the next round, from nodes that rotated before us, are kept and handled here.
+    /// The returned step contains the faults of their senders if they are invalid, and the
+    /// signature if they already suffice to compute it. Messages for any later round are ignored.
+    ///
+    /// Returns an error if the signature was deferred with `set_defer_output(true)` and hasn't been
+    /// retrieved with `release_output` yet, so that it isn't lost.
+    pub fn rotate_nonce<M: AsRef<[u8]>>(&mut self, nonce: M) -> Result<Step<N>> {
+        if self.state.deferred_output.is_some() {
+            return Err(Error::UnreleasedOutput);
+        }
+        self.round += 1;
+        debug!("{} event=rotate_nonce round={}", self, self.round);
+        self.state = RoundState::new(self.shut_down);
+        self.init_document(&round_document(nonce, self.round));
+        let next_round_shares = mem::replace(&mut self.next_round_shares, BTreeMap::new());
+        // All shares are recorded before we try to combine them, so that an error doesn't discard
+        // the remaining ones.
+        let mut step = Step::default();
+        for (sender_id, share) in next_round_shares {
+            // The network info may have been replaced since the share was received.
+            match self.netinfo.node_index(&sender_id) {
+                Some(idx) if !self.shut_down => {
+                    if let Some(fault_step) = self.record_share(&sender_id, idx, share) {
+                        step.extend(fault_step);
+                    }
+                }
+                _ => (),
+            }
+        }
+        step.extend(self.try_output()?);
+        Ok(step)
+    }
+
+    /// Replaces the network info, e.g. with the new key set after a key rotation in
+    /// `DynamicHoneyBadger`. Returns an error if the instance hasn't terminated yet, so a round is
+    /// never signed with two different key sets.
+    ///
+    /// The finished round's signature remains available, but late shares for it are ignored from
+    /// now on. The next round, started with `rotate_nonce`, uses the new keys. A custom
+    /// `ShareSigner` is not replaced and must be switched to the new key share separately.
+    pub fn rotate_netinfo(&mut self, netinfo: Arc<NetworkInfo<N>>) -> Result<()> {
+        if !self.state.terminated {
+            return Err(Error::NotTerminated);
+        }
+        debug!("{} event=rotate_netinfo", self);
+        self.netinfo = netinfo;
+        self.state.netinfo_rotated = true;
+        Ok(())
+    }
+
+    /// Returns the number of times `rotate_nonce` was called.
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    /// Sets the provider of our signature shares, which is used instead of the secret key share
+    /// in our `NetworkInfo`. This must be called before `sign` or `publish_share`, and has no
+    /// effect if we are not a validator. With a signer, our `NetworkInfo` doesn't need to contain
+    /// a secret key share.
+    ///
+    /// Our own share is verified like everyone else's: If the signer returns an invalid share, we
+    /// report ourselves as faulty and don't count it.
+    pub fn set_share_signer<S: ShareSigner + 'static>(&mut self, signer: S) {
+        self.signer = Some(Arc::new(signer));
+    }
+
+    /// Sets whether the signature should be kept in the instance instead of being output.
+    ///
+    /// If this is `true`, the step in which the signature is computed does not contain it. The
+    /// instance still terminates, and the signature can then be retrieved with `release_output`.
+    /// This allows the caller to release the outputs of several instances in a particular order.
+    pub fn set_defer_output(&mut self, defer: bool) {
+        self.defer_output = defer;
+    }
+
+    /// Sets whether shares that arrive after the signature has been computed are still verified
+    /// and recorded, e.g. to audit which nodes participated, even if they were late. Invalid late
+    /// shares are reported as faults. The late shares never affect the output.
+    pub fn set_collect_late_shares(&mut self, collect: bool) {
+        self.collect_late_shares = collect;
+    }
+
+    /// Returns the IDs of all nodes whose valid share we have received, including shares that
+    /// arrived after termination if `set_collect_late_shares(true)` was called.
+    pub fn all_contributors(&self) -> BTreeSet<N> {
+        self.state
+            .received_shares
+            .keys()
+            .chain(self.state.late_shares.keys())
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the signature if it has been computed with `set_defer_output(true)` and not
+    /// released yet.
+    pub fn release_output(&mut self) -> Option<Signature> {
+        self.state.deferred_output.take()
+    }
+
+    /// Returns an estimate of the time until the signature is computed, based on the rate at which
+    /// valid shares, including our own, have arrived since the document was set.
+    ///
+    /// The estimate assumes that the remaining shares arrive at the same average interval as the
+    /// previous ones. It is zero once the signature has been computed, or if the missing shares
+    /// are already overdue. Returns `None` if there is no clock (see
+    /// `ThresholdSignBuilder::clock`), if no share has arrived yet, or after `shutdown`.
+    pub fn eta_to_output(&self) -> Option<Duration> {
+        if self.state.signature.is_some() {
+            return Some(Duration::default());
+        }
+        if self.state.terminated || self.state.received_shares.is_empty() {
+            return None;
+        }
+        let clock = self.clock.as_ref()?;
+        let (start, last) = (self.state.started_at?, self.state.last_share_at?);
+        let count = self.state.received_shares.len();
+        let interval = last.checked_sub(start).unwrap_or_default() / count as u32;
+        let missing = (self.netinfo.num_faulty() + 1).saturating_sub(count);
+        let expected = last + interval * missing as u32;
+        Some(expected.checked_sub(clock()).unwrap_or_default())
+    }
+
+    /// Returns the node indices of the _f + 1_ shares that were combined into the signature, in
+    /// ascending order of the signers' IDs, or `None` if the signature hasn't been computed yet.
+    ///
+    /// Usually the signature is computed as soon as the _f + 1_-th valid share arrives, so exactly
+    /// those shares are used. Only shares received before the document was set can accumulate
+    /// beyond that: Then the valid shares of the _f + 1_ signers with the lowest IDs, i.e. the
+    /// lowest node indices, are combined, regardless of the order in which they arrived. Since
+    /// the signature is unique, it doesn't depend on the choice anyway.
+    ///
+    /// Together with the shares, these can be used to audit the round, e.g. using `verify_round`.
+    pub fn combining_set(&self) -> Option<Vec<u64>> {
+        self.state.combining_set.clone()
+    }
+
+    /// Combines the received shares like the signature to be output, and returns the result
+    /// together with whether it is valid, without modifying the instance. Returns an error if the
+    /// document is not set or there are fewer than _f + 1_ shares.
+    ///
+    /// If the combined signature is invalid, the regular path aborts with
+    /// `Error::VerificationFailed`. This method returns it instead, so that a monitor can export
+    /// it together with the shares as evidence. Note that every share is verified on receipt, so
+    /// an invalid result indicates corrupted state rather than merely faulty peers.
+    pub fn try_combine_diagnostic(&self) -> Result<(Signature, bool)> {
+        let hash = self.state.doc_hash.ok_or(Error::DocumentHashIsNone)?;
+        let (sig, _) = self.combine_sig()?;
+        #[cfg(feature = "fault-injection")]
+        {
+            if self.injected_faults.fail_combine {
+                return Ok((sig, false));
+            }
+        }
+        let verified = self
+            .netinfo
+            .public_key_set()
+            .public_key()
+            .verify_g2(&sig, hash);
+        Ok((sig, verified))
+    }
+
+    /// Returns `true` if the signature has been computed, and our own share was among the shares
+    /// received at that point, i.e. if we had signed before enough shares arrived.
+    ///
+    /// This is `false` for observers, and for validators that only signed after others had
+    /// already produced the signature. Note that only _f + 1_ of the received shares are actually
+    /// combined; `combining_set` returns which ones.
+    pub fn contributed_to_output(&self) -> bool {
+        self.state.contributed
+    }
+
+    /// Returns the public key set the shares are verified with. Its master public key verifies
+    /// the signature.
+    pub fn public_key_set(&self) -> &PublicKeySet {
+        self.netinfo.public_key_set()
+    }
+
+    /// Returns the signature once it has been computed, even after it was output or released.
+    ///
+    /// The signature is unpredictable until more than _f_ validators have signed, and anyone with
+    /// the public master key can verify it for the document. It can therefore be used as the output
+    /// of a verifiable random function, with the document as the nonce, and the signature itself as
+    /// the proof.
+    pub fn output_signature(&self) -> Option<&Signature> {
+        self.state.signature.as_ref()
+    }
+
+    /// Returns the serialized signature, which serves as a proof of the randomness derived from it,
+    /// or `None` if it hasn't been computed yet.
+    pub fn proof_bytes(&self) -> Option<Vec<u8>> {
+        self.state
+            .signature
+            .as_ref()
+            .map(|sig| sig.to_bytes().to_vec())
+    }
+
+    /// Returns `true` if the share is valid for the document and the public key share of the
+    /// validator with the given node index, or an error if the document is not set yet. Returns
+    /// `false` if there is no validator with that index.
+    ///
+    /// This doesn't need the sender's ID, so it can e.g. check a share with a claimed index whose
+    /// sender is not known yet. Unlike the shares of known senders, such a share is not stored.
+    pub fn verify_share_by_index(&self, idx: u64, share: &SignatureShare) -> Result<bool> {
+        let hash = self.state.doc_hash.ok_or(Error::DocumentHashIsNone)?;
+        Ok(self
+            .netinfo
+            .public_key_share_by_index(idx)
+            .map_or(false, |pk_share| pk_share.verify_g2(share, hash)))
+    }
+
+    /// Returns `true` if more than _f_ shares have been received, so that more shares are not
+    /// needed anymore: If the document is known, the shares have been verified and the signature
+    /// is output in the same step in which this becomes `true`. Otherwise the shares will only be
+    /// verified once the document is set, and invalid ones will be removed again.
+    pub fn has_quorum(&self) -> bool {
+        self.netinfo
+            .has_weak_quorum(self.state.received_shares.len())
+    }
+
+    /// Returns the IDs of the validators whose signature shares we haven't received yet, or an
+    /// empty list if the signature has already been output.
+    ///
+    /// Shares that turn out to be invalid once the document is set are removed again, so their
+    /// senders are listed here, too.
+    pub fn missing_contributors(&self) -> Vec<N> {
+        if self.state.terminated {
+            return Vec::new();
+        }
+        self.netinfo
+            .all_ids()
+            .filter(|id| !self.state.received_shares.contains_key(id))
+            .cloned()
+            .collect()
+    }
+
+    /// Handles the expiry of a deadline set by the caller, e.g. using a timer in the network
+    /// layer, and returns an `Event::DeadlineMissed` if the signature has not been output yet.
+    ///
+    /// Slow nodes are not necessarily faulty, so this doesn't affect the algorithm's state: It
+    /// only informs the caller, who can then e.g. escalate or log the delay. Returns `None` if the
+    /// instance has already terminated.
+    pub fn handle_timeout(&mut self) -> Option<Event> {
+        if self.state.terminated {
+            return None;
+        }
+        debug!(
+            "{} event=deadline_missed shares={} had_input={}",
+            self,
+            self.state.received_shares.len(),
+            self.state.had_input
+        );
+        Some(Event::DeadlineMissed {
+            received_shares: self.state.received_shares.len(),
+            has_document: self.state.doc_hash.is_some(),
+        })
+    }
+
+    /// Returns the messages that `sign` would send if it were called now, without changing the
+    /// state. Returns an error if the message to sign hasn't been received yet.
+    ///
+    /// This computes our signature share, so it is as expensive as signing. It can be used e.g. to
+    /// estimate the network load before committing to the input.
+    pub fn preview_input(&self) -> Result<Vec<Message>> {
+        if self.state.had_input || self.shut_down {
+            return Ok(Vec::new());
+        }
+        let hash = self.state.doc_hash.ok_or(Error::DocumentHashIsNone)?;
+        if self.state.share_published {
+            return Ok(Vec::new());
+        }
+        let round = self.round;
+        let msg = |share| Message::new(round, share);
+        Ok(self.our_share(hash).map(msg).into_iter().collect())
+    }
+
     /// Sends our signature shares, and if we have collected enough, returns the full signature.
     /// Returns an error if the message to sign hasn't been received yet.
     pub fn sign(&mut self) -> Result<Step<N>> {
-        if self.had_input {
+        if self.state.had_input || self.shut_down {
             // Don't waste time on redundant shares.
             return Ok(Step::default());
         }
-        let hash = self.doc_hash.ok_or(Error::DocumentHashIsNone)?;
-        self.had_input = true;
+        let hash = self.state.doc_hash.ok_or(Error::DocumentHashIsNone)?;
+        self.state.had_input = true;
         let mut step = Step::default();
         step.fault_log.extend(self.remove_invalid_shares());
-        let msg = match self.netinfo.secret_key_share() {
-            Some(sks) => Message(sks.sign_g2(hash)),
+        let msg = match self.our_share(hash) {
+            Some(share) => Message::new(self.round, share),
             None => return Ok(step.join(self.try_output()?)), // Not a validator.
         };
-        step.messages.push(Target::All.message(msg.clone()));
+        if !self.state.share_published {
+            self.state.share_published = true;
+            step.messages.push(Target::All.message(msg.clone()));
+        }
         let id = self.our_id().clone();
-        step.extend(self.handle_message(&id, msg)?);
+        step.extend(self.handle_share(&id, msg.share)?);
         Ok(step)
     }
 
+    /// Multicasts our signature share without counting it towards our own output yet. Returns an
+    /// error if the message to sign hasn't been received yet.
+    ///
+    /// This can be used to send the share as early as possible, e.g. before we have decided
+    /// whether we actually want to sign. We only use our own share once `sign` is called, which
+    /// doesn't send it again. Until then, we output the signature only if we receive _f + 1_
+    /// shares from other nodes.
+    ///
+    /// Note that this only affects our own output. For everyone else, publishing the share is
+    /// exactly as revealing as `sign`: It counts towards the _f + 1_ shares that anyone needs to
+    /// compute the signature, so together with the shares of the _f_ faulty nodes, the adversary
+    /// may learn the signature immediately. Only publish the share early if that is acceptable,
+    /// e.g. if it is safe for the value of a coin to become known at this point.
+    pub fn publish_share(&mut self) -> Result<Step<N>> {
+        let hash = self.state.doc_hash.ok_or(Error::DocumentHashIsNone)?;
+        if self.state.share_published || self.state.terminated {
+            return Ok(Step::default());
+        }
+        let msg = match self.our_share(hash) {
+            Some(share) => Message::new(self.round, share),
+            None => return Ok(Step::default()), // Not a validator.
+        };
+        self.state.share_published = true;
+        Ok(Target::All.message(msg).into())
+    }
+
+    /// Sets the failures to force in this instance. This replaces any faults injected earlier.
+    #[cfg(feature = "fault-injection")]
+    pub fn inject_faults(&mut self, faults: InjectedFaults<N>) {
+        self.injected_faults = faults;
+    }
+
+    /// Records a share as if it had been received from `sender_id`, without verifying it or trying
+    /// to output the signature. Returns an error if the sender is not a validator.
+    ///
+    /// This makes it possible to combine a tampered set of shares, e.g. to test
+    /// `try_combine_diagnostic`.
+    #[cfg(feature = "fault-injection")]
+    pub fn inject_share(&mut self, sender_id: &N, share: SignatureShare) -> Result<()> {
+        let idx = self
+            .netinfo
+            .node_index(sender_id)
+            .ok_or(Error::UnknownSender)?;
+        self.state
+            .received_shares
+            .insert(sender_id.clone(), (idx, share));
+        Ok(())
+    }
+
+    /// Terminates the instance without waiting for the signature, and ignores any further input
+    /// and messages.
+    ///
+    /// A share that we have published was multicast in the step returned by `sign` or
+    /// `publish_share`, so nothing else is owed to our peers and the returned step is always
+    /// empty: They can still compute the signature from the messages we sent before shutting
+    /// down. If we haven't signed yet, we never will, as if we were silent.
+    pub fn shutdown(&mut self) -> Step<N> {
+        if !self.shut_down {
+            debug!("{} event=shutdown had_input={}", self, self.state.had_input);
+            self.shut_down = true;
+            self.state.terminated = true;
+        }
+        Step::default()
+    }
+
     /// Handles a message with a signature share received from `sender_id`.
     ///
     /// This must be called with every message we receive from another node.
     ///
     /// If we have collected enough, returns the full signature.
+    ///
+    /// Messages with our own ID as the sender are ignored: Our share is handled when we sign, so
+    /// receiving it again indicates that the transport loops our messages back to us.
+    ///
+    /// A message for the next round is kept until we call `rotate_nonce`, and messages for any
+    /// other round are ignored.
     pub fn handle_message(&mut self, sender_id: &N, message: Message) -> Result<Step<N>> {
-        if self.terminated {
+        if sender_id == self.our_id() {
+            debug!("{} event=own_message_received", self);
             return Ok(Step::default());
         }
-        let Message(share) = message;
+        self.messages_received += 1;
+        let Message { round, share } = message;
+        if round == self.round {
+            self.handle_share(sender_id, share)
+        } else if Some(round) == self.round.checked_add(1) {
+            self.handle_next_round_share(sender_id, share)
+        } else {
+            debug!(
+                "{} event=other_round_share sender={:?} round={}",
+                self, sender_id, round
+            );
+            Ok(Step::default())
+        }
+    }
+
+    /// Keeps the first share from `sender_id` for the next round, to handle it in `rotate_nonce`.
+    fn handle_next_round_share(&mut self, sender_id: &N, share: SignatureShare) -> Result<Step<N>> {
+        if !self.netinfo.is_node_validator(sender_id) {
+            return Err(Error::UnknownSender);
+        }
+        debug!("{} event=next_round_share sender={:?}", self, sender_id);
+        self.next_round_shares
+            .entry(sender_id.clone())
+            .or_insert(share);
+        Ok(Step::default())
+    }
+
+    /// Verifies and stores the signature share from `sender_id`, and returns the full signature if
+    /// we have collected enough.
+    fn handle_share(&mut self, sender_id: &N, share: SignatureShare) -> Result<Step<N>> {
+        if self.state.terminated {
+            return self.handle_late_share(sender_id, share);
+        }
         // Before checking the share, ensure the sender is a known validator
         let idx = self
             .netinfo
             .node_index(sender_id)
             .ok_or(Error::UnknownSender)?;
+        if let Some(step) = self.record_share(sender_id, idx, share) {
+            return Ok(step);
+        }
+        self.try_output()
+    }
+
+    /// Verifies the share of the sender with the given node index, and adds it to the received
+    /// shares. Returns a step with the sender's fault if the share is invalid.
+    fn record_share(
+        &mut self,
+        sender_id: &N,
+        idx: usize,
+        share: SignatureShare,
+    ) -> Option<Step<N>> {
+        if let Some(step) = self.reject_malformed_share(sender_id, &share) {
+            return Some(step);
+        }
+        if !self.is_share_valid(sender_id, &share) {
+            debug!("{} event=invalid_share sender={:?}", self, sender_id);
+            self.faults_reported += 1;
+            let fault_kind = FaultKind::UnverifiedSignatureShareSender;
+            return Some(Fault::new(sender_id.clone(), fault_kind).into());
+        }
+        debug!("{} event=share sender={:?}", self, sender_id);
+        let had_quorum = self.has_quorum();
+        if self
+            .state
+            .received_shares
+            .insert(sender_id.clone(), (idx, share))
+            .is_none()
+        {
+            self.state.last_share_at = self.clock.as_ref().map(|clock| clock());
+        }
+        if !had_quorum && self.has_quorum() {
+            debug!(
+                "{} event=quorum shares={}",
+                self,
+                self.state.received_shares.len()
+            );
+        }
+        None
+    }
+
+    /// Verifies and records a share received after termination, if `collect_late_shares` is set.
+    fn handle_late_share(&mut self, sender_id: &N, share: SignatureShare) -> Result<Step<N>> {
+        if !self.collect_late_shares
+            || self.shut_down
+            || self.state.netinfo_rotated
+            || self.state.received_shares.contains_key(sender_id)
+            || self.state.late_shares.contains_key(sender_id)
+        {
+            return Ok(Step::default());
+        }
+        let idx = self
+            .netinfo
+            .node_index(sender_id)
+            .ok_or(Error::UnknownSender)?;
+        if let Some(step) = self.reject_malformed_share(sender_id, &share) {
+            return Ok(step);
+        }
         if !self.is_share_valid(sender_id, &share) {
+            debug!("{} event=invalid_late_share sender={:?}", self, sender_id);
+            self.faults_reported += 1;
             let fault_kind = FaultKind::UnverifiedSignatureShareSender;
             return Ok(Fault::new(sender_id.clone(), fault_kind).into());
         }
-        self.received_shares.insert(sender_id.clone(), (idx, share));
-        self.try_output()
+        debug!("{} event=late_share sender={:?}", self, sender_id);
+        self.state
+            .late_shares
+            .insert(sender_id.clone(), (idx, share));
+        Ok(Step::default())
+    }
+
+    /// Returns our signature share for the document with the given hash, or `None` if we are not
+    /// a validator.
+    fn our_share(&self, hash: G2) -> Option<SignatureShare> {
+        match self.signer {
+            Some(ref signer) if self.netinfo.is_validator() => {
+                self.state.doc.as_ref().map(|doc| signer.sign(doc))
+            }
+            Some(_) => None,
+            None => self.netinfo.secret_key_share().map(|sks| sks.sign_g2(hash)),
+        }
     }
 
     /// Removes all shares that are invalid, and returns faults for their senders.
     fn remove_invalid_shares(&mut self) -> FaultLog<N> {
         let faulty_senders: Vec<N> = self
+            .state
             .received_shares
             .iter()
             .filter(|(id, (_, ref share))| !self.is_share_valid(id, share))
@@ -189,72 +989,359 @@ impl<N: NodeIdT> ThresholdSign<N> {
             .collect();
         let mut fault_log = FaultLog::default();
         for id in faulty_senders {
-            self.received_shares.remove(&id);
+            self.faults_reported += 1;
+            self.state.received_shares.remove(&id);
             fault_log.append(id, FaultKind::UnverifiedSignatureShareSender);
         }
         fault_log
     }
 
+    /// Returns a step with a fault if the share is malformed. This is checked before the expensive
+    /// verification, so that a flood of malformed shares costs us little.
+    fn reject_malformed_share(&mut self, sender_id: &N, share: &SignatureShare) -> Option<Step<N>> {
+        if crypto_util::is_well_formed(share) {
+            return None;
+        }
+        debug!("{} event=malformed_share sender={:?}", self, sender_id);
+        self.faults_reported += 1;
+        let fault_kind = FaultKind::MalformedSignatureShare;
+        Some(Fault::new(sender_id.clone(), fault_kind).into())
+    }
+
     /// Returns `true` if the share is valid, or if we don't have the message data yet.
     fn is_share_valid(&self, id: &N, share: &SignatureShare) -> bool {
-        let hash = match self.doc_hash {
-            None => return true, // No document yet. Verification postponed.
-            Some(ref doc_hash) => doc_hash,
+        #[cfg(feature = "fault-injection")]
+        {
+            if self.injected_faults.invalid_shares.contains(id) {
+                return false;
+            }
+        }
+        let pk_share = match self.netinfo.public_key_share(id) {
+            None => return false, // Unknown sender.
+            Some(pk_share) => pk_share,
         };
-        match self.netinfo.public_key_share(id) {
-            None => false, // Unknown sender.
-            Some(pk_i) => pk_i.verify_g2(&share, *hash),
+        match self.state.doc_hash {
+            None => true, // No document yet. Verification postponed.
+            Some(hash) => pk_share.verify_g2(share, hash),
         }
     }
 
     fn try_output(&mut self) -> Result<Step<N>> {
-        let hash = match self.doc_hash {
+        let hash = match self.state.doc_hash {
             Some(hash) => hash,
             None => return Ok(Step::default()),
         };
-        if !self.terminated && self.received_shares.len() > self.netinfo.num_faulty() {
-            let sig = self.combine_and_verify_sig(hash)?;
-            self.terminated = true;
+        if !self.state.terminated
+            && self
+                .netinfo
+                .has_weak_quorum(self.state.received_shares.len())
+        {
+            let (sig, combining_set) = self.combine_and_verify_sig(hash)?;
+            self.state.combining_set = Some(combining_set);
+            self.state.signature = Some(sig.clone());
+            self.state.contributed = self.state.received_shares.contains_key(self.our_id());
+            self.state.terminated = true;
+            self.report_coin(&sig);
             let step = self.sign()?; // Before terminating, make sure we sent our share.
-            debug!("{} output {:?}", self, sig);
+            if self.defer_output {
+                debug!("{} event=deferred_output parity={}", self, sig.parity());
+                self.state.deferred_output = Some(sig);
+                return Ok(step);
+            }
+            debug!("{} event=output parity={}", self, sig.parity());
             Ok(step.with_output(sig))
         } else {
             debug!(
-                "{} received {} shares, {}",
+                "{} event=waiting shares={} had_input={}",
                 self,
-                self.received_shares.len(),
-                if self.had_input { ", had input" } else { "" }
+                self.state.received_shares.len(),
+                self.state.had_input
             );
             Ok(Step::default())
         }
     }
 
-    fn combine_and_verify_sig(&self, hash: G2) -> Result<Signature> {
-        // Pass the indices of sender nodes to `combine_signatures`.
-        let shares_itr = self
+    /// Passes a record of the computed signature to the coin sink, if there is one.
+    fn report_coin(&self, sig: &Signature) {
+        let sink = match self.coin_sink {
+            Some(ref sink) => sink,
+            None => return,
+        };
+        let elapsed = match (&self.clock, self.state.started_at) {
+            (Some(clock), Some(start)) => clock().checked_sub(start),
+            _ => None,
+        };
+        sink(CoinResult {
+            doc_hash: self.state.doc_id.unwrap_or_default(),
+            parity: sig.parity(),
+            shares_used: self.state.combining_set.as_ref().map_or(0, Vec::len),
+            elapsed,
+        });
+    }
+
+    /// Combines `threshold + 1` shares, and returns the signature together with the node indices
+    /// of the shares that were used.
+    fn combine_and_verify_sig(&self, hash: G2) -> Result<(Signature, Vec<u64>)> {
+        #[cfg(feature = "fault-injection")]
+        {
+            if self.injected_faults.fail_combine {
+                return Err(Error::VerificationFailed);
+            }
+        }
+        let (sig, indices) = self.combine_sig()?;
+        if !self
+            .netinfo
+            .public_key_set()
+            .public_key()
+            .verify_g2(&sig, hash)
+        {
+            return Err(Error::VerificationFailed);
+        }
+        Ok((sig, indices))
+    }
+
+    /// Combines `threshold + 1` shares without verifying the result, and returns the signature
+    /// together with the node indices of the shares that were used.
+    fn combine_sig(&self) -> Result<(Signature, Vec<u64>)> {
+        // Pass the indices of sender nodes to `combine_signatures`. All shares have already been
+        // verified, so any `threshold + 1` of them suffice. We deterministically use the ones with
+        // the lowest IDs: `received_shares` is ordered by sender ID, and node indices are assigned
+        // in the same order, so these are also the lowest indices. `combine_signatures` would only
+        // interpolate the first `threshold + 1` shares anyway; we select them explicitly so that the
+        // returned indices are exactly the ones that were used.
+        let shares: Vec<_> = self
+            .state
             .received_shares
             .values()
-            .map(|&(ref idx, ref share)| (idx, share));
+            .take(self.netinfo.threshold() + 1)
+            .collect();
+        let shares_itr = shares.iter().map(|&&(idx, ref share)| (idx as u64, share));
         let sig = self
             .netinfo
             .public_key_set()
             .combine_signatures(shares_itr)
             .map_err(Error::CombineAndVerifySigCrypto)?;
-        if !self
-            .netinfo
-            .public_key_set()
-            .public_key()
-            .verify_g2(&sig, hash)
-        {
-            Err(Error::VerificationFailed)
-        } else {
-            Ok(sig)
+        let indices = shares.iter().map(|&&(idx, _)| idx as u64).collect();
+        Ok((sig, indices))
+    }
+}
+
+/// A builder to configure and create a new `ThresholdSign` instance. The defaults are the same as
+/// in `ThresholdSign::new`.
+pub struct ThresholdSignBuilder<N> {
+    /// Shared network data.
+    netinfo: Arc<NetworkInfo<N>>,
+    /// The document to be signed, if it is already known.
+    doc: Option<Vec<u8>>,
+    /// The provider of our signature shares, if it is not the secret key share in `netinfo`.
+    signer: Option<Arc<dyn ShareSigner>>,
+    /// Whether the signature is kept until `release_output` is called, instead of being output.
+    defer_output: bool,
+    /// Whether shares received after termination are verified and recorded.
+    collect_late_shares: bool,
+    /// The callback that receives a record of every computed signature.
+    coin_sink: Option<CoinSink>,
+    /// The clock used to measure the time until the signature is computed.
+    clock: Option<Clock>,
+}
+
+impl<N: NodeIdT> ThresholdSignBuilder<N> {
+    /// Returns a new builder for instances that use the keys in `netinfo`.
+    pub fn new(netinfo: Arc<NetworkInfo<N>>) -> Self {
+        ThresholdSignBuilder {
+            netinfo,
+            doc: None,
+            signer: None,
+            defer_output: false,
+            collect_late_shares: false,
+            coin_sink: None,
+            clock: None,
         }
     }
+
+    /// Sets the document to sign. By default, it must be set later, using `set_document`.
+    pub fn document<M: AsRef<[u8]>>(mut self, doc: M) -> Self {
+        self.doc = Some(doc.as_ref().to_vec());
+        self
+    }
+
+    /// Sets the provider of our signature shares. See `ThresholdSign::set_share_signer`.
+    pub fn share_signer<S: ShareSigner + 'static>(mut self, signer: S) -> Self {
+        self.signer = Some(Arc::new(signer));
+        self
+    }
+
+    /// Sets whether the signature is kept until it is released. See
+    /// `ThresholdSign::set_defer_output`.
+    pub fn defer_output(mut self, defer: bool) -> Self {
+        self.defer_output = defer;
+        self
+    }
+
+    /// Sets whether shares that arrive after termination are recorded. See
+    /// `ThresholdSign::set_collect_late_shares`.
+    pub fn collect_late_shares(mut self, collect: bool) -> Self {
+        self.collect_late_shares = collect;
+        self
+    }
+
+    /// Sets a callback that is invoked once whenever the instance computes a signature, i.e. once
+    /// per signing round, with the document's hash, the coin value and the number of shares used.
+    /// It is called even if the output is deferred. By default, nothing is recorded.
+    pub fn coin_sink(mut self, sink: CoinSink) -> Self {
+        self.coin_sink = Some(sink);
+        self
+    }
+
+    /// Sets the clock used to measure the time between setting the document and computing the
+    /// signature, which is reported to the coin sink. By default, no time is measured.
+    pub fn clock(mut self, clock: Clock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Creates the new `ThresholdSign` instance.
+    pub fn build(self) -> ThresholdSign<N> {
+        let mut ts = ThresholdSign::new(self.netinfo);
+        ts.coin_sink = self.coin_sink;
+        ts.clock = self.clock;
+        if let Some(doc) = self.doc {
+            ts.init_document(&doc);
+        }
+        ts.signer = self.signer;
+        ts.defer_output = self.defer_output;
+        ts.collect_late_shares = self.collect_late_shares;
+        ts
+    }
+}
+
+/// Combines the shares, indexed by the signers' node indices, and verifies the signature.
+fn combine_and_verify<'a, I>(pk_set: &PublicKeySet, hash: G2, shares: I) -> Result<Signature>
+where
+    I: IntoIterator<Item = (u64, &'a SignatureShare)>,
+{
+    let sig = pk_set
+        .combine_signatures(shares)
+        .map_err(Error::CombineAndVerifySigCrypto)?;
+    if !pk_set.public_key().verify_g2(&sig, hash) {
+        return Err(Error::VerificationFailed);
+    }
+    Ok(sig)
+}
+
+/// Computes the signature from messages collected from the wire, e.g. to audit a past coin flip,
+/// without a `ThresholdSign` instance or `NetworkInfo`.
+///
+/// `index_of` returns the node index of a sender, i.e. its position in the ordered list of all
+/// validator IDs. Shares that are invalid for `doc`, and repeated messages from the same sender,
+/// are ignored, and a threshold plus one of the remaining shares are combined. The result is the
+/// same signature that a `ThresholdSign` instance outputs. Returns an error if there are not
+/// enough valid shares.
+pub fn combine_from_messages<N, F, M, I>(
+    pk_set: &PublicKeySet,
+    index_of: F,
+    doc: M,
+    msgs: I,
+) -> Result<Signature>
+where
+    F: Fn(&N) -> u64,
+    M: AsRef<[u8]>,
+    I: IntoIterator<Item = (N, Message)>,
+{
+    let hash = hash_g2(doc);
+    let mut shares = BTreeMap::new();
+    for (sender_id, Message { share, .. }) in msgs {
+        let idx = index_of(&sender_id);
+        if !shares.contains_key(&idx) && pk_set.public_key_share(idx).verify_g2(&share, hash) {
+            shares.insert(idx, share);
+        }
+    }
+    let shares_itr = shares.iter().take(pk_set.threshold() + 1);
+    combine_and_verify(pk_set, hash, shares_itr.map(|(idx, share)| (*idx, share)))
+}
+
+/// Returns the document that is signed in the given round after `ThresholdSign::rotate_nonce`: the
+/// nonce, followed by the round as a big-endian `u64`.
+pub fn round_document<M: AsRef<[u8]>>(nonce: M, round: u64) -> Vec<u8> {
+    let mut doc = nonce.as_ref().to_vec();
+    let mut round_bytes = [0u8; 8];
+    BigEndian::write_u64(&mut round_bytes, round);
+    doc.extend_from_slice(&round_bytes);
+    doc
+}
+
+/// Verifies a recorded signing round offline, e.g. to audit a past coin flip.
+///
+/// Returns `Ok(true)` if every share in `shares`, indexed by the signers' node indices, is valid
+/// for `doc`, and the combined signature's parity is `claimed`. Returns `Ok(false)` if any share
+/// is invalid or the parity does not match. Returns an error if the shares cannot be combined,
+/// e.g. because there are not more than `pk_set.threshold()` of them.
+pub fn verify_round<M: AsRef<[u8]>>(
+    pk_set: &PublicKeySet,
+    doc: M,
+    shares: &BTreeMap<u64, SignatureShare>,
+    claimed: bool,
+) -> Result<bool> {
+    let hash = hash_g2(doc);
+    let is_valid = |(idx, share): (&u64, &SignatureShare)| {
+        pk_set.public_key_share(*idx).verify_g2(share, hash)
+    };
+    if !shares.iter().all(is_valid) {
+        return Ok(false);
+    }
+    let sig = combine_and_verify(
+        pk_set,
+        hash,
+        shares.iter().map(|(idx, share)| (*idx, share)),
+    )?;
+    Ok(sig.parity() == claimed)
+}
+
+/// Deterministically selects one of the `candidates`, using the signature as a source of
+/// randomness. Returns `None` if there are no candidates.
+///
+/// All nodes that use the same signature and the same candidates in the same order elect the same
+/// leader. The signature is hashed together with a counter, and the first eight bytes of the
+/// hash are interpreted as a number. Numbers beyond the largest multiple of the number of
+/// candidates are rejected and the counter is incremented, so that every candidate is equally
+/// likely, without modulo bias.
+pub fn elect_leader<'a, N>(sig: &Signature, candidates: &'a [N]) -> Option<&'a N> {
+    if candidates.is_empty() {
+        return None;
+    }
+    let num = candidates.len() as u64;
+    let limit = u64::max_value() - u64::max_value() % num;
+    let sig_bytes = sig.to_bytes();
+    let mut counter_bytes = [0u8; 8];
+    for counter in 0u64.. {
+        BigEndian::write_u64(&mut counter_bytes, counter);
+        let bytes: Vec<u8> = sig_bytes.iter().chain(&counter_bytes).cloned().collect();
+        let value = BigEndian::read_u64(&sha3_256(&bytes)[..8]);
+        if value < limit {
+            return candidates.get((value % num) as usize);
+        }
+    }
+    unreachable!("rejection sampling ran out of counters")
+}
+
+/// Returns an estimate of the traffic in a signing round among `num_nodes` validators, e.g. a
+/// coin flip: Every validator multicasts one signature share.
+pub fn estimated_messages(num_nodes: usize) -> RoundCost {
+    // The message consists of the wire version, the round and the share.
+    RoundCost::multicasts(num_nodes, 1, 1 + 8 + crypto::SIG_SIZE)
 }
 
 impl<N: NodeIdT> fmt::Display for ThresholdSign<N> {
     fn fmt(&self, f: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
-        write!(f, "{:?} TS({:?})", self.our_id(), self.doc_hash)
+        // All log messages of one signing round share this prefix, so they can be filtered.
+        match self.state.doc_id {
+            Some(ref doc_id) => write!(
+                f,
+                "TS node_id={:?} doc={:0.8}",
+                self.our_id(),
+                HexFmt(doc_id)
+            ),
+            None => write!(f, "TS node_id={:?} doc=none", self.our_id()),
+        }
     }
 }
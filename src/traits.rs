@@ -1,7 +1,7 @@
 //! Common supertraits for distributed algorithms.
 
 use std::collections::BTreeMap;
-use std::fmt::{Debug, Display};
+use std::fmt::{self, Debug, Display};
 use std::hash::Hash;
 use std::iter::once;
 
@@ -144,6 +144,36 @@ impl<M, O, N> Step<M, O, N> {
     }
 }
 
+impl<M: Clone, O, N: NodeIdT> Step<M, O, N> {
+    /// Returns the step's messages, grouped by recipient, in the order they appear in the step.
+    ///
+    /// A `Target::All` message is sent to every node in `all` except `our_id`. A `Target::Node`
+    /// message is sent to its target, even if that is not in `all`. Nodes that don't receive any
+    /// messages are not included.
+    pub fn messages_by_recipient(&self, all: &[N], our_id: &N) -> BTreeMap<N, Vec<M>> {
+        let mut by_recipient: BTreeMap<N, Vec<M>> = BTreeMap::new();
+        for tm in &self.messages {
+            match tm.target {
+                Target::All => {
+                    for id in all.iter().filter(|id| *id != our_id) {
+                        by_recipient
+                            .entry(id.clone())
+                            .or_insert_with(Vec::new)
+                            .push(tm.message.clone());
+                    }
+                }
+                Target::Node(ref id) => {
+                    by_recipient
+                        .entry(id.clone())
+                        .or_insert_with(Vec::new)
+                        .push(tm.message.clone());
+                }
+            }
+        }
+        by_recipient
+    }
+}
+
 impl<M, O, N> From<FaultLog<N>> for Step<M, O, N> {
     fn from(fault_log: FaultLog<N>) -> Self {
         Step {
@@ -194,6 +224,93 @@ pub trait Epoched {
     fn epoch(&self) -> Self::Epoch;
 }
 
+/// An interface to algorithm instances with an identifier. When many instances run side by side,
+/// e.g. one coin per agreement, a driver can tag each instance's outgoing messages with it using
+/// `TargetedMessage::with_instance_id`, and dispatch received messages to the instance with the
+/// same identifier.
+pub trait InstanceId {
+    /// Returns the instance's identifier. The instances on different nodes that exchange messages
+    /// with each other have the same one.
+    fn instance_id(&self) -> &[u8];
+}
+
+/// A summary of an algorithm instance's activity, e.g. to export it to a monitoring system.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AlgoMetrics {
+    /// The number of messages the instance has sent. A message to all nodes counts once.
+    pub messages_sent: usize,
+    /// The number of messages the instance has received from other nodes.
+    pub messages_received: usize,
+    /// The number of faults the instance has reported.
+    pub faults: usize,
+    /// The current epoch or round, if the algorithm has any.
+    pub epoch: Option<u64>,
+    /// Whether the instance has terminated.
+    pub terminated: bool,
+}
+
+/// An algorithm instance that keeps track of its `AlgoMetrics`.
+///
+/// This is separate from `DistAlgorithm`, so that algorithms can implement it one by one, and
+/// drivers that don't need metrics are not affected.
+pub trait Instrumented {
+    /// Returns the current metrics.
+    fn metrics(&self) -> AlgoMetrics;
+}
+
+/// A description of the state of an algorithm instance and its sub-instances, to find out why an
+/// instance doesn't make progress.
+///
+/// The `Display` implementation renders the tree with one line per instance, with each level of
+/// sub-instances indented by two more spaces.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiagnosticTree {
+    /// A one-line summary of the instance's own state.
+    pub label: String,
+    /// The diagnostics of the sub-instances.
+    pub children: Vec<DiagnosticTree>,
+}
+
+impl DiagnosticTree {
+    /// Returns a tree with the given label and no children.
+    pub fn new<L: Into<String>>(label: L) -> Self {
+        DiagnosticTree {
+            label: label.into(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Returns the same tree, with the given additional child.
+    pub fn with_child(mut self, child: DiagnosticTree) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    /// Writes the tree, indented by `depth` levels.
+    fn fmt_indented(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        writeln!(f, "{:indent$}{}", "", self.label, indent = 2 * depth)?;
+        for child in &self.children {
+            child.fmt_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for DiagnosticTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+/// An algorithm instance that can describe its state in a `DiagnosticTree`.
+///
+/// Composite algorithms include the diagnostics of their sub-instances, so that the root of a
+/// stalled Honey Badger epoch reveals e.g. which agreement instance is waiting for which coin.
+pub trait Diagnose {
+    /// Returns the current state of the instance and its sub-instances.
+    fn diagnose(&self) -> DiagnosticTree;
+}
+
 /// An alias for the type of `Step` returned by `D`'s methods.
 pub type DaStep<D> =
     Step<<D as DistAlgorithm>::Message, <D as DistAlgorithm>::Output, <D as DistAlgorithm>::NodeId>;
@@ -265,6 +382,10 @@ pub trait DistAlgorithm: Send + Sync {
     /// The messages that need to be exchanged between the instances in the participating nodes.
     type Message: Message;
     /// The errors that can occur during execution.
+    ///
+    /// `Fail` already implies `Debug + Display + Send + Sync + 'static`. The errors convert into
+    /// `failure::Error` with `?`, and `Fail::compat` turns them into a `std::error::Error`, e.g.
+    /// to box them as `Box<dyn std::error::Error + Send + Sync>`.
     type Error: Fail;
 
     /// Handles an input provided by the user, and returns
@@ -286,9 +407,75 @@ pub trait DistAlgorithm: Send + Sync {
     where
         Self: Sized;
 
+    /// Handles a batch of messages received from node `sender_id`, e.g. created using
+    /// `TargetedMessage::batch`, in order, and returns the combined step.
+    ///
+    /// If handling one of the messages fails, the error is returned and the remaining messages are
+    /// not handled.
+    fn handle_message_batch<R: Rng, I>(
+        &mut self,
+        sender_id: &Self::NodeId,
+        messages: I,
+        rng: &mut R,
+    ) -> Result<DaStep<Self>, Self::Error>
+    where
+        Self: Sized,
+        I: IntoIterator<Item = Self::Message>,
+    {
+        let mut step = DaStep::<Self>::default();
+        for message in messages {
+            step.extend(self.handle_message(sender_id, message, rng)?);
+        }
+        Ok(step)
+    }
+
+    /// Stops the instance gracefully, e.g. because the node is shutting down, and returns the
+    /// messages that still need to be sent so that peers aren't left waiting for them.
+    ///
+    /// Afterwards, further input should be ignored, and once the returned messages have been sent,
+    /// the instance can be dropped. The default implementation returns an empty step and doesn't
+    /// change the state: Algorithms that don't override it don't support shutting down early.
+    fn shutdown(&mut self) -> DaStep<Self>
+    where
+        Self: Sized,
+    {
+        DaStep::<Self>::default()
+    }
+
     /// Returns `true` if execution has completed and this instance can be dropped.
     fn terminated(&self) -> bool;
 
     /// Returns this node's own ID.
     fn our_id(&self) -> &Self::NodeId;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Step;
+    use crate::Target;
+
+    #[test]
+    fn test_messages_by_recipient() {
+        let ids: Vec<usize> = (0..4).collect();
+        let mut step: Step<&str, (), usize> = Target::All.message("share").into();
+
+        // A multicast is sent to everyone but ourselves.
+        let by_recipient = step.messages_by_recipient(&ids, &2);
+        assert_eq!(
+            vec![0, 1, 3],
+            by_recipient.keys().cloned().collect::<Vec<_>>()
+        );
+        assert!(by_recipient.values().all(|msgs| *msgs == vec!["share"]));
+
+        // Messages to a single node are added to that node's list, in order, even if the node is
+        // not in the list of all nodes.
+        step.messages.push(Target::Node(1).message("direct"));
+        step.messages.push(Target::Node(5).message("other"));
+        let by_recipient = step.messages_by_recipient(&ids, &2);
+        assert_eq!(vec!["share", "direct"], by_recipient[&1]);
+        assert_eq!(vec!["share"], by_recipient[&0]);
+        assert_eq!(vec!["share"], by_recipient[&3]);
+        assert_eq!(vec!["other"], by_recipient[&5]);
+        assert!(!by_recipient.contains_key(&2));
+    }
+}
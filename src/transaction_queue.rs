@@ -24,6 +24,16 @@ pub trait TransactionQueue<T>: fmt::Debug + Default + Extend<T> + Sync + Send {
         T: 'a + Contribution;
 }
 
+/// A transaction queue that keeps its transactions in a stable order. This is required by
+/// `QueueingHoneyBadgerBuilder::ordered_proposals`.
+pub trait OrderedTransactionQueue<T>: TransactionQueue<T> {
+    /// Returns the number of transactions in the queue.
+    fn len(&self) -> usize;
+    /// Returns the first `amount` transactions, in queue order. No transactions are removed from
+    /// the queue.
+    fn first(&self, amount: usize) -> Vec<T>;
+}
+
 impl<T> TransactionQueue<T> for Vec<T>
 where
     T: Clone + fmt::Debug + Sync + Send,
@@ -51,3 +61,18 @@ where
         sample.cloned().collect()
     }
 }
+
+impl<T> OrderedTransactionQueue<T> for Vec<T>
+where
+    T: Clone + fmt::Debug + Sync + Send,
+{
+    #[inline]
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    #[inline]
+    fn first(&self, amount: usize) -> Vec<T> {
+        self[..cmp::min(amount, self.len())].to_vec()
+    }
+}
@@ -1,9 +1,12 @@
 //! Utility functions
 //!
-//! Functions not large enough to warrant their own crate or module, but flexible enough to be used
-//! in multiple disjunct places in the library. May also contain backports, workarounds.
+//! Functions and small types, like the `RoundCost` traffic estimates, not large enough to warrant
+//! their own crate or module, but flexible enough to be used in multiple disjunct places in the
+//! library. May also contain backports, workarounds.
+//!
+//! Encodings of keys and signatures are in `encoding`, and cryptographic checks in `crypto_util`.
 
-use std::fmt;
+use std::{fmt, ops};
 
 use hex_fmt::HexFmt;
 
@@ -23,3 +26,61 @@ pub fn max_faulty(n: usize) -> usize {
     assert!(n > 0, "A valid network requires at least one node.");
     (n - 1) / 3
 }
+
+/// An estimate of the network traffic caused by one instance or round of an algorithm.
+///
+/// The numbers are totals over all nodes. A multicast counts as one message to each of the other
+/// nodes, since that is what is sent over point-to-point connections. All the algorithms are
+/// symmetric, so each node sends and receives about `1 / n` of the total, except for the
+/// proposer in `Broadcast`. The bytes are the sizes of the `bincode`-serialized messages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RoundCost {
+    /// The number of messages.
+    pub messages: usize,
+    /// The number of bytes in all messages.
+    pub bytes: usize,
+}
+
+impl RoundCost {
+    /// Returns the cost of `count` messages to each of `num_nodes - 1` other nodes, from
+    /// each of `num_nodes` nodes, if each message has `size` bytes.
+    pub fn multicasts(num_nodes: usize, count: usize, size: usize) -> Self {
+        let messages = num_nodes * count * num_nodes.saturating_sub(1);
+        RoundCost {
+            messages,
+            bytes: messages * size,
+        }
+    }
+
+    /// Returns the number of messages each of `num_nodes` nodes sends and receives on average.
+    pub fn messages_per_node(&self, num_nodes: usize) -> usize {
+        self.messages / num_nodes
+    }
+
+    /// Returns the number of bytes each of `num_nodes` nodes sends and receives on average.
+    pub fn bytes_per_node(&self, num_nodes: usize) -> usize {
+        self.bytes / num_nodes
+    }
+}
+
+impl ops::Add for RoundCost {
+    type Output = RoundCost;
+
+    fn add(self, other: RoundCost) -> RoundCost {
+        RoundCost {
+            messages: self.messages + other.messages,
+            bytes: self.bytes + other.bytes,
+        }
+    }
+}
+
+impl ops::Mul<usize> for RoundCost {
+    type Output = RoundCost;
+
+    fn mul(self, factor: usize) -> RoundCost {
+        RoundCost {
+            messages: self.messages * factor,
+            bytes: self.bytes * factor,
+        }
+    }
+}
@@ -15,8 +15,9 @@
 
 pub mod net;
 
+use std::collections::{BTreeMap, VecDeque};
 use std::iter::once;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time;
 
 use proptest::arbitrary::any;
@@ -24,7 +25,7 @@ use proptest::{prelude::ProptestConfig, prop_compose, proptest, proptest_helper}
 use rand::{Rng, SeedableRng};
 
 use hbbft::binary_agreement::BinaryAgreement;
-use hbbft::DistAlgorithm;
+use hbbft::{DistAlgorithm, NetworkInfo, Target};
 
 use crate::net::adversary::{Adversary, ReorderingAdversary};
 use crate::net::proptest::{gen_seed, NetworkDimension, TestRng, TestRngSeed};
@@ -133,3 +134,82 @@ fn binary_agreement(cfg: TestConfig) {
         num_good_nodes, num_faulty_nodes, cfg.input
     );
 }
+
+/// Runs four instances with the deterministic coin, where the first two propose `true`, and
+/// delivers all messages, including the ones sent after the decision. Before proposing, `prepare`
+/// is called with each instance.
+fn run_deterministic<F>(mut prepare: F) -> BTreeMap<usize, (BinaryAgreement<usize, u8>, Vec<bool>)>
+where
+    F: FnMut(usize, &mut BinaryAgreement<usize, u8>),
+{
+    let mut rng = rand::thread_rng();
+    let netinfos = NetworkInfo::generate_map(0..4, &mut rng).expect("netinfos");
+    let mut nodes: BTreeMap<usize, (BinaryAgreement<usize, u8>, Vec<bool>)> = netinfos
+        .into_iter()
+        .map(|(id, netinfo)| {
+            let ba = BinaryAgreement::new_with_deterministic_coin(Arc::new(netinfo), 0)
+                .expect("binary agreement");
+            (id, (ba, Vec::new()))
+        })
+        .collect();
+    let mut queue = VecDeque::new();
+    for (id, (ba, outputs)) in &mut nodes {
+        prepare(*id, ba);
+        assert_eq!(None, ba.decision());
+        let step = ba.propose(*id < 2).expect("propose");
+        outputs.extend(step.output);
+        queue.extend(step.messages.into_iter().map(|tm| (*id, tm)));
+    }
+    while let Some((sender_id, tm)) = queue.pop_front() {
+        for (id, (ba, outputs)) in &mut nodes {
+            let is_recipient = match tm.target {
+                Target::All => *id != sender_id,
+                Target::Node(ref to) => to == id,
+            };
+            if !is_recipient {
+                continue;
+            }
+            let step = ba
+                .handle_message(&sender_id, tm.message.clone())
+                .expect("handle message");
+            outputs.extend(step.output);
+            queue.extend(step.messages.into_iter().map(|tm| (*id, tm)));
+        }
+    }
+    nodes
+}
+
+#[test]
+fn test_decision() {
+    let nodes = run_deterministic(|_, _| ());
+    for (ba, outputs) in nodes.values() {
+        assert!(ba.terminated());
+        assert_eq!(1, outputs.len());
+        assert_eq!(Some(outputs[0]), ba.decision());
+    }
+}
+
+#[test]
+fn test_on_decision() {
+    let decisions = Arc::new(Mutex::new(Vec::new()));
+    let mut nodes = run_deterministic(|id, ba| {
+        let decisions = decisions.clone();
+        ba.on_decision(move |b| decisions.lock().expect("lock").push((id, b)));
+    });
+    // Each callback was called exactly once, with the output value, although the instances kept
+    // handling messages after deciding.
+    let expected: Vec<(usize, bool)> = nodes
+        .iter()
+        .map(|(id, (_, outputs))| (*id, outputs[0]))
+        .collect();
+    let mut called = decisions.lock().expect("lock").clone();
+    called.sort();
+    assert_eq!(expected, called);
+
+    // A callback registered after the decision is called immediately.
+    let (ba, outputs) = nodes.get_mut(&0).expect("node 0");
+    let late = Arc::new(Mutex::new(None));
+    let late_clone = late.clone();
+    ba.on_decision(move |b| *late_clone.lock().expect("lock") = Some(b));
+    assert_eq!(Some(outputs[0]), *late.lock().expect("lock"));
+}
@@ -0,0 +1,61 @@
+#![deny(unused_must_use)]
+//! Tests of Binary Agreement with a custom coin projection.
+
+mod network;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use hbbft::binary_agreement::BinaryAgreement;
+use hbbft::crypto::Signature;
+use hbbft::NetworkInfo;
+
+use crate::network::{MessageScheduler, NodeId, SilentAdversary, TestNetwork, TestNode};
+
+type BA = BinaryAgreement<NodeId, u8>;
+
+/// The number of times `lowest_bit` has been called.
+static PROJECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Uses the lowest bit of the signature's last byte as the coin value, and counts the calls.
+fn lowest_bit(sig: &Signature) -> bool {
+    PROJECTIONS.fetch_add(1, Ordering::SeqCst);
+    sig.to_bytes().last().expect("signature is empty") & 1 == 1
+}
+
+/// Runs a network of `num_nodes` Binary Agreement instances with alternating inputs, and checks
+/// that all nodes output the same value.
+fn run_ba_with_projection(num_nodes: usize) {
+    let new_ba = |netinfo: Arc<NetworkInfo<NodeId>>| {
+        BA::new_with_coin_projection(netinfo, 0, lowest_bit)
+            .expect("failed to create BinaryAgreement")
+    };
+    let adversary = |_| SilentAdversary::new(MessageScheduler::Random);
+    let mut network = TestNetwork::new(num_nodes, 0, adversary, new_ba);
+    for id in (0..num_nodes).map(NodeId) {
+        network.input(id, id.0 % 2 == 0);
+    }
+    while !network.nodes.values().all(TestNode::terminated) {
+        network.step();
+    }
+    let output = network.nodes[&NodeId(0)].outputs().to_vec();
+    assert_eq!(1, output.len());
+    assert!(network
+        .nodes
+        .values()
+        .all(|node| node.outputs() == &output[..]));
+}
+
+#[test]
+fn test_binary_agreement_coin_projection() {
+    // This returns an error in all but the first test.
+    let _ = env_logger::try_init();
+
+    for _ in 0..20 {
+        for num_nodes in &[2, 4, 7] {
+            run_ba_with_projection(*num_nodes);
+        }
+    }
+    // With mixed inputs, at least some of the runs need the threshold signature coin.
+    assert!(PROJECTIONS.load(Ordering::SeqCst) > 0);
+}
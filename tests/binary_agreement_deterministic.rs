@@ -0,0 +1,183 @@
+#![deny(unused_must_use)]
+//! Tests of Binary Agreement with the insecure, deterministic coin.
+//!
+//! With the deterministic coin the sequence of epochs only depends on the inputs and the message
+//! scheduling, not on the randomly generated key shares.
+
+mod network;
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+
+use hbbft::binary_agreement::{self, BinaryAgreement};
+use hbbft::util::RoundCost;
+use hbbft::{NetworkInfo, Target};
+
+use crate::network::{MessageScheduler, NodeId, SilentAdversary, TestNetwork, TestNode};
+
+type BA = BinaryAgreement<NodeId, u8>;
+
+/// Runs a network of `num_nodes` Binary Agreement instances with alternating inputs until all
+/// nodes have terminated, and returns the epoch and output of each node.
+fn run_deterministic_ba(num_nodes: usize) -> Vec<(NodeId, u64, Vec<bool>)> {
+    let new_ba = |netinfo: Arc<NetworkInfo<NodeId>>| {
+        BA::new_with_deterministic_coin(netinfo, 0).expect("failed to create BinaryAgreement")
+    };
+    let adversary = |_| SilentAdversary::new(MessageScheduler::First);
+    let mut network = TestNetwork::new(num_nodes, 0, adversary, new_ba);
+    for id in (0..num_nodes).map(NodeId) {
+        network.input(id, id.0 % 2 == 0);
+    }
+    while !network.nodes.values().all(TestNode::terminated) {
+        network.step();
+    }
+    network
+        .nodes
+        .iter()
+        .map(|(id, node)| (*id, node.instance().epoch(), node.outputs().to_vec()))
+        .collect()
+}
+
+#[test]
+fn test_deterministic_coin_reproducible_epochs() {
+    // This returns an error in all but the first test.
+    let _ = env_logger::try_init();
+
+    for num_nodes in &[1, 4, 7, 10] {
+        // Each run generates new keys, so a threshold signature coin would differ between runs.
+        let expected = run_deterministic_ba(*num_nodes);
+        for _ in 0..3 {
+            assert_eq!(expected, run_deterministic_ba(*num_nodes));
+        }
+        let (_, _, ref output) = expected[0];
+        assert_eq!(1, output.len());
+        assert!(expected.iter().all(|(_, _, out)| out == output));
+    }
+}
+
+#[test]
+fn test_estimated_messages() {
+    let mut rng = rand::thread_rng();
+    for &num_nodes in &[1, 4, 7] {
+        let netinfos = NetworkInfo::generate_map((0..num_nodes).map(NodeId), &mut rng)
+            .expect("failed to generate `NetworkInfo` map");
+        let mut nodes: BTreeMap<NodeId, BA> = netinfos
+            .into_iter()
+            .map(|(id, netinfo)| {
+                let ba = BA::new_with_deterministic_coin(Arc::new(netinfo), 0)
+                    .expect("failed to create BinaryAgreement");
+                (id, ba)
+            })
+            .collect();
+        let mut queue = VecDeque::new();
+        for (id, ba) in &mut nodes {
+            let step = ba.propose(true).expect("failed to propose");
+            queue.extend(step.messages.into_iter().map(|tm| (*id, tm)));
+        }
+        // Deliver all messages, counting each recipient of a multicast separately.
+        let mut cost = RoundCost::default();
+        while let Some((sender_id, tm)) = queue.pop_front() {
+            let size = bincode::serialize(&tm.message).expect("serialize").len();
+            for (id, ba) in &mut nodes {
+                let is_recipient = match tm.target {
+                    Target::All => *id != sender_id,
+                    Target::Node(ref target_id) => target_id == id,
+                };
+                if !is_recipient {
+                    continue;
+                }
+                cost.messages += 1;
+                cost.bytes += size;
+                let step = ba
+                    .handle_message(&sender_id, tm.message.clone())
+                    .expect("failed to handle message");
+                queue.extend(step.messages.into_iter().map(|tm| (*id, tm)));
+            }
+        }
+        assert!(nodes.values().all(|ba| ba.decision() == Some(true)));
+        // With unanimous input, the instances decide in the first round.
+        assert_eq!(binary_agreement::estimated_messages(num_nodes, 1), cost);
+    }
+    // The third round uses the coin, so it adds `Conf` and coin share messages.
+    let estimate = binary_agreement::estimated_messages(4, 3);
+    assert_eq!(4 * 3 * (1 + 3 * 2 + 2), estimate.messages);
+}
+
+#[test]
+fn test_last_progress_epoch() {
+    let mut rng = rand::thread_rng();
+    let netinfos = NetworkInfo::generate_map((0..4).map(NodeId), &mut rng)
+        .expect("failed to generate `NetworkInfo` map");
+    let mut nodes: BTreeMap<NodeId, BA> = netinfos
+        .into_iter()
+        .map(|(id, netinfo)| {
+            let ba = BA::new_with_deterministic_coin(Arc::new(netinfo), 0)
+                .expect("failed to create BinaryAgreement");
+            (id, ba)
+        })
+        .collect();
+    // With unanimous `false` input, the first coin is `true`, so the nodes move to epoch 1.
+    let mut queue = VecDeque::new();
+    for (id, ba) in &mut nodes {
+        let step = ba.propose(false).expect("failed to propose");
+        queue.extend(step.messages.into_iter().map(|tm| (*id, tm)));
+    }
+    // Deliver only the epoch 0 messages, and hold back the later ones.
+    let mut held = Vec::new();
+    while let Some((sender_id, tm)) = queue.pop_front() {
+        if tm.message.epoch > 0 {
+            held.push((sender_id, tm));
+            continue;
+        }
+        for (id, ba) in &mut nodes {
+            if *id != sender_id {
+                let step = ba
+                    .handle_message(&sender_id, tm.message.clone())
+                    .expect("failed to handle message");
+                queue.extend(step.messages.into_iter().map(|tm| (*id, tm)));
+            }
+        }
+    }
+    for ba in nodes.values() {
+        assert_eq!(1, ba.epoch());
+        assert_eq!(0, ba.last_progress_epoch());
+        assert_eq!(None, ba.decision());
+    }
+
+    // A single node's messages for epoch 1 are not enough: Node 0 stays stalled.
+    let mut queue = VecDeque::new();
+    {
+        let ba0 = nodes.get_mut(&NodeId(0)).expect("node 0");
+        for (sender_id, tm) in held.iter().filter(|(id, _)| *id == NodeId(1)) {
+            let step = ba0
+                .handle_message(sender_id, tm.message.clone())
+                .expect("failed to handle message");
+            assert!(step.output.is_empty());
+            queue.extend(step.messages.into_iter().map(|tm| (NodeId(0), tm, false)));
+        }
+        assert_eq!(1, ba0.epoch());
+        assert_eq!(0, ba0.last_progress_epoch());
+    }
+
+    // With all messages, the nodes decide in epoch 1.
+    for (sender_id, tm) in held {
+        // Node 0 has already received node 1's messages.
+        queue.push_back((sender_id, tm, sender_id == NodeId(1)));
+    }
+    while let Some((sender_id, tm, skip_node0)) = queue.pop_front() {
+        for (id, ba) in &mut nodes {
+            if *id == sender_id || (skip_node0 && *id == NodeId(0)) {
+                continue;
+            }
+            let step = ba
+                .handle_message(&sender_id, tm.message.clone())
+                .expect("failed to handle message");
+            assert!(step.fault_log.is_empty());
+            queue.extend(step.messages.into_iter().map(|tm| (*id, tm, false)));
+        }
+    }
+    for ba in nodes.values() {
+        assert_eq!(Some(false), ba.decision());
+        assert_eq!(1, ba.last_progress_epoch());
+    }
+}
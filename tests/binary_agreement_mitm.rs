@@ -6,7 +6,7 @@ pub mod net;
 use std::iter;
 use std::sync::{Arc, Mutex};
 
-use hbbft::binary_agreement::{BinaryAgreement, MessageContent, SbvMessage};
+use hbbft::binary_agreement::{self, BinaryAgreement, MessageContent, SbvMessage};
 use hbbft::threshold_sign::ThresholdSign;
 use hbbft::{DaStep, DistAlgorithm, NetworkInfo};
 use proptest::{proptest, proptest_helper};
@@ -267,7 +267,7 @@ impl AbaCommonCoinAdversary {
                         .as_ref()
                         .cloned()
                         .expect("Adversary netinfo mutex not populated");
-                    let coin_id = bincode::serialize(&(0 as SessionId, epoch))
+                    let coin_id = binary_agreement::coin_nonce(&(0 as SessionId), epoch)
                         .expect("Failed to serialize coin_id");
                     let mut coin = ThresholdSign::new_with_document(netinfo, coin_id)
                         .expect("Failed to set the coin's ID");
@@ -0,0 +1,67 @@
+#![deny(unused_must_use)]
+//! Tests of Binary Agreement in a network that is temporarily partitioned.
+//!
+//! The protocol assumes an asynchronous network: Messages can be delayed arbitrarily, but are
+//! eventually delivered. While the network is split into two groups that are both smaller than
+//! _N - f_, no node can decide. Once the partition heals, all correct nodes must terminate and
+//! agree.
+
+mod network;
+
+use std::sync::Arc;
+
+use hbbft::binary_agreement::BinaryAgreement;
+use hbbft::NetworkInfo;
+
+use crate::network::{MessageScheduler, NodeId, SilentAdversary, TestNetwork, TestNode};
+
+type BA = BinaryAgreement<NodeId, u8>;
+
+/// Runs `num_nodes` instances, where the first half inputs `true` and the second `false`, with the
+/// two halves partitioned until no messages are left, and checks that all nodes output the same
+/// value after healing.
+fn run_partitioned_ba(num_nodes: usize) {
+    let new_ba = |netinfo: Arc<NetworkInfo<NodeId>>| {
+        BA::new(netinfo, 0).expect("failed to create BinaryAgreement")
+    };
+    let adversary = |_| SilentAdversary::new(MessageScheduler::Random);
+    let mut network = TestNetwork::new(num_nodes, 0, adversary, new_ba);
+    let half = num_nodes / 2;
+    network.partition((0..half).map(NodeId), (half..num_nodes).map(NodeId));
+    for id in (0..num_nodes).map(NodeId) {
+        network.input(id, id.0 < half);
+    }
+
+    // Neither group is large enough to make progress on its own.
+    while !network.is_idle() {
+        network.step();
+    }
+    assert!(network
+        .nodes
+        .values()
+        .all(|node| node.outputs().is_empty() && !node.terminated()));
+
+    // After healing, the held back messages are delivered and all nodes decide.
+    network.heal();
+    while !network.nodes.values().all(TestNode::terminated) {
+        network.step();
+    }
+    let output = network.nodes[&NodeId(0)].outputs().to_vec();
+    assert_eq!(1, output.len());
+    assert!(network
+        .nodes
+        .values()
+        .all(|node| node.outputs() == &output[..]));
+}
+
+#[test]
+fn test_binary_agreement_partition_heal() {
+    // This returns an error in all but the first test.
+    let _ = env_logger::try_init();
+
+    for _ in 0..10 {
+        for num_nodes in &[4, 7] {
+            run_partitioned_ba(*num_nodes);
+        }
+    }
+}
@@ -3,8 +3,9 @@
 
 mod network;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
 use std::iter::once;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use log::info;
@@ -14,8 +15,12 @@ use crate::network::{
     Adversary, MessageScheduler, MessageWithSender, NodeId, RandomAdversary, SilentAdversary,
     TestNetwork, TestNode,
 };
-use hbbft::broadcast::{Broadcast, Message};
-use hbbft::{util, DistAlgorithm, NetworkInfo, Target, TargetedMessage};
+use hbbft::broadcast::{
+    estimated_messages, verify_opening, Broadcast, CodingError, CodingResult, ErasureCoder,
+    Message, Progress, Step,
+};
+use hbbft::util::{self, RoundCost};
+use hbbft::{DistAlgorithm, Fault, FaultKind, NetworkInfo, Target, TargetedMessage};
 
 /// An adversary that inputs an alternate value.
 struct ProposeAdversary {
@@ -162,3 +167,596 @@ fn test_broadcast_random_adversary() {
     };
     test_broadcast_different_sizes(new_adversary, b"RandomFoo");
 }
+
+/// An erasure coder without parity shards, that sends the value's chunks as they are, and counts
+/// how often it is used.
+#[derive(Debug)]
+struct IdentityCoder {
+    num_nodes: usize,
+    encodings: Arc<AtomicUsize>,
+    reconstructions: Arc<AtomicUsize>,
+}
+
+impl ErasureCoder for IdentityCoder {
+    fn data_shard_count(&self) -> usize {
+        self.num_nodes
+    }
+
+    fn parity_shard_count(&self) -> usize {
+        0
+    }
+
+    fn encode(&self, _shards: &mut [&mut [u8]]) -> CodingResult<()> {
+        self.encodings.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn reconstruct(&self, shards: &mut [Option<Box<[u8]>>]) -> CodingResult<()> {
+        self.reconstructions.fetch_add(1, Ordering::SeqCst);
+        if shards.iter().all(Option::is_some) {
+            Ok(())
+        } else {
+            Err(CodingError::TooFewShards)
+        }
+    }
+}
+
+#[test]
+fn test_broadcast_custom_erasure_coder() {
+    let encodings = Arc::new(AtomicUsize::new(0));
+    let reconstructions = Arc::new(AtomicUsize::new(0));
+    let new_coder = |num_nodes| IdentityCoder {
+        num_nodes,
+        encodings: encodings.clone(),
+        reconstructions: reconstructions.clone(),
+    };
+
+    // The coder's shard count must match the number of validators.
+    let mut rng = rand::thread_rng();
+    let netinfos = NetworkInfo::generate_map(0..4, &mut rng).expect("netinfos");
+    let netinfo = Arc::new(netinfos[&0].clone());
+    assert_eq!(
+        Some(hbbft::broadcast::Error::InvalidShardCount),
+        Broadcast::new_with_coder(netinfo, 0, new_coder(3)).err()
+    );
+
+    // Without faulty nodes, every node receives all shards, and the value can be decoded.
+    let adversary = |_| SilentAdversary::new(MessageScheduler::Random);
+    let new_broadcast = |netinfo: Arc<NetworkInfo<NodeId>>| {
+        let coder = new_coder(netinfo.num_nodes());
+        Broadcast::new_with_coder(netinfo, NodeId(0), coder).expect("Instantiate broadcast")
+    };
+    let network = TestNetwork::new(4, 0, adversary, new_broadcast);
+    test_broadcast(network, b"Longer than the replication threshold");
+    // The proposer encoded the value, and each of the four validators and the observer decoded it.
+    assert_eq!(1, encodings.load(Ordering::SeqCst));
+    assert_eq!(5, reconstructions.load(Ordering::SeqCst));
+}
+
+/// An erasure coder that always fails.
+#[derive(Debug)]
+struct BrokenCoder(usize);
+
+impl ErasureCoder for BrokenCoder {
+    fn data_shard_count(&self) -> usize {
+        self.0
+    }
+
+    fn parity_shard_count(&self) -> usize {
+        0
+    }
+
+    fn encode(&self, _shards: &mut [&mut [u8]]) -> CodingResult<()> {
+        Err(CodingError::InvalidShards)
+    }
+
+    fn reconstruct(&self, _shards: &mut [Option<Box<[u8]>>]) -> CodingResult<()> {
+        Err(CodingError::InvalidShards)
+    }
+}
+
+#[test]
+fn test_broadcast_encoding_error() {
+    let mut rng = rand::thread_rng();
+    let netinfos = NetworkInfo::generate_map(0..4, &mut rng).expect("netinfos");
+    let netinfo = Arc::new(netinfos[&0].clone());
+    let mut bc = Broadcast::new_with_coder(netinfo, 0, BrokenCoder(4)).expect("broadcast");
+    // With replication enabled, a value below the threshold is not encoded.
+    bc.set_replication_threshold(1);
+    assert!(bc.broadcast(vec![1]).is_ok());
+
+    // The coder's error is returned instead of panicking.
+    let netinfo = Arc::new(netinfos[&0].clone());
+    let mut bc = Broadcast::new_with_coder(netinfo, 0, BrokenCoder(4)).expect("broadcast");
+    assert_eq!(
+        Some(hbbft::broadcast::Error::Encode(CodingError::InvalidShards)),
+        bc.broadcast(b"Longer than the replication threshold".to_vec())
+            .err()
+    );
+}
+
+#[test]
+fn test_shard_index_of() {
+    let mut rng = rand::thread_rng();
+    let ids = vec![17usize, 3, 42, 8, 25, 11, 30];
+    let netinfos = NetworkInfo::generate_map(ids.clone(), &mut rng).expect("netinfos");
+    let proposer_id = 42;
+    let mut proposer =
+        Broadcast::new(Arc::new(netinfos[&proposer_id].clone()), proposer_id).expect("bc");
+    for netinfo in netinfos.values() {
+        let bc = Broadcast::new(Arc::new(netinfo.clone()), proposer_id).expect("broadcast");
+        for id in &ids {
+            assert_eq!(netinfo.node_index(id), bc.shard_index_of(id));
+            assert_eq!(proposer.shard_index_of(id), bc.shard_index_of(id));
+        }
+        assert_eq!(None, bc.shard_index_of(&0));
+    }
+    // Each validator receives the proof for its own shard.
+    let step = proposer.broadcast(b"shards".to_vec()).expect("broadcast");
+    for tm in step.messages {
+        match (tm.target, tm.message) {
+            (Target::Node(id), Message::Value(proof)) => {
+                assert_eq!(proposer.shard_index_of(&id), Some(proof.index()));
+            }
+            (Target::All, Message::Echo(proof)) => {
+                assert_eq!(proposer.shard_index_of(&proposer_id), Some(proof.index()));
+            }
+            (target, msg) => panic!("unexpected message {:?} to {:?}", msg, target),
+        }
+    }
+}
+
+#[test]
+fn test_cancel() {
+    let mut rng = rand::thread_rng();
+    let netinfos = NetworkInfo::generate_map(0..4usize, &mut rng).expect("netinfos");
+    let new_bc = |id| Broadcast::new(Arc::new(netinfos[&id].clone()), 0).expect("broadcast");
+    let mut proposer = new_bc(0);
+    let mut node = new_bc(1);
+    let mut other = new_bc(2);
+
+    // Node 1 receives its `Value` and node 2's `Echo`, then cancels.
+    let value_step = proposer
+        .broadcast(b"cancel me".to_vec())
+        .expect("broadcast");
+    let value_msg = value_step
+        .messages
+        .iter()
+        .find(|tm| tm.target == Target::Node(1))
+        .expect("value for node 1")
+        .message
+        .clone();
+    let other_value_msg = value_step
+        .messages
+        .iter()
+        .find(|tm| tm.target == Target::Node(2))
+        .expect("value for node 2")
+        .message
+        .clone();
+    assert!(!node
+        .handle_message(&0, value_msg)
+        .expect("value")
+        .is_empty());
+    let echo_step = other.handle_message(&0, other_value_msg).expect("value");
+    let echo_msg = echo_step.messages[0].message.clone();
+    assert!(node
+        .handle_message(&2, echo_msg.clone())
+        .expect("echo")
+        .fault_log
+        .is_empty());
+    assert!(!node.terminated());
+
+    node.cancel();
+    assert!(node.cancelled());
+    assert!(node.terminated());
+    assert!(node.missing_contributors().is_empty());
+
+    // Any further messages are ignored, including the proposer's `Echo`.
+    for tm in value_step.messages {
+        if tm.target == Target::All {
+            assert!(node
+                .handle_message(&0, tm.message)
+                .expect("echo")
+                .is_empty());
+        }
+    }
+    assert!(node.handle_message(&2, echo_msg).expect("echo").is_empty());
+}
+
+#[test]
+fn test_value_from_non_proposer() {
+    let mut rng = rand::thread_rng();
+    let netinfos = NetworkInfo::generate_map(0..4usize, &mut rng).expect("netinfos");
+    let new_bc = |id, proposer_id| {
+        Broadcast::new(Arc::new(netinfos[&id].clone()), proposer_id).expect("broadcast")
+    };
+    let value_for_1 = |step: Step<usize>| {
+        step.messages
+            .into_iter()
+            .find(|tm| tm.target == Target::Node(1))
+            .expect("value for node 1")
+            .message
+    };
+    let mut proposer = new_bc(0, 0);
+    let mut node = new_bc(1, 0);
+    node.set_track_progress(true);
+    let value_msg = value_for_1(proposer.broadcast(b"legit".to_vec()).expect("broadcast"));
+
+    // Node 2 forwards node 1's `Value`, and sends one of its own, with a different root.
+    let mut impostor = new_bc(2, 2);
+    let fake_msg = value_for_1(impostor.broadcast(b"fake".to_vec()).expect("broadcast"));
+    let expected_fault = Fault::new(2, FaultKind::ReceivedValueFromNonProposer);
+    for msg in vec![value_msg.clone(), fake_msg] {
+        let step = node.handle_message(&2, msg).expect("value");
+        assert_eq!(vec![expected_fault.clone()], step.fault_log.0);
+        assert!(step.messages.is_empty());
+    }
+    assert!(node.take_progress().is_empty());
+
+    // The proposer's `Value` is accepted, and echoed.
+    let step = node.handle_message(&0, value_msg).expect("value");
+    assert!(step.fault_log.is_empty());
+    match step.messages[..] {
+        [ref tm] => match (&tm.target, &tm.message) {
+            (Target::All, Message::Echo(_)) => (),
+            (target, msg) => panic!("unexpected message {:?} to {:?}", msg, target),
+        },
+        ref msgs => panic!("unexpected messages {:?}", msgs),
+    }
+    assert_eq!(
+        vec![Progress::ValueReceived, Progress::EchoSent],
+        node.take_progress()
+    );
+}
+
+#[test]
+fn test_output_value() {
+    let mut rng = rand::thread_rng();
+    let netinfos = NetworkInfo::generate_map(0..4usize, &mut rng).expect("netinfos");
+    let mut nodes: BTreeMap<usize, _> = netinfos
+        .iter()
+        .map(|(id, netinfo)| {
+            let bc = Broadcast::new(Arc::new(netinfo.clone()), 0).expect("broadcast");
+            (*id, bc)
+        })
+        .collect();
+    for (id, node) in &nodes {
+        assert_eq!(None, node.output_value(), "node {} output too early", id);
+        assert_eq!(vec![0, 1, 2, 3], node.missing_contributors());
+    }
+    let value = b"reconstruct me".to_vec();
+    let outputs = run_broadcast(&mut nodes, value.clone());
+    assert_eq!(4, outputs.len());
+    for (id, node) in &nodes {
+        assert_eq!(Some(&outputs[id][..]), node.output_value());
+        assert_eq!(Some(&value[..]), node.output_value());
+        assert!(node.missing_contributors().is_empty());
+    }
+}
+
+#[test]
+fn test_progress() {
+    let mut rng = rand::thread_rng();
+    let netinfos = NetworkInfo::generate_map(0..4usize, &mut rng).expect("netinfos");
+    let mut nodes: BTreeMap<usize, _> = netinfos
+        .iter()
+        .map(|(id, netinfo)| {
+            let mut bc = Broadcast::new(Arc::new(netinfo.clone()), 0).expect("broadcast");
+            if *id != 3 {
+                bc.set_track_progress(true);
+            }
+            (*id, bc)
+        })
+        .collect();
+    run_broadcast(&mut nodes, b"track my progress".to_vec());
+    let expected = vec![
+        Progress::ValueReceived,
+        Progress::EchoSent,
+        Progress::ReadySent,
+        Progress::Decoded,
+    ];
+    for id in 0..3 {
+        let node = nodes.get_mut(&id).expect("node");
+        assert_eq!(expected, node.take_progress(), "node {}", id);
+        assert!(node.take_progress().is_empty());
+    }
+    // Node 3 didn't enable tracking.
+    assert!(nodes.get_mut(&3).expect("node").take_progress().is_empty());
+}
+
+#[test]
+fn test_reconstruct_without_proofs() {
+    let mut rng = rand::thread_rng();
+    let netinfos = NetworkInfo::generate_map(0..16usize, &mut rng).expect("netinfos");
+    let mut nodes: BTreeMap<usize, _> = netinfos
+        .iter()
+        .map(|(id, netinfo)| {
+            let bc = Broadcast::new(Arc::new(netinfo.clone()), 0).expect("broadcast");
+            (*id, bc)
+        })
+        .collect();
+    let value: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+    let outputs = run_broadcast(&mut nodes, value.clone());
+    assert_eq!(16, outputs.len());
+    assert!(outputs.values().all(|output| *output == value));
+}
+
+#[test]
+fn test_opening() {
+    let mut rng = rand::thread_rng();
+    let netinfos = NetworkInfo::generate_map(0..7usize, &mut rng).expect("netinfos");
+    let mut nodes: BTreeMap<usize, _> = netinfos
+        .iter()
+        .map(|(id, netinfo)| {
+            let bc = Broadcast::new(Arc::new(netinfo.clone()), 0).expect("broadcast");
+            (*id, bc)
+        })
+        .collect();
+    assert!(nodes[&1].opening(0).is_none());
+    let value: Vec<u8> = (0..100).collect();
+    run_broadcast(&mut nodes, value);
+    let root_hash = *nodes[&0].opening(0).expect("opening").1.root_hash();
+
+    // Every node can open every shard, including the interpolated ones.
+    for node in nodes.values() {
+        for index in 0..7 {
+            let (leaf, proof) = node.opening(index).expect("opening");
+            assert!(verify_opening(&root_hash, 7, index, &leaf, &proof));
+        }
+        assert!(node.opening(7).is_none());
+    }
+
+    // Tampered openings are rejected.
+    let (leaf, proof) = nodes[&3].opening(2).expect("opening");
+    let mut tampered_leaf = leaf.clone();
+    tampered_leaf[0] ^= 1;
+    assert!(!verify_opening(&root_hash, 7, 2, &tampered_leaf, &proof));
+    assert!(!verify_opening(&root_hash, 7, 3, &leaf, &proof));
+    assert!(!verify_opening(&[0; 32], 7, 2, &leaf, &proof));
+    let (other_leaf, other_proof) = nodes[&3].opening(5).expect("opening");
+    assert!(!verify_opening(&root_hash, 7, 2, &other_leaf, &other_proof));
+    assert!(!verify_opening(&root_hash, 7, 2, &leaf, &other_proof));
+}
+
+#[test]
+fn test_proposer_equivocation() {
+    let mut rng = rand::thread_rng();
+    let netinfos = NetworkInfo::generate_map(0..4usize, &mut rng).expect("netinfos");
+    let new_bc = |id: usize| Broadcast::new(Arc::new(netinfos[&id].clone()), 0).expect("bc");
+    // The faulty proposer 0 creates `Value`s for two different values.
+    let value_msgs = |value: &[u8]| -> BTreeMap<usize, Message> {
+        let step = new_bc(0).broadcast(value.to_vec()).expect("broadcast");
+        step.messages
+            .into_iter()
+            .map(|tm| match tm.target {
+                Target::All => (0, tm.message),
+                Target::Node(id) => (id, tm.message),
+            })
+            .collect()
+    };
+    let (msgs_a, msgs_b) = (value_msgs(b"Alice"), value_msgs(b"Bob"));
+
+    // Node 1 receives a shard of `Alice`, nodes 2 and 3 of `Bob`. Node 0 echoes `Alice`.
+    let mut nodes: BTreeMap<usize, _> = (1..4).map(|id| (id, new_bc(id))).collect();
+    let mut queue: VecDeque<_> = vec![(0, Target::All.message(msgs_a[&0].clone()))].into();
+    for (id, node) in &mut nodes {
+        let msg = if *id == 1 { &msgs_a[id] } else { &msgs_b[id] };
+        let step = node.handle_message(&0, msg.clone()).expect("value");
+        queue.extend(step.messages.into_iter().map(|tm| (*id, tm)));
+    }
+    let mut faults = BTreeMap::new();
+    while let Some((sender_id, tm)) = queue.pop_front() {
+        for (id, node) in &mut nodes {
+            let is_recipient = match tm.target {
+                Target::All => *id != sender_id,
+                Target::Node(ref target_id) => target_id == id,
+            };
+            if !is_recipient {
+                continue;
+            }
+            let step = node
+                .handle_message(&sender_id, tm.message.clone())
+                .expect("message");
+            assert!(step.output.is_empty());
+            queue.extend(step.messages.into_iter().map(|tm| (*id, tm)));
+            faults
+                .entry(*id)
+                .or_insert_with(Vec::new)
+                .extend(step.fault_log.0);
+        }
+    }
+
+    // Both trees were echoed by two nodes, so all correct nodes detect the equivocation.
+    let fault = Fault::new(0, FaultKind::ProposerEquivocation);
+    for id in 1..4 {
+        assert_eq!(vec![fault.clone()], faults[&id]);
+        assert!(!nodes[&id].terminated());
+    }
+}
+
+#[test]
+fn test_duplicate_echos_and_readys() {
+    let mut rng = rand::thread_rng();
+    let netinfos = NetworkInfo::generate_map(0..4usize, &mut rng).expect("netinfos");
+    let new_bc = |id: usize| Broadcast::new(Arc::new(netinfos[&id].clone()), 0).expect("bc");
+    // Node 2's `Echo` of node 2's shard of the given value.
+    let echo_msg = |value: &[u8]| -> Message {
+        let step = new_bc(0).broadcast(value.to_vec()).expect("broadcast");
+        let value_msg = step
+            .messages
+            .into_iter()
+            .find(|tm| tm.target == Target::Node(2))
+            .expect("value for node 2")
+            .message;
+        let echo_step = new_bc(2).handle_message(&0, value_msg).expect("value");
+        echo_step.messages[0].message.clone()
+    };
+    let (echo_a, echo_b) = (echo_msg(b"Alice"), echo_msg(b"Bob"));
+    let mut node = new_bc(1);
+
+    // Repeated `Echo`s are ignored, and a conflicting one is reported.
+    for _ in 0..3 {
+        let step = node.handle_message(&2, echo_a.clone()).expect("echo");
+        assert!(step.fault_log.is_empty());
+    }
+    let step = node.handle_message(&2, echo_b).expect("echo");
+    let fault = Fault::new(2, FaultKind::MultipleEchos);
+    assert_eq!(vec![fault], step.fault_log.0);
+
+    // The same applies to `Ready`s.
+    for _ in 0..3 {
+        let step = node
+            .handle_message(&2, Message::Ready([1; 32]))
+            .expect("ready");
+        assert!(step.fault_log.is_empty());
+    }
+    let step = node
+        .handle_message(&2, Message::Ready([2; 32]))
+        .expect("ready");
+    let fault = Fault::new(2, FaultKind::MultipleReadys);
+    assert_eq!(vec![fault], step.fault_log.0);
+    assert_eq!(vec![0, 1, 3], node.missing_contributors());
+}
+
+#[test]
+fn test_estimated_messages() {
+    let mut rng = rand::thread_rng();
+    for &(num_nodes, value_len) in &[(2, 10), (4, 2), (4, 100), (7, 1000), (16, 100)] {
+        let num_faulty = util::max_faulty(num_nodes);
+        let netinfos = NetworkInfo::generate_map(0..num_nodes, &mut rng).expect("netinfos");
+        let mut nodes: BTreeMap<usize, _> = netinfos
+            .iter()
+            .map(|(id, netinfo)| {
+                let bc = Broadcast::new(Arc::new(netinfo.clone()), 0).expect("broadcast");
+                (*id, bc)
+            })
+            .collect();
+        let (outputs, cost) = run_broadcast_with_cost(&mut nodes, vec![1; value_len]);
+        assert_eq!(num_nodes, outputs.len());
+        // The proposer sends a `Value` to every other node, and every node multicasts an
+        // `Echo` and a `Ready`.
+        let estimate = estimated_messages(num_nodes, num_faulty, value_len).expect("estimate");
+        assert_eq!((num_nodes - 1) * (2 * num_nodes + 1), estimate.messages);
+        assert_eq!(estimate.messages, cost.messages);
+        // Branches are shorter in the last leaves if `num_nodes` is not a power of two.
+        if num_nodes.is_power_of_two() {
+            assert_eq!(estimate.bytes, cost.bytes);
+        } else {
+            assert!(estimate.bytes >= cost.bytes);
+            assert!(estimate.bytes < cost.bytes + estimate.messages * 32);
+        }
+    }
+}
+
+#[test]
+fn test_estimated_messages_invalid() {
+    // With `2 * num_faulty >= num_nodes` there are no data shards.
+    assert_eq!(None, estimated_messages(3, 2, 100));
+    assert_eq!(None, estimated_messages(4, 2, 100));
+    assert_eq!(None, estimated_messages(0, 0, 100));
+    assert_eq!(None, estimated_messages(1, usize::max_value(), 100));
+    assert!(estimated_messages(3, 1, 100).is_some());
+}
+
+#[test]
+fn test_replicated_small_values() {
+    let mut rng = rand::thread_rng();
+    let netinfos = NetworkInfo::generate_map(0..7usize, &mut rng).expect("netinfos");
+    let new_nodes = || -> BTreeMap<usize, _> {
+        netinfos
+            .iter()
+            .map(|(id, netinfo)| {
+                let bc = Broadcast::new(Arc::new(netinfo.clone()), 0).expect("broadcast");
+                (*id, bc)
+            })
+            .collect()
+    };
+    // Returns the length of the leaf values the proposer sends with the given threshold.
+    let leaf_len = |value: &[u8], threshold: usize| {
+        let mut bc = Broadcast::new(Arc::new(netinfos[&0].clone()), 0).expect("broadcast");
+        bc.set_replication_threshold(threshold);
+        let step = bc.broadcast(value.to_vec()).expect("broadcast");
+        match step.messages[0].message {
+            Message::Value(ref proof) => proof.value().len(),
+            ref msg => panic!("unexpected message {:?}", msg),
+        }
+    };
+
+    // With seven nodes, there are three data shards. With that threshold, a single byte and a
+    // value exactly at the threshold are sent in full, with their length.
+    for value in &[vec![42], vec![1, 2, 3]] {
+        assert_eq!(value.len() + 4, leaf_len(value, 3));
+        let mut nodes = new_nodes();
+        nodes.get_mut(&0).unwrap().set_replication_threshold(3);
+        let outputs = run_broadcast(&mut nodes, value.clone());
+        assert_eq!(7, outputs.len());
+        assert!(outputs.values().all(|output| output == value));
+        let (leaf, proof) = nodes[&4].opening(5).expect("opening");
+        assert_eq!(value.len() + 4, leaf.len());
+        assert!(verify_opening(proof.root_hash(), 7, 5, &leaf, &proof));
+    }
+    // Longer values are erasure-coded: 4 + 4 bytes in three shards.
+    assert_eq!(3, leaf_len(&[1, 2, 3, 4], 3));
+    let mut nodes = new_nodes();
+    nodes.get_mut(&0).unwrap().set_replication_threshold(3);
+    let outputs = run_broadcast(&mut nodes, vec![1, 2, 3, 4]);
+    assert!(outputs.values().all(|output| *output == vec![1, 2, 3, 4]));
+
+    // By default, even a single byte or the empty value is coded.
+    assert_eq!(0, new_nodes()[&0].replication_threshold());
+    assert_eq!(2, leaf_len(&[42], 0));
+    assert_eq!(2, leaf_len(&[], 0));
+    let mut nodes = new_nodes();
+    let outputs = run_broadcast(&mut nodes, vec![42]);
+    assert!(outputs.values().all(|output| *output == vec![42]));
+    let (leaf, _) = nodes[&4].opening(5).expect("opening");
+    assert_eq!(2, leaf.len());
+}
+
+/// Makes node 0 broadcast `value`, delivers all messages and returns the outputs.
+fn run_broadcast(
+    nodes: &mut BTreeMap<usize, Broadcast<usize>>,
+    value: Vec<u8>,
+) -> BTreeMap<usize, Vec<u8>> {
+    run_broadcast_with_cost(nodes, value).0
+}
+
+/// Makes node 0 broadcast `value`, delivers all messages and returns the outputs, together
+/// with the number and serialized size of all messages that were delivered.
+fn run_broadcast_with_cost(
+    nodes: &mut BTreeMap<usize, Broadcast<usize>>,
+    value: Vec<u8>,
+) -> (BTreeMap<usize, Vec<u8>>, RoundCost) {
+    let step = nodes.get_mut(&0).unwrap().broadcast(value);
+    let mut queue: VecDeque<_> = step
+        .expect("broadcast")
+        .messages
+        .into_iter()
+        .map(|tm| (0, tm))
+        .collect();
+    let mut outputs = BTreeMap::new();
+    let mut cost = RoundCost::default();
+    while let Some((sender_id, tm)) = queue.pop_front() {
+        let size = bincode::serialize(&tm.message).expect("serialize").len();
+        for (id, node) in nodes.iter_mut() {
+            let is_recipient = match tm.target {
+                Target::All => *id != sender_id,
+                Target::Node(ref target_id) => target_id == id,
+            };
+            if !is_recipient {
+                continue;
+            }
+            cost.messages += 1;
+            cost.bytes += size;
+            let step = node
+                .handle_message(&sender_id, tm.message.clone())
+                .expect("message");
+            queue.extend(step.messages.into_iter().map(|tm| (*id, tm)));
+            assert!(step.fault_log.is_empty());
+            for output in step.output {
+                assert!(outputs.insert(*id, output).is_none());
+            }
+        }
+    }
+    (outputs, cost)
+}
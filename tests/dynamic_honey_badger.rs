@@ -0,0 +1,28 @@
+#![deny(unused_must_use)]
+//! Tests of the error paths of a single Dynamic Honey Badger node.
+
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+use hbbft::dynamic_honey_badger::{DynamicHoneyBadger, Error};
+use hbbft::NetworkInfo;
+
+type DHB = DynamicHoneyBadger<Vec<u64>, usize>;
+
+#[test]
+fn test_dynamic_honey_badger_epoch_overflow() {
+    let mut rng = XorShiftRng::from_seed([16; 16]);
+    let netinfo = NetworkInfo::generate_map(0..1, &mut rng)
+        .expect("netinfos")
+        .remove(&0)
+        .expect("our netinfo");
+    let mut dhb: DHB = DynamicHoneyBadger::builder()
+        .era(u64::max_value())
+        .build(netinfo);
+    // A single validator outputs its batch right away, and then fails to advance the epoch.
+    match dhb.propose(vec![1], &mut rng) {
+        Err(Error::EpochOverflow) => (),
+        result => panic!("expected an epoch overflow error, got {:?}", result),
+    }
+    assert_eq!(u64::max_value(), dhb.next_epoch());
+}
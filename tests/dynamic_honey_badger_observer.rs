@@ -0,0 +1,190 @@
+#![deny(unused_must_use)]
+//! Tests of an observer that joins a Dynamic Honey Badger network without a vote.
+//!
+//! Four validators run a few epochs. After the first one, an observer joins with a plan created
+//! by `DynamicHoneyBadger::observer_join_plan`, and must output the same batches as the
+//! validators from then on. Later the validators vote to remove one of them, and the change must
+//! complete without any participation by the observer.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+use hbbft::crypto::SecretKey;
+use hbbft::dynamic_honey_badger::{
+    Batch, Change, ChangeState, DynamicHoneyBadger, Error, JoinPlan, Message, Step,
+};
+use hbbft::{Fault, FaultKind, NetworkInfo, Target, TargetedMessage};
+
+type NodeId = usize;
+type DHB = DynamicHoneyBadger<Vec<u64>, NodeId>;
+
+/// The ID of the observer.
+const OBSERVER_ID: NodeId = 10;
+
+/// A simulated network, in which all messages are delivered in a random order.
+struct Network {
+    nodes: BTreeMap<NodeId, DHB>,
+    queue: VecDeque<(NodeId, TargetedMessage<Message<NodeId>, NodeId>)>,
+    batches: BTreeMap<NodeId, Vec<Batch<Vec<u64>, NodeId>>>,
+    rng: XorShiftRng,
+}
+
+impl Network {
+    /// Queues the step's messages and records its batches.
+    fn handle_step(&mut self, id: NodeId, step: Step<Vec<u64>, NodeId>) {
+        assert!(
+            step.fault_log.is_empty(),
+            "unexpected faults at {}: {:?}",
+            id,
+            step.fault_log
+        );
+        if id == OBSERVER_ID {
+            assert!(step.messages.is_empty(), "the observer sent a message");
+        }
+        self.queue
+            .extend(step.messages.into_iter().map(|tm| (id, tm)));
+        self.batches
+            .entry(id)
+            .or_insert_with(Vec::new)
+            .extend(step.output);
+    }
+
+    /// Lets every validator that hasn't yet propose, and delivers all messages until none are
+    /// left.
+    fn run_epoch(&mut self) {
+        let ids: Vec<NodeId> = self.nodes.keys().cloned().collect();
+        for id in ids {
+            let dhb = self.nodes.get_mut(&id).expect("node");
+            if !dhb.netinfo().is_validator() || dhb.has_input() {
+                continue;
+            }
+            let contrib = vec![dhb.next_epoch(), id as u64];
+            let step = dhb.propose(contrib, &mut self.rng).expect("propose");
+            self.handle_step(id, step);
+        }
+        while !self.queue.is_empty() {
+            let idx = self.rng.gen_range(0, self.queue.len());
+            let (sender_id, tm) = self.queue.remove(idx).expect("message");
+            let recipients: Vec<NodeId> = match tm.target {
+                Target::All => self
+                    .nodes
+                    .keys()
+                    .cloned()
+                    .filter(|id| *id != sender_id)
+                    .collect(),
+                Target::Node(id) => vec![id],
+            };
+            for id in recipients {
+                let dhb = self.nodes.get_mut(&id).expect("recipient");
+                let step = dhb
+                    .handle_message(&sender_id, tm.message.clone(), &mut self.rng)
+                    .expect("handle message");
+                // Without a `SenderQueue`, messages from a later era are rejected. They are
+                // retried once the recipient has caught up.
+                if step.fault_log.0
+                    == vec![Fault::new(sender_id, FaultKind::UnexpectedDhbMessageEra)]
+                {
+                    let tm = Target::Node(id).message(tm.message.clone());
+                    self.queue.push_back((sender_id, tm));
+                    continue;
+                }
+                self.handle_step(id, step);
+            }
+        }
+    }
+
+    /// Returns the last batch the node has output.
+    fn last_batch(&self, id: NodeId) -> &Batch<Vec<u64>, NodeId> {
+        self.batches[&id].last().expect("no batch")
+    }
+}
+
+#[test]
+fn test_dynamic_honey_badger_observer_without_vote() {
+    let mut rng = XorShiftRng::from_seed([3; 16]);
+    let netinfos = NetworkInfo::generate_map(0..4, &mut rng).expect("netinfos");
+    let nodes = netinfos
+        .into_iter()
+        .map(|(id, netinfo)| (id, DHB::builder().build(netinfo)))
+        .collect();
+    let mut net = Network {
+        nodes,
+        queue: VecDeque::new(),
+        batches: BTreeMap::new(),
+        rng,
+    };
+
+    // After the first epoch there is no change, so the batch doesn't contain a join plan.
+    net.run_epoch();
+    assert!(net.last_batch(0).join_plan().is_none());
+    let join_plan = net.nodes[&0].observer_join_plan().expect("join plan");
+    assert_eq!(1, join_plan.next_epoch());
+    let sk: SecretKey = net.rng.gen();
+    let (observer, step) =
+        DHB::new_joining(OBSERVER_ID, sk, join_plan, &mut net.rng).expect("join");
+    assert!(!observer.netinfo().is_validator());
+    net.nodes.insert(OBSERVER_ID, observer);
+    net.handle_step(OBSERVER_ID, step);
+
+    // The observer outputs the same batches as the validators.
+    for epoch in 1..3 {
+        net.run_epoch();
+        let batch = net.last_batch(OBSERVER_ID);
+        assert_eq!(epoch, batch.epoch());
+        assert_eq!(epoch as usize, net.batches[&0].len() - 1);
+        assert!(batch.public_eq(net.last_batch(0)));
+        assert!(batch.contributions().count() >= 3);
+    }
+
+    // The validators vote to remove node 3. Once votes are committed, observers cannot join
+    // without waiting for the next era.
+    for id in 0..4 {
+        let step = net.nodes.get_mut(&id).expect("node").vote_to_remove(&3);
+        let step = step.expect("vote");
+        net.handle_step(id, step);
+    }
+    let mut complete = None;
+    for _ in 0..10 {
+        net.run_epoch();
+        let batch = net.last_batch(OBSERVER_ID).clone();
+        assert!(batch.public_eq(net.last_batch(0)));
+        if let ChangeState::Complete(Change::NodeChange(ref pub_keys)) = *batch.change() {
+            complete = Some(pub_keys.keys().cloned().collect::<Vec<_>>());
+            break;
+        }
+        assert!(net.nodes[&0].observer_join_plan().is_none());
+    }
+    assert_eq!(Some(vec![0, 1, 2]), complete);
+
+    // The new validator set works, and the observer is still following.
+    net.run_epoch();
+    let batch = net.last_batch(OBSERVER_ID);
+    assert!(batch.public_eq(net.last_batch(0)));
+    assert_eq!(3, batch.contributions().count());
+    assert!(!net.nodes[&OBSERVER_ID].netinfo().is_validator());
+    assert!(net.nodes[&0].observer_join_plan().is_some());
+}
+
+#[test]
+fn test_dynamic_honey_badger_malformed_join_plan() {
+    let mut rng = XorShiftRng::from_seed([4; 16]);
+    let netinfos = NetworkInfo::generate_map(0..1, &mut rng).expect("netinfos");
+    let netinfo = netinfos.into_iter().next().expect("netinfo").1;
+    let dhb = DHB::builder().build(netinfo);
+    let join_plan = dhb.observer_join_plan().expect("join plan");
+    assert_eq!(0, join_plan.next_epoch());
+
+    // The plan is sent by another node. Change its era, the first serialized field, so that its
+    // epoch is before the beginning of the era.
+    let mut bytes = bincode::serialize(&join_plan).expect("serialize");
+    bytes[..8].copy_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+    let join_plan: JoinPlan<NodeId> = bincode::deserialize(&bytes).expect("deserialize");
+    let sk: SecretKey = rng.gen();
+    match DHB::new_joining(OBSERVER_ID, sk, join_plan, &mut rng) {
+        Err(Error::InvalidJoinPlan) => (),
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("joined with a malformed plan"),
+    }
+}
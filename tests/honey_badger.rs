@@ -11,10 +11,12 @@ use itertools::Itertools;
 use log::info;
 use rand::{seq::SliceRandom, Rng};
 
-use hbbft::honey_badger::{Batch, EncryptionSchedule, HoneyBadger, MessageContent};
+use hbbft::honey_badger::{self, Batch, EncryptionSchedule, HoneyBadger, MessageContent};
 use hbbft::sender_queue::{self, SenderQueue, Step};
 use hbbft::transaction_queue::TransactionQueue;
-use hbbft::{threshold_decrypt, util, DistAlgorithm, NetworkInfo, Target, TargetedMessage};
+use hbbft::{
+    threshold_decrypt, util, DistAlgorithm, Fault, FaultKind, NetworkInfo, Target, TargetedMessage,
+};
 
 use crate::network::{
     Adversary, MessageScheduler, MessageWithSender, NodeId, RandomAdversary, SilentAdversary,
@@ -243,3 +245,55 @@ fn test_honey_badger_random_adversary() {
     };
     test_honey_badger_different_sizes(new_adversary, 8);
 }
+
+#[test]
+fn test_honey_badger_epoch_overflow() {
+    let mut rng = rand::thread_rng();
+    let netinfo = NetworkInfo::generate_map(0..1, &mut rng)
+        .expect("generate network info")
+        .remove(&0)
+        .expect("our network info");
+    let mut hb = HoneyBadger::<Vec<usize>, usize>::builder(Arc::new(netinfo))
+        .epoch(u64::max_value())
+        .build();
+    // A single validator outputs its batch right away, and then fails to advance the epoch.
+    match hb.propose(&vec![1], &mut rng) {
+        Err(honey_badger::Error::EpochOverflow) => (),
+        result => panic!("expected an epoch overflow error, got {:?}", result),
+    }
+    assert_eq!(hb.next_epoch(), u64::max_value());
+}
+
+#[test]
+fn test_honey_badger_future_epoch_flood() {
+    let mut rng = rand::thread_rng();
+    let netinfos = NetworkInfo::generate_map(0..4, &mut rng).expect("generate network info");
+    let mut hb = HoneyBadger::<Vec<usize>, usize>::builder(Arc::new(netinfos[&0].clone()))
+        .max_future_epochs(2)
+        .build();
+    // Node 1 floods node 0 with valid messages for ever later epochs.
+    for &epoch in &[0, 1, 2, 3, 4, 10, 1000, u64::max_value()] {
+        let mut sender = HoneyBadger::<Vec<usize>, usize>::builder(Arc::new(netinfos[&1].clone()))
+            .epoch(epoch)
+            .build();
+        let step = sender.propose(&vec![1, 2, 3], &mut rng).expect("propose");
+        assert!(!step.messages.is_empty());
+        for tm in step.messages {
+            if tm.target != Target::All && tm.target != Target::Node(0) {
+                continue;
+            }
+            let step = hb.handle_message(&1, tm.message).expect("handle message");
+            if epoch <= 2 {
+                assert!(step.fault_log.is_empty());
+            } else {
+                let fault = Fault::new(1, FaultKind::UnexpectedHbMessageEpoch);
+                assert_eq!(vec![fault], step.fault_log.0);
+            }
+        }
+        // State is only kept for the current and the next two epochs.
+        assert_eq!(
+            epoch.saturating_add(1).min(3) as usize,
+            hb.epochs_in_memory()
+        );
+    }
+}
@@ -0,0 +1,248 @@
+#![deny(unused_must_use)]
+//! An end-to-end test of a single Honey Badger epoch.
+//!
+//! Four nodes with deterministic keys each propose their own transactions. Each node's proposal
+//! is sent to the others via `Broadcast`, `Subset` runs one `BinaryAgreement` instance per
+//! proposer to decide which proposals are included, and in some epochs those instances use a
+//! `ThresholdSign` coin. Finally, the contributions are threshold-decrypted and output as the
+//! epoch's `Batch`, which must be identical on all correct nodes.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::Arc;
+
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+use hbbft::honey_badger::{self, Batch, HoneyBadger, Message};
+use hbbft::{Diagnose, NetworkInfo, Target, TargetedMessage};
+
+type NodeId = usize;
+type HB = HoneyBadger<Vec<u64>, NodeId>;
+
+/// The transactions proposed by node `id`.
+fn transactions(id: NodeId) -> Vec<u64> {
+    (0..3).map(|i| 100 * id as u64 + i).collect()
+}
+
+/// Runs one epoch in a network of `num_nodes` nodes, of which the ones in `silent` neither send
+/// nor receive any messages, and returns each correct node's batch.
+fn run_epoch(
+    num_nodes: usize,
+    silent: &BTreeSet<NodeId>,
+) -> BTreeMap<NodeId, Batch<Vec<u64>, NodeId>> {
+    let mut rng = XorShiftRng::from_seed([7; 16]);
+    let netinfos = NetworkInfo::generate_map(0..num_nodes, &mut rng)
+        .expect("failed to generate `NetworkInfo` map");
+    let mut nodes: BTreeMap<NodeId, HB> = netinfos
+        .into_iter()
+        .filter(|(id, _)| !silent.contains(id))
+        .map(|(id, netinfo)| (id, HoneyBadger::builder(Arc::new(netinfo)).build()))
+        .collect();
+    let mut queue: VecDeque<(NodeId, TargetedMessage<Message<NodeId>, NodeId>)> = VecDeque::new();
+    let mut batches = BTreeMap::new();
+
+    // Every correct node proposes its own transactions.
+    for (id, hb) in &mut nodes {
+        let step = hb
+            .propose(&transactions(*id), &mut rng)
+            .expect("failed to propose");
+        assert!(step.output.is_empty());
+        queue.extend(step.messages.into_iter().map(|tm| (*id, tm)));
+    }
+
+    // Deliver the messages in a random order until all correct nodes have output a batch.
+    while batches.len() < nodes.len() {
+        let idx = rng.gen_range(0, queue.len());
+        let (sender_id, tm) = queue.remove(idx).expect("no pending messages");
+        let recipients: Vec<NodeId> = match tm.target {
+            Target::All => nodes
+                .keys()
+                .cloned()
+                .filter(|id| *id != sender_id)
+                .collect(),
+            Target::Node(id) if nodes.contains_key(&id) => vec![id],
+            Target::Node(_) => vec![], // Silent nodes drop all messages.
+        };
+        for id in recipients {
+            let step = nodes
+                .get_mut(&id)
+                .expect("unknown recipient")
+                .handle_message(&sender_id, tm.message.clone())
+                .expect("failed to handle message");
+            assert!(step.fault_log.is_empty());
+            queue.extend(step.messages.into_iter().map(|tm| (id, tm)));
+            for batch in step.output {
+                assert!(batches.insert(id, batch).is_none(), "more than one batch");
+            }
+        }
+    }
+    batches
+}
+
+/// Checks that all batches are identical and contain the transactions of at least _N - f_ nodes,
+/// none of which are in `silent`.
+fn verify_batches(
+    num_nodes: usize,
+    silent: &BTreeSet<NodeId>,
+    batches: &BTreeMap<NodeId, Batch<Vec<u64>, NodeId>>,
+) {
+    let batch = batches.values().next().expect("no batches");
+    assert!(batches
+        .values()
+        .all(|b| b.epoch == 0 && b.contributions == batch.contributions));
+    let num_faulty = (num_nodes - 1) / 3;
+    assert!(batch.contributions.len() >= num_nodes - num_faulty);
+    for (id, contribution) in &batch.contributions {
+        assert!(!silent.contains(id));
+        assert_eq!(transactions(*id), *contribution);
+    }
+}
+
+#[test]
+fn test_honey_badger_epoch_all_correct() {
+    let silent = BTreeSet::new();
+    let batches = run_epoch(4, &silent);
+    assert_eq!(4, batches.len());
+    verify_batches(4, &silent, &batches);
+}
+
+#[test]
+fn test_honey_badger_epoch_one_silent() {
+    let silent = vec![3].into_iter().collect();
+    let batches = run_epoch(4, &silent);
+    assert_eq!(3, batches.len());
+    verify_batches(4, &silent, &batches);
+    // Only the three correct nodes' proposals can make it into the batch.
+    let proposers: Vec<NodeId> = batches[&0].contributions.keys().cloned().collect();
+    assert_eq!(vec![0, 1, 2], proposers);
+}
+
+#[test]
+fn test_honey_badger_estimated_messages() {
+    let estimate = honey_badger::estimated_messages(4, 1, 100).expect("estimate");
+    // Four broadcasts, each with three `Value`s and twelve `Echo`s and `Ready`s, four agreement
+    // instances with twelve `BVal`s, `Aux`s and `Term`s, and twelve decryption shares for each
+    // contribution.
+    assert_eq!(4 * (3 + 2 * 12) + 4 * 3 * 12 + 4 * 12, estimate.messages);
+    assert_eq!(75, estimate.messages_per_node(4));
+    // With real keys, a ciphertext of 100 bytes has 252 bytes, and so do the `Value` and `Echo`
+    // messages with two shards of 128 bytes and a branch of two digests.
+    if cfg!(not(feature = "use-insecure-test-only-mock-crypto")) {
+        let broadcasts = 4 * (15 * 252 + 12 * 36);
+        let agreements = 4 * (24 * 17 + 12 * 13);
+        let decryption = 48 * 48;
+        assert_eq!(broadcasts + agreements + decryption, estimate.bytes);
+    }
+    // Three nodes can't tolerate two faulty ones.
+    assert_eq!(None, honey_badger::estimated_messages(3, 2, 100));
+}
+
+#[test]
+fn test_honey_badger_buffered_messages() {
+    let mut rng = XorShiftRng::from_seed([8; 16]);
+    let netinfos =
+        NetworkInfo::generate_map(0..4, &mut rng).expect("failed to generate `NetworkInfo` map");
+    let mut nodes: BTreeMap<NodeId, HB> = netinfos
+        .into_iter()
+        .map(|(id, netinfo)| (id, HoneyBadger::builder(Arc::new(netinfo)).build()))
+        .collect();
+    // Node 3 lags behind: The messages to it are held back, and the other three run two epochs
+    // without it.
+    let lagging = 3;
+    let mut held = Vec::new();
+    let mut queue: VecDeque<(NodeId, TargetedMessage<Message<NodeId>, NodeId>)> = VecDeque::new();
+    let mut num_batches: BTreeMap<NodeId, u64> = BTreeMap::new();
+    for epoch in 0..2 {
+        for id in 0..lagging {
+            let hb = nodes.get_mut(&id).expect("node");
+            let step = hb.propose(&transactions(id), &mut rng).expect("propose");
+            queue.extend(step.messages.into_iter().map(|tm| (id, tm)));
+        }
+        while (0..lagging).any(|id| num_batches.get(&id).cloned().unwrap_or(0) <= epoch) {
+            let idx = rng.gen_range(0, queue.len());
+            let (sender_id, tm) = queue.remove(idx).expect("no pending messages");
+            let recipients: Vec<NodeId> = match tm.target {
+                Target::All => (0..4).filter(|id| *id != sender_id).collect(),
+                Target::Node(id) => vec![id],
+            };
+            for id in recipients {
+                if id == lagging {
+                    held.push((sender_id, tm.message.clone()));
+                    continue;
+                }
+                let hb = nodes.get_mut(&id).expect("recipient");
+                let step = hb
+                    .handle_message(&sender_id, tm.message.clone())
+                    .expect("failed to handle message");
+                assert!(step.fault_log.is_empty());
+                queue.extend(step.messages.into_iter().map(|tm| (id, tm)));
+                *num_batches.entry(id).or_insert(0) += step.output.len() as u64;
+            }
+        }
+    }
+    // Messages that are still in the queue are for completed epochs, so they are dropped.
+    for (_, tm) in queue {
+        assert!(tm.message.epoch() < 2);
+    }
+
+    // Node 3 first receives the messages for epoch 1, which it keeps until epoch 0 is complete.
+    let (epoch1, epoch0): (Vec<_>, Vec<_>) =
+        held.into_iter().partition(|(_, msg)| msg.epoch() == 1);
+    let hb = nodes.get_mut(&lagging).expect("lagging node");
+    for (sender_id, msg) in &epoch1 {
+        let step = hb.handle_message(sender_id, msg.clone()).expect("message");
+        assert!(step.output.is_empty());
+    }
+    assert_eq!(epoch1.len(), hb.buffered_message_count());
+    assert_eq!(1, hb.epochs_in_memory());
+
+    // Once it receives the messages for epoch 0, it outputs both batches, and all state is
+    // dropped.
+    let mut epochs = Vec::new();
+    for (sender_id, msg) in epoch0 {
+        let step = hb.handle_message(&sender_id, msg).expect("message");
+        epochs.extend(step.output.into_iter().map(|batch| batch.epoch));
+    }
+    assert_eq!(vec![0, 1], epochs);
+    assert_eq!(2, hb.next_epoch());
+    assert_eq!(0, hb.buffered_message_count());
+    assert_eq!(0, hb.epochs_in_memory());
+}
+
+#[test]
+fn test_honey_badger_diagnose() {
+    let mut rng = XorShiftRng::from_seed([9; 16]);
+    let netinfos =
+        NetworkInfo::generate_map(0..4, &mut rng).expect("failed to generate `NetworkInfo` map");
+    let mut hb = HoneyBadger::builder(Arc::new(netinfos[&0].clone())).build();
+    let step = hb.propose(&transactions(0), &mut rng).expect("propose");
+    assert!(!step.messages.is_empty());
+
+    // Without any messages from the other nodes, the epoch is stuck in the `Subset` instance: Our
+    // own broadcast has sent its `Echo`, and the others are still waiting for their values.
+    let tree = hb.diagnose();
+    assert_eq!(
+        "HoneyBadger epoch 0: proposed, 1 epochs in progress",
+        tree.label
+    );
+    let epoch = &tree.children[0];
+    assert_eq!(
+        "epoch 0: subset ongoing, 0 of 0 decryptions complete",
+        epoch.label
+    );
+    let subset = &epoch.children[0];
+    assert_eq!(4, subset.children.len());
+    let (own, other) = (&subset.children[0], &subset.children[1]);
+    assert_eq!("proposer 0: ongoing", own.label);
+    assert_eq!(
+        "Broadcast from 0: 1 echos, 0 readys, echo sent",
+        own.children[0].label
+    );
+    assert_eq!(
+        "Broadcast from 1: 0 echos, 0 readys, waiting for value",
+        other.children[0].label
+    );
+    assert!(other.children[1]
+        .label
+        .starts_with("BinaryAgreement epoch 0"));
+}
@@ -0,0 +1,132 @@
+#![deny(unused_must_use)]
+//! Tests of a Honey Badger fairness policy over several epochs.
+//!
+//! Node 0 proposes ten times as many transactions as the other nodes in every epoch. Without a
+//! policy, it dominates the batches. With a `ByteShareCap`, its contributions are only included
+//! in some epochs, so that every proposer gets a similar share of the batches.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+use hbbft::honey_badger::{Batch, ByteShareCap, HoneyBadger, Message};
+use hbbft::{NetworkInfo, Target, TargetedMessage};
+
+type NodeId = usize;
+type HB = HoneyBadger<Vec<u64>, NodeId>;
+
+const NUM_NODES: usize = 4;
+const NUM_EPOCHS: u64 = 10;
+
+/// The transactions proposed by node `id` in `epoch`: 40 for node 0, and 4 for everyone else.
+fn transactions(id: NodeId, epoch: u64) -> Vec<u64> {
+    let count = if id == 0 { 40 } else { 4 };
+    (0..count)
+        .map(|i| 1000 * epoch + 100 * id as u64 + i)
+        .collect()
+}
+
+/// Runs `NUM_EPOCHS` epochs and returns each node's batches. If `policy` is given, it is used in
+/// all nodes.
+fn run_epochs(policy: Option<ByteShareCap>) -> BTreeMap<NodeId, Vec<Batch<Vec<u64>, NodeId>>> {
+    let mut rng = XorShiftRng::from_seed([9; 16]);
+    let netinfos = NetworkInfo::generate_map(0..NUM_NODES, &mut rng)
+        .expect("failed to generate `NetworkInfo` map");
+    let mut nodes: BTreeMap<NodeId, HB> = netinfos
+        .into_iter()
+        .map(|(id, netinfo)| {
+            let mut builder = HoneyBadger::builder(Arc::new(netinfo));
+            if let Some(ref policy) = policy {
+                builder.fairness_policy(policy.clone());
+            }
+            (id, builder.build())
+        })
+        .collect();
+    let mut queue: VecDeque<(NodeId, TargetedMessage<Message<NodeId>, NodeId>)> = VecDeque::new();
+    let mut batches: BTreeMap<NodeId, Vec<_>> = BTreeMap::new();
+
+    for (id, hb) in &mut nodes {
+        let step = hb
+            .propose(&transactions(*id, 0), &mut rng)
+            .expect("failed to propose");
+        queue.extend(step.messages.into_iter().map(|tm| (*id, tm)));
+    }
+
+    // Deliver the messages in a random order. Whenever a node outputs a batch, it proposes its
+    // transactions for the next epoch.
+    while !queue.is_empty() {
+        let idx = rng.gen_range(0, queue.len());
+        let (sender_id, tm) = queue.remove(idx).expect("no pending messages");
+        let recipients: Vec<NodeId> = match tm.target {
+            Target::All => (0..NUM_NODES).filter(|id| *id != sender_id).collect(),
+            Target::Node(id) => vec![id],
+        };
+        for id in recipients {
+            let hb = nodes.get_mut(&id).expect("unknown recipient");
+            let step = hb
+                .handle_message(&sender_id, tm.message.clone())
+                .expect("failed to handle message");
+            assert!(step.fault_log.is_empty());
+            let mut messages = step.messages;
+            for batch in step.output {
+                batches.entry(id).or_insert_with(Vec::new).push(batch);
+                if hb.next_epoch() < NUM_EPOCHS {
+                    let txs = transactions(id, hb.next_epoch());
+                    let step = hb.propose(&txs, &mut rng).expect("failed to propose");
+                    assert!(step.output.is_empty() && step.fault_log.is_empty());
+                    messages.extend(step.messages);
+                }
+            }
+            queue.extend(messages.into_iter().map(|tm| (id, tm)));
+        }
+    }
+    batches
+}
+
+/// Checks that all nodes output the same `NUM_EPOCHS` batches, and returns the number of included
+/// transactions by proposer.
+fn included_transactions(
+    batches: &BTreeMap<NodeId, Vec<Batch<Vec<u64>, NodeId>>>,
+) -> BTreeMap<NodeId, usize> {
+    let first = &batches[&0];
+    assert_eq!(NUM_EPOCHS as usize, first.len());
+    for node_batches in batches.values() {
+        assert_eq!(first.len(), node_batches.len());
+        for (batch, other) in first.iter().zip(node_batches) {
+            assert_eq!(batch.epoch, other.epoch);
+            assert_eq!(batch.contributions, other.contributions);
+        }
+    }
+    let mut counts = BTreeMap::new();
+    for batch in first {
+        for (id, contrib) in &batch.contributions {
+            *counts.entry(*id).or_insert(0) += contrib.len();
+        }
+    }
+    counts
+}
+
+#[test]
+fn test_honey_badger_without_fairness() {
+    let counts = included_transactions(&run_epochs(None));
+    // Node 0 contributes much more than anyone else.
+    let others = (1..NUM_NODES).filter_map(|id| counts.get(&id)).max();
+    assert!(counts[&0] > 5 * others.expect("no other contributions"));
+}
+
+#[test]
+fn test_honey_badger_fairness_balanced() {
+    let counts = included_transactions(&run_epochs(Some(ByteShareCap::new(10, 2))));
+    // Every proposer is included, and nobody has much more than twice the share of anyone else.
+    assert_eq!(
+        NUM_NODES,
+        counts.len(),
+        "a proposer was starved: {:?}",
+        counts
+    );
+    let max = *counts.values().max().expect("no contributions");
+    let min = *counts.values().min().expect("no contributions");
+    assert!(max <= 3 * min, "unbalanced batches: {:?}", counts);
+}
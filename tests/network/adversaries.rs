@@ -0,0 +1,116 @@
+//! Adversaries whose nodes send messages that look correct, but try to stall or bias the
+//! algorithm.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use hbbft::threshold_sign::{Message, ThresholdSign};
+use hbbft::{NetworkInfo, Target, TargetedMessage};
+
+use super::{Adversary, MessageScheduler, MessageWithSender, NodeId, TestNode};
+
+/// An adversary whose nodes sign a different document than the correct nodes, e.g. a stale
+/// nonce. The shares are valid signature shares, but the correct nodes' verification must reject
+/// them and report the senders as faulty.
+#[allow(unused)] // Not used in all tests.
+pub struct WrongDocumentAdversary {
+    scheduler: MessageScheduler,
+    /// The shares that haven't been sent yet.
+    outgoing: Vec<MessageWithSender<ThresholdSign<NodeId>>>,
+}
+
+impl WrongDocumentAdversary {
+    /// Creates a new adversary whose nodes sign `wrong_doc`.
+    #[allow(unused)] // Not used in all tests.
+    pub fn new<M: AsRef<[u8]>>(
+        scheduler: MessageScheduler,
+        adv_nodes: &BTreeMap<NodeId, Arc<NetworkInfo<NodeId>>>,
+        wrong_doc: M,
+    ) -> Self {
+        let outgoing = adv_nodes
+            .iter()
+            .filter_map(|(id, netinfo)| {
+                let share = netinfo.secret_key_share()?.sign(&wrong_doc);
+                let tm = Target::All.message(Message::new(0, share));
+                Some(MessageWithSender::new(*id, tm))
+            })
+            .collect();
+        WrongDocumentAdversary {
+            scheduler,
+            outgoing,
+        }
+    }
+}
+
+impl Adversary<ThresholdSign<NodeId>> for WrongDocumentAdversary {
+    fn pick_node(&self, nodes: &BTreeMap<NodeId, TestNode<ThresholdSign<NodeId>>>) -> NodeId {
+        self.scheduler.pick_node(nodes)
+    }
+
+    fn push_message(&mut self, _: NodeId, _: TargetedMessage<Message, NodeId>) {
+        // All incoming messages are ignored.
+    }
+
+    fn step(&mut self) -> Vec<MessageWithSender<ThresholdSign<NodeId>>> {
+        self.outgoing.drain(..).collect()
+    }
+}
+
+/// An adversary whose nodes send correct shares, but only after they have received enough
+/// shares from the correct nodes that their own shares are not needed anymore.
+#[allow(unused)] // Not used in all tests.
+pub struct LateShareAdversary {
+    scheduler: MessageScheduler,
+    /// The number of shares that suffice to produce the signature.
+    threshold: usize,
+    /// The nodes from which the adversary has received a share.
+    senders: BTreeSet<NodeId>,
+    /// The shares that haven't been sent yet.
+    outgoing: Vec<MessageWithSender<ThresholdSign<NodeId>>>,
+}
+
+impl LateShareAdversary {
+    /// Creates a new adversary whose nodes sign `doc`, but hold back their shares.
+    #[allow(unused)] // Not used in all tests.
+    pub fn new<M: AsRef<[u8]>>(
+        scheduler: MessageScheduler,
+        adv_nodes: &BTreeMap<NodeId, Arc<NetworkInfo<NodeId>>>,
+        doc: M,
+    ) -> Self {
+        let threshold = adv_nodes
+            .values()
+            .next()
+            .map_or(0, |netinfo| netinfo.threshold() + 1);
+        let outgoing = adv_nodes
+            .iter()
+            .filter_map(|(id, netinfo)| {
+                let share = netinfo.secret_key_share()?.sign(&doc);
+                let tm = Target::All.message(Message::new(0, share));
+                Some(MessageWithSender::new(*id, tm))
+            })
+            .collect();
+        LateShareAdversary {
+            scheduler,
+            threshold,
+            senders: BTreeSet::new(),
+            outgoing,
+        }
+    }
+}
+
+impl Adversary<ThresholdSign<NodeId>> for LateShareAdversary {
+    fn pick_node(&self, nodes: &BTreeMap<NodeId, TestNode<ThresholdSign<NodeId>>>) -> NodeId {
+        self.scheduler.pick_node(nodes)
+    }
+
+    fn push_message(&mut self, sender_id: NodeId, _: TargetedMessage<Message, NodeId>) {
+        self.senders.insert(sender_id);
+    }
+
+    fn step(&mut self) -> Vec<MessageWithSender<ThresholdSign<NodeId>>> {
+        if self.senders.len() < self.threshold {
+            return vec![]; // Wait until the shares are useless.
+        }
+        self.outgoing.drain(..).collect()
+    }
+}
@@ -1,3 +1,5 @@
+pub mod adversaries;
+
 use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::fmt::{self, Debug};
 use std::mem;
@@ -16,7 +18,9 @@ use hbbft::{
 };
 
 /// A node identifier. In the tests, nodes are simply numbered.
-#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Clone, Copy, Serialize, Deserialize, Rand)]
+#[derive(
+    Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Clone, Copy, Serialize, Deserialize, Rand,
+)]
 pub struct NodeId(pub usize);
 
 /// A "node" running an instance of the algorithm `D`.
@@ -376,7 +380,28 @@ pub struct TestNetwork<A: Adversary<D>, D: DistAlgorithm> {
     pub nodes: BTreeMap<D::NodeId, TestNode<D>>,
     pub observer: TestNode<D>,
     pub adv_nodes: BTreeMap<D::NodeId, Arc<NetworkInfo<D::NodeId>>>,
+    /// The faults that correct nodes reported about the adversary's nodes.
+    pub faults: Vec<Fault<D::NodeId>>,
     adversary: A,
+    /// The current partition, if any.
+    partition: Option<Partition>,
+    /// Messages between the two groups of the partition, as sender, recipient and content. They
+    /// are delivered when the partition heals.
+    held: Vec<(NodeId, NodeId, D::Message)>,
+}
+
+/// A split of the network into two groups of nodes that cannot reach each other.
+pub struct Partition {
+    group_a: BTreeSet<NodeId>,
+    group_b: BTreeSet<NodeId>,
+}
+
+impl Partition {
+    /// Returns whether messages between the two nodes are currently cut off.
+    fn separates(&self, id0: &NodeId, id1: &NodeId) -> bool {
+        (self.group_a.contains(id0) && self.group_b.contains(id1))
+            || (self.group_b.contains(id0) && self.group_a.contains(id1))
+    }
 }
 
 impl<A: Adversary<D>, D: DistAlgorithm<NodeId = NodeId>> TestNetwork<A, D>
@@ -442,6 +467,9 @@ where
             observer,
             adversary: adversary(adv_nodes.clone()),
             adv_nodes,
+            faults: Vec::new(),
+            partition: None,
+            held: Vec::new(),
         };
 
         // Inform the adversary about their nodes.
@@ -474,8 +502,14 @@ where
             match msg.target {
                 Target::All => {
                     for node in self.nodes.values_mut() {
-                        if node.id != sender_id {
-                            node.queue.push_back((sender_id, msg.message.clone()))
+                        if node.id == sender_id {
+                            continue;
+                        }
+                        match self.partition {
+                            Some(ref partition) if partition.separates(&sender_id, &node.id) => {
+                                self.held.push((sender_id, node.id, msg.message.clone()))
+                            }
+                            _ => node.queue.push_back((sender_id, msg.message.clone())),
                         }
                     }
                     if self.observer.id != sender_id {
@@ -489,7 +523,12 @@ where
                     if self.adv_nodes.contains_key(&to_id) {
                         self.adversary.push_message(sender_id, msg);
                     } else if let Some(node) = self.nodes.get_mut(&to_id) {
-                        node.queue.push_back((sender_id, msg.message));
+                        match self.partition {
+                            Some(ref partition) if partition.separates(&sender_id, &to_id) => {
+                                self.held.push((sender_id, to_id, msg.message))
+                            }
+                            _ => node.queue.push_back((sender_id, msg.message)),
+                        }
                     } else if self.observer.id == to_id {
                         self.observer.queue.push_back((sender_id, msg.message));
                     } else {
@@ -593,12 +632,47 @@ where
         }
     }
 
-    /// Verifies that no correct node is reported as faulty.
-    fn check_faults<I: IntoIterator<Item = Fault<D::NodeId>>>(&self, faults: I) {
+    /// Splits the network into two groups: Until `heal` is called, messages between a node in
+    /// `group_a` and one in `group_b` are held back. Nodes in neither group are unaffected.
+    #[allow(unused)] // Not used in all tests.
+    pub fn partition<I, J>(&mut self, group_a: I, group_b: J)
+    where
+        I: IntoIterator<Item = NodeId>,
+        J: IntoIterator<Item = NodeId>,
+    {
+        self.partition = Some(Partition {
+            group_a: group_a.into_iter().collect(),
+            group_b: group_b.into_iter().collect(),
+        });
+    }
+
+    /// Removes the partition, and delivers all messages that were held back, in the order in which
+    /// they were sent.
+    #[allow(unused)] // Not used in all tests.
+    pub fn heal(&mut self) {
+        self.partition = None;
+        for (sender_id, to_id, msg) in self.held.drain(..) {
+            let node = self
+                .nodes
+                .get_mut(&to_id)
+                .expect("recipient of held message");
+            node.queue.push_back((sender_id, msg));
+        }
+    }
+
+    /// Returns whether no correct node has any messages left to handle.
+    #[allow(unused)] // Not used in all tests.
+    pub fn is_idle(&self) -> bool {
+        self.nodes.values().all(TestNode::is_idle)
+    }
+
+    /// Verifies that no correct node is reported as faulty, and records the other faults.
+    fn check_faults<I: IntoIterator<Item = Fault<D::NodeId>>>(&mut self, faults: I) {
         for fault in faults {
             if self.nodes.contains_key(&fault.node_id) {
                 panic!("Unexpected fault: {:?}", fault);
             }
+            self.faults.push(fault);
         }
     }
 }
@@ -11,10 +11,11 @@ use log::info;
 use rand::{Rng, SeedableRng};
 use rand_xorshift::XorShiftRng;
 
+use hbbft::crypto::PublicKey;
 use hbbft::dynamic_honey_badger::{DynamicHoneyBadger, JoinPlan};
 use hbbft::queueing_honey_badger::{Change, ChangeState, Input, QueueingHoneyBadger};
 use hbbft::sender_queue::{Message, SenderQueue, Step};
-use hbbft::{util, NetworkInfo};
+use hbbft::{util, MemoryTransport, NetworkInfo};
 
 use crate::network::{Adversary, MessageScheduler, NodeId, SilentAdversary, TestNetwork, TestNode};
 
@@ -139,6 +140,46 @@ where
     }
     let node1 = network.nodes.get(&NodeId(1)).expect("node 1 is missing");
     network.verify_batches(&node1);
+    verify_join_epoch(&network, &pub_keys_add);
+}
+
+/// Verifies that every node reports node 0 as joined exactly once, in the same epoch, and that
+/// this is the epoch in which adding node 0 back was completed.
+fn verify_join_epoch<A>(network: &TestNetwork<A, QHB>, pub_keys_add: &BTreeMap<NodeId, PublicKey>)
+where
+    A: Adversary<QHB>,
+{
+    let join_epochs = |node: &TestNode<QHB>| -> Vec<u64> {
+        node.outputs()
+            .iter()
+            .filter(|batch| batch.joined_nodes().any(|id| *id == NodeId(0)))
+            .map(|batch| batch.epoch())
+            .collect()
+    };
+    let add_epochs: Vec<u64> = network
+        .observer
+        .outputs()
+        .iter()
+        .filter_map(|batch| match batch.change() {
+            ChangeState::Complete(Change::NodeChange(pub_keys)) if pub_keys == pub_keys_add => {
+                Some(batch.epoch())
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        add_epochs.len(),
+        1,
+        "node 0 should be added back exactly once"
+    );
+    for node in network.nodes.values().chain(iter::once(&network.observer)) {
+        assert_eq!(
+            add_epochs,
+            join_epochs(node),
+            "wrong join epoch in {:?}",
+            node.id
+        );
+    }
 }
 
 /// Restarts a stopped and removed node with a given join plan and adds the node back on the test
@@ -233,3 +274,86 @@ fn test_queueing_honey_badger_first_delivery_silent() {
     let new_adversary = |_: usize, _: usize, _| SilentAdversary::new(MessageScheduler::First);
     test_queueing_honey_badger_different_sizes(new_adversary, 30);
 }
+
+#[test]
+fn test_queueing_honey_badger_idle_network_is_quiescent() {
+    let mut rng = XorShiftRng::from_seed(rand::thread_rng().gen::<[u8; 16]>());
+    let netinfos = NetworkInfo::generate_map(0..4usize, &mut rng)
+        .expect("Failed to generate `NetworkInfo` map");
+    let mut nodes: Vec<QueueingHoneyBadger<usize, usize, Vec<usize>>> = netinfos
+        .into_iter()
+        .map(|(_, netinfo)| {
+            let dhb = DynamicHoneyBadger::builder().build(netinfo);
+            let (qhb, step) = QueueingHoneyBadger::builder(dhb)
+                .batch_size(3)
+                .build(&mut rng)
+                .expect("failed to build QueueingHoneyBadger");
+            // Without any transactions, nodes don't start the first epoch.
+            assert!(step.is_empty());
+            qhb
+        })
+        .collect();
+    assert!(nodes.iter().all(|qhb| qhb.next_epoch() == 0));
+
+    // As soon as one node has a transaction, it proposes.
+    let step = nodes[0]
+        .push_transaction(42, &mut rng)
+        .expect("failed to push transaction");
+    assert!(!step.messages.is_empty());
+}
+
+#[test]
+fn test_queueing_honey_badger_ordered_carryover() {
+    let mut rng = XorShiftRng::from_seed([3; 16]);
+    let netinfos = NetworkInfo::generate_map(0..4usize, &mut rng)
+        .expect("Failed to generate `NetworkInfo` map");
+    let mut transports = MemoryTransport::network(0..4usize);
+    let mut nodes = BTreeMap::new();
+    let mut steps = BTreeMap::new();
+    for (id, netinfo) in netinfos {
+        // Each node has twelve transactions of its own, and proposes two per epoch.
+        let txs: Vec<usize> = (0..12).map(|tx| 100 * id + tx).collect();
+        let dhb = DynamicHoneyBadger::builder().build(netinfo);
+        let (qhb, step) = QueueingHoneyBadger::builder(dhb)
+            .batch_size(8)
+            .ordered_proposals(true)
+            .build_with_transactions(txs, &mut rng)
+            .expect("failed to build QueueingHoneyBadger");
+        nodes.insert(id, qhb);
+        steps.insert(id, step);
+    }
+    let node0: &QueueingHoneyBadger<usize, usize, Vec<usize>> = &nodes[&0];
+    assert_eq!(10, node0.carryover_len());
+
+    let mut batches: BTreeMap<usize, Vec<_>> = BTreeMap::new();
+    loop {
+        for (id, qhb) in &mut nodes {
+            let step = steps.remove(id).unwrap_or_default();
+            let transport = transports.get_mut(id).expect("transport");
+            let step = hbbft::run(qhb, step, transport, &mut rng).expect("run");
+            assert!(step.fault_log.is_empty());
+            batches
+                .entry(*id)
+                .or_insert_with(Vec::new)
+                .extend(step.output);
+        }
+        if transports[&0].is_idle() {
+            break;
+        }
+    }
+
+    // All transactions were output, each node's in the order in which they were queued.
+    for (id, node_batches) in &batches {
+        assert!(node_batches.len() >= 6);
+        for proposer_id in 0..4 {
+            let expected: Vec<usize> = (0..12).map(|tx| 100 * proposer_id + tx).collect();
+            let txs: Vec<usize> = node_batches
+                .iter()
+                .flat_map(|batch| batch.iter().cloned())
+                .filter(|tx| tx / 100 == proposer_id)
+                .collect();
+            assert_eq!(expected, txs, "node {}, proposer {}", id, proposer_id);
+        }
+    }
+    assert!(nodes.values().all(|qhb| qhb.carryover_len() == 0));
+}
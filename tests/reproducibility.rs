@@ -0,0 +1,99 @@
+#![deny(unused_must_use)]
+//! Tests that a simulated network is reproducible bit for bit, given a random seed.
+//!
+//! All randomness in the library is drawn from the random number generators passed into its
+//! constructors and methods. If these are seeded, and the message delivery order is derived from
+//! the same seed, the whole simulation is deterministic.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+use hbbft::dynamic_honey_badger::{DynamicHoneyBadger, Message};
+use hbbft::queueing_honey_badger::QueueingHoneyBadger;
+use hbbft::{NetworkInfo, Target, TargetedMessage};
+
+type QHB = QueueingHoneyBadger<u64, usize, Vec<u64>>;
+
+/// Everything observable about a simulation run: the serialized messages in the order they were
+/// delivered, and each node's batches.
+#[derive(Debug, PartialEq)]
+struct Trace {
+    messages: Vec<(usize, usize, Vec<u8>)>,
+    batches: BTreeMap<usize, Vec<(u64, Vec<(usize, Vec<u64>)>)>>,
+}
+
+/// Runs a network of Queueing Honey Badger nodes until each has output `num_epochs` batches, with
+/// all keys, proposals and the message delivery order derived from `seed`.
+fn simulate(seed: [u8; 16], num_nodes: usize, num_epochs: usize) -> Trace {
+    let mut rng = XorShiftRng::from_seed(seed);
+    let netinfos = NetworkInfo::generate_map(0..num_nodes, &mut rng)
+        .expect("Failed to generate `NetworkInfo` map");
+    let mut queue: VecDeque<(usize, TargetedMessage<Message<usize>, usize>)> = VecDeque::new();
+    let mut nodes: BTreeMap<usize, QHB> = BTreeMap::new();
+    let mut trace = Trace {
+        messages: Vec::new(),
+        batches: BTreeMap::new(),
+    };
+    for (id, netinfo) in netinfos {
+        let txs: Vec<u64> = (0..10).map(|_| rng.gen()).collect();
+        let dhb = DynamicHoneyBadger::builder().build(netinfo);
+        let (qhb, step) = QueueingHoneyBadger::builder(dhb)
+            .batch_size(num_nodes * 2)
+            .build_with_transactions(txs, &mut rng)
+            .expect("failed to build QueueingHoneyBadger");
+        queue.extend(step.messages.into_iter().map(|tm| (id, tm)));
+        nodes.insert(id, qhb);
+        trace.batches.insert(id, Vec::new());
+    }
+
+    while trace
+        .batches
+        .values()
+        .any(|batches| batches.len() < num_epochs)
+    {
+        // Deliver a random pending message.
+        let idx = rng.gen_range(0, queue.len());
+        let (sender_id, tm) = queue.remove(idx).expect("no pending messages");
+        let recipients: Vec<usize> = match tm.target {
+            Target::All => nodes
+                .keys()
+                .cloned()
+                .filter(|id| *id != sender_id)
+                .collect(),
+            Target::Node(id) => vec![id],
+        };
+        for id in recipients {
+            let ser_msg = bincode::serialize(&tm.message).expect("serialize message");
+            trace.messages.push((sender_id, id, ser_msg));
+            let step = nodes
+                .get_mut(&id)
+                .expect("unknown recipient")
+                .handle_message(&sender_id, tm.message.clone(), &mut rng)
+                .expect("failed to handle message");
+            assert!(step.fault_log.is_empty());
+            queue.extend(step.messages.into_iter().map(|tm| (id, tm)));
+            let batches = trace.batches.get_mut(&id).expect("unknown node");
+            for batch in step.output {
+                let contribs = batch
+                    .contributions()
+                    .map(|(proposer, txs)| (*proposer, txs.clone()))
+                    .collect();
+                batches.push((batch.epoch(), contribs));
+            }
+        }
+    }
+    trace
+}
+
+#[test]
+fn test_reproducible_simulation() {
+    let seed: [u8; 16] = rand::thread_rng().gen();
+    let trace = simulate(seed, 4, 3);
+    assert_eq!(trace, simulate(seed, 4, 3));
+
+    let mut other_seed = seed;
+    other_seed[0] = other_seed[0].wrapping_add(1);
+    assert_ne!(trace.messages, simulate(other_seed, 4, 3).messages);
+}
@@ -0,0 +1,80 @@
+#![deny(unused_must_use)]
+//! Tests of the `Sequence` combinator, chaining a common coin into Binary Agreement.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+use hbbft::binary_agreement::BinaryAgreement;
+use hbbft::crypto::Signature;
+use hbbft::threshold_sign::ThresholdSign;
+use hbbft::{DistAlgorithm, MemoryTransport, NetworkInfo, Sequence, SequenceMessage};
+
+type CoinThenAgreement =
+    Sequence<ThresholdSign<usize>, BinaryAgreement<usize, u64>, fn(Signature) -> bool>;
+
+/// Returns a sequence that flips a coin and then agrees on the result.
+fn new_sequence(netinfo: NetworkInfo<usize>) -> CoinThenAgreement {
+    let netinfo = Arc::new(netinfo);
+    let coin = ThresholdSign::new_with_document(netinfo.clone(), "sequence").expect("document");
+    let ba = BinaryAgreement::new(netinfo, 0).expect("binary agreement");
+    Sequence::new(coin, ba, |sig| sig.parity())
+}
+
+#[test]
+fn test_sequence_coin_then_agreement() {
+    let mut rng = XorShiftRng::from_seed([7; 16]);
+    let netinfos = NetworkInfo::generate_map(0..4, &mut rng).expect("netinfos");
+    let mut transports = MemoryTransport::network(0..4);
+    let mut nodes: BTreeMap<usize, CoinThenAgreement> = netinfos
+        .into_iter()
+        .map(|(id, netinfo)| (id, new_sequence(netinfo)))
+        .collect();
+    let mut outputs = BTreeMap::new();
+
+    // Every node flips the coin. Its own share alone is not enough for a signature, so the
+    // agreement doesn't start before the node has received another one.
+    for (id, seq) in &mut nodes {
+        let initial = seq.handle_input((), &mut rng).expect("input");
+        assert!(!seq.handed_off());
+        for tm in &initial.messages {
+            match tm.message {
+                SequenceMessage::First(_) => (),
+                SequenceMessage::Second(_) => panic!("agreement started before the coin"),
+            }
+        }
+        let transport = transports.get_mut(id).expect("transport");
+        let step = hbbft::run(seq, initial, transport, &mut rng).expect("run");
+        assert!(step.fault_log.is_empty());
+        outputs
+            .entry(*id)
+            .or_insert_with(Vec::new)
+            .extend(step.output);
+    }
+    while !nodes.values().all(CoinThenAgreement::terminated) {
+        assert!(!transports[&0].is_idle(), "no messages left");
+        for (id, seq) in &mut nodes {
+            let transport = transports.get_mut(id).expect("transport");
+            let step = hbbft::run(seq, Default::default(), transport, &mut rng).expect("run");
+            assert!(step.fault_log.is_empty());
+            outputs
+                .entry(*id)
+                .or_insert_with(Vec::new)
+                .extend(step.output);
+        }
+    }
+
+    // All nodes handed the same coin to the agreement, so they decided on its value.
+    let sig = nodes[&0]
+        .first()
+        .output_signature()
+        .expect("signature")
+        .clone();
+    for (id, seq) in &nodes {
+        assert!(seq.handed_off());
+        assert_eq!(Some(&sig), seq.first().output_signature());
+        assert_eq!(vec![sig.parity()], outputs[id], "node {}", id);
+    }
+}
@@ -114,3 +114,37 @@ fn test_subset_1_node() {
     let network = new_network(1, 0, adversary);
     test_subset(network, &proposals);
 }
+
+#[test]
+fn test_subset_output_independent_of_message_order() {
+    let proposals: BTreeMap<NodeId, ProposedValue> = (0..7)
+        .map(|i| (NodeId(i), format!("Proposal {}", i).into_bytes()))
+        .collect();
+    for _ in 0..5 {
+        // With random scheduling, every node handles the messages in a different order.
+        let adversary = |_| SilentAdversary::new(MessageScheduler::Random);
+        let mut network = new_network(7, 0, adversary);
+        for (id, value) in &proposals {
+            network.input(*id, value.clone());
+        }
+        while !network.nodes.values().all(TestNode::terminated) {
+            network.step();
+        }
+        // Ordered by proposer ID, the outputs are byte-identical on all nodes.
+        let serialize_sorted = |node: &TestNode<Subset<NodeId, u8>>| {
+            let contributions: BTreeMap<NodeId, ProposedValue> = node
+                .outputs()
+                .iter()
+                .filter_map(|output| match output {
+                    SubsetOutput::Contribution(id, value) => Some((*id, value.clone())),
+                    SubsetOutput::Done => None,
+                })
+                .collect();
+            bincode::serialize(&contributions).expect("serialize contributions")
+        };
+        let expected = serialize_sorted(&network.observer);
+        for node in network.nodes.values() {
+            assert_eq!(expected, serialize_sorted(node));
+        }
+    }
+}
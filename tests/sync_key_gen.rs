@@ -1,10 +1,10 @@
 #![deny(unused_must_use)]
 //! Tests for synchronous distributed key generation.
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use hbbft::crypto::{PublicKey, SecretKey};
-use hbbft::sync_key_gen::{PartOutcome, SyncKeyGen};
+use hbbft::sync_key_gen::{AckOutcome, Error, PartFault, PartOutcome, SyncKeyGen};
 use hbbft::util;
 
 fn test_sync_key_gen_with(threshold: usize, node_num: usize) {
@@ -89,6 +89,20 @@ fn test_sync_key_gen_with(threshold: usize, node_num: usize) {
         .combine_signatures(sig_shares.iter().take(threshold + 1))
         .expect("signature shares match");
     assert!(pub_key_set.public_key().verify(&sig, msg));
+
+    // The resulting network infos report the key set's threshold and index the shares correctly.
+    for (idx, node) in nodes.into_iter().enumerate() {
+        let netinfo = node
+            .into_network_info()
+            .unwrap_or_else(|_| panic!("Failed to create `NetworkInfo` for node #{}", idx));
+        assert_eq!(threshold, netinfo.threshold());
+        assert_eq!(netinfo.num_faulty(), netinfo.threshold());
+        assert_eq!(
+            Some(&pub_key_set.public_key_share(idx)),
+            netinfo.public_key_share_by_index(idx as u64)
+        );
+        assert_eq!(None, netinfo.public_key_share_by_index(node_num as u64));
+    }
 }
 
 #[test]
@@ -101,3 +115,109 @@ fn test_sync_key_gen() {
         test_sync_key_gen_with(threshold, node_num);
     }
 }
+
+#[test]
+fn test_sync_key_gen_dealer_subset() {
+    let mut rng = rand::thread_rng();
+    let (threshold, node_num) = (2, 7);
+    let sec_keys: Vec<SecretKey> = (0..node_num).map(|_| SecretKey::random()).collect();
+    let pub_keys: BTreeMap<usize, PublicKey> = sec_keys
+        .iter()
+        .map(SecretKey::public_key)
+        .enumerate()
+        .collect();
+    let dealers: BTreeSet<usize> = vec![0, 2, 4, 6].into_iter().collect();
+
+    // The dealers must be known nodes, and more than `threshold`.
+    let new_with_dealers = |dealers: BTreeSet<usize>| {
+        SyncKeyGen::new_with_dealers(
+            0,
+            sec_keys[0].clone(),
+            pub_keys.clone(),
+            dealers,
+            threshold,
+            &mut rand::thread_rng(),
+        )
+        .map(|_| ())
+    };
+    let too_few = vec![0, 2].into_iter().collect();
+    assert_eq!(Err(Error::InvalidDealers), new_with_dealers(too_few));
+    let unknown = vec![0, 2, 4, 9].into_iter().collect();
+    assert_eq!(Err(Error::InvalidDealers), new_with_dealers(unknown));
+
+    // Only the dealers create a `Part`.
+    let mut nodes = Vec::new();
+    let mut parts = BTreeMap::new();
+    for (id, sk) in sec_keys.into_iter().enumerate() {
+        let (node, part) = SyncKeyGen::new_with_dealers(
+            id,
+            sk,
+            pub_keys.clone(),
+            dealers.clone(),
+            threshold,
+            &mut rng,
+        )
+        .expect("new instance");
+        assert_eq!(dealers.contains(&id), part.is_some());
+        assert_eq!(dealers, *node.dealers());
+        nodes.push(node);
+        parts.extend(part.map(|part| (id, part)));
+    }
+
+    // A `Part` from a node that is not a dealer is rejected.
+    match nodes[0]
+        .handle_part(&1, parts[&0].clone(), &mut rng)
+        .expect("handle part")
+    {
+        PartOutcome::Invalid(PartFault::NotDealer) => (),
+        _ => panic!("accepted a part from a node that is not a dealer"),
+    }
+
+    // All nodes, including the ones that are not dealers, acknowledge the dealers' parts.
+    let mut acks = Vec::new();
+    for (dealer_id, part) in &parts {
+        for (node_id, node) in nodes.iter_mut().enumerate() {
+            match node
+                .handle_part(dealer_id, part.clone(), &mut rng)
+                .expect("handle part")
+            {
+                PartOutcome::Valid(Some(ack)) => acks.push((node_id, ack)),
+                _ => panic!("missing ack message"),
+            }
+        }
+    }
+    for (sender_id, ack) in acks {
+        for node in &mut nodes {
+            match node
+                .handle_ack(&sender_id, ack.clone())
+                .expect("handle ack")
+            {
+                AckOutcome::Valid => (),
+                AckOutcome::Invalid(fault) => panic!("invalid ack: {:?}", fault),
+            }
+        }
+    }
+
+    // Every node receives a key share of the same key set.
+    let msg = "Dealt by a few, held by all";
+    let pub_key_set = nodes[0].generate().expect("generate keys").0;
+    let sig_shares: BTreeMap<_, _> = nodes
+        .iter()
+        .enumerate()
+        .map(|(idx, node)| {
+            assert!(node.is_ready());
+            assert_eq!(dealers.len(), node.count_complete());
+            let (pks, opt_sk) = node.generate().expect("generate keys");
+            assert_eq!(pub_key_set, pks);
+            let sig = opt_sk.expect("secret key share").sign(msg);
+            assert!(pks.public_key_share(idx).verify(&sig, msg));
+            (idx, sig)
+        })
+        .collect();
+    // Shares of nodes that are not dealers suffice to sign.
+    let non_dealer_shares = sig_shares.iter().filter(|(idx, _)| !dealers.contains(idx));
+    let sig = pub_key_set
+        .combine_signatures(non_dealer_shares)
+        .expect("signature shares match");
+    assert!(pub_key_set.public_key().verify(&sig, msg));
+}
@@ -0,0 +1,140 @@
+#![deny(unused_must_use)]
+//! Threshold decryption tests
+
+mod network;
+
+use std::cell::RefCell;
+use std::sync::Arc;
+
+use log::info;
+use rand::Rng;
+
+use hbbft::threshold_decrypt::{Message, ThresholdDecrypt};
+use hbbft::{util, DistAlgorithm, FaultKind, NetworkInfo};
+
+use crate::network::{Adversary, MessageScheduler, NodeId, SilentAdversary, TestNetwork, TestNode};
+
+/// Tests a network of threshold decryption instances, and checks that all nodes output the
+/// `expected` plaintext.
+fn test_threshold_decrypt<A>(mut network: TestNetwork<A, ThresholdDecrypt<NodeId>>, expected: &[u8])
+where
+    A: Adversary<ThresholdDecrypt<NodeId>>,
+{
+    let mut rng = rand::thread_rng();
+    network.input_all(());
+    network.observer.handle_input((), &mut rng);
+
+    // Handle messages until all good nodes have terminated.
+    while !network.nodes.values().all(TestNode::terminated) {
+        network.step();
+    }
+    for node in network.nodes.values().chain(Some(&network.observer)) {
+        assert_eq!(node.outputs(), &[expected.to_vec()]);
+    }
+}
+
+fn test_threshold_decrypt_different_sizes<A, F>(new_adversary: F)
+where
+    A: Adversary<ThresholdDecrypt<NodeId>>,
+    F: Fn(usize, usize) -> A,
+{
+    // This returns an error in all but the first test.
+    let _ = env_logger::try_init();
+
+    let mut rng = rand::thread_rng();
+    let sizes = (1..5).chain(Some(rng.gen_range(6, 20)));
+    for size in sizes {
+        let num_faulty_nodes = util::max_faulty(size);
+        let num_good_nodes = size - num_faulty_nodes;
+        info!(
+            "Network size: {} good nodes, {} faulty nodes",
+            num_good_nodes, num_faulty_nodes
+        );
+        let plaintext: Vec<u8> = (0..rng.gen_range(0, 100)).map(|_| rng.gen()).collect();
+        let adversary = |_| new_adversary(num_good_nodes, num_faulty_nodes);
+        // The keys are generated by the test network, so remember the public key to encrypt with.
+        let pub_key = RefCell::new(None);
+        let new_td = |netinfo: Arc<NetworkInfo<NodeId>>| {
+            *pub_key.borrow_mut() = Some(netinfo.public_key_set().public_key());
+            ThresholdDecrypt::new(netinfo)
+        };
+        let mut network = TestNetwork::new(num_good_nodes, num_faulty_nodes, adversary, new_td);
+        let ct = pub_key.into_inner().expect("no nodes").encrypt(&plaintext);
+        for node in network
+            .nodes
+            .values_mut()
+            .chain(Some(&mut network.observer))
+        {
+            let td = node.instance_mut();
+            td.set_ciphertext(ct.clone()).expect("invalid ciphertext");
+        }
+        test_threshold_decrypt(network, &plaintext);
+    }
+}
+
+#[test]
+fn test_threshold_decrypt_random_silent() {
+    let new_adversary = |_: usize, _: usize| SilentAdversary::new(MessageScheduler::Random);
+    test_threshold_decrypt_different_sizes(new_adversary);
+}
+
+#[test]
+fn test_threshold_decrypt_first_silent() {
+    let new_adversary = |_: usize, _: usize| SilentAdversary::new(MessageScheduler::First);
+    test_threshold_decrypt_different_sizes(new_adversary);
+}
+
+#[test]
+fn test_threshold_decrypt_bad_share() {
+    let mut rng = rand::thread_rng();
+    let netinfos = NetworkInfo::generate_map(0..4, &mut rng).expect("Failed to generate netinfos");
+    let pk = netinfos[&0].public_key_set().public_key();
+    let ct = pk.encrypt(b"secret");
+    let other_ct = pk.encrypt(b"another secret");
+
+    // Node 1 sends a share for the wrong ciphertext.
+    let sks1 = netinfos[&1].secret_key_share().expect("secret key share");
+    let bad_share = Message(sks1.decrypt_share_no_verify(&other_ct));
+    let good_share = Message(sks1.decrypt_share_no_verify(&ct));
+
+    let netinfo = Arc::new(netinfos[&0].clone());
+    let mut td = ThresholdDecrypt::new_with_ciphertext(netinfo.clone(), ct.clone())
+        .expect("invalid ciphertext");
+    let step = td.handle_message(&1, bad_share.clone()).expect("bad share");
+    let faults: Vec<_> = step
+        .fault_log
+        .0
+        .iter()
+        .map(|f| (f.node_id, f.kind))
+        .collect();
+    assert_eq!(
+        vec![(1, FaultKind::UnverifiedDecryptionShareSender)],
+        faults
+    );
+    assert!(step.output.is_empty());
+
+    // The bad share was dropped, so with a valid share from node 1 we can decrypt.
+    let step = td.handle_input((), &mut rng).expect("input");
+    assert!(step.output.is_empty());
+    let step = td.handle_message(&1, good_share).expect("good share");
+    assert_eq!(step.output, vec![b"secret".to_vec()]);
+
+    // If the ciphertext is set only later, the bad share is detected then.
+    let mut td = ThresholdDecrypt::new(netinfo);
+    assert!(td
+        .handle_message(&1, bad_share)
+        .expect("bad share")
+        .is_empty());
+    td.set_ciphertext(ct).expect("invalid ciphertext");
+    let step = td.start_decryption().expect("start decryption");
+    let faults: Vec<_> = step
+        .fault_log
+        .0
+        .iter()
+        .map(|f| (f.node_id, f.kind))
+        .collect();
+    assert_eq!(
+        vec![(1, FaultKind::UnverifiedDecryptionShareSender)],
+        faults
+    );
+}
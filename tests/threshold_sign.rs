@@ -1,15 +1,25 @@
 #![deny(unused_must_use)]
-//! Threshold signing tests
+//! Threshold signing tests, in simulated networks of nodes.
 
 mod network;
 
+use std::collections::BTreeMap;
 use std::iter::once;
+use std::sync::Arc;
 
 use log::info;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
 
-use hbbft::{crypto::Signature, threshold_sign::ThresholdSign, util};
+use hbbft::event::FaultEvent;
+use hbbft::recorder::{self, Recorder, TraceEvent};
+use hbbft::threshold_sign::{self, ThresholdSign};
+use hbbft::{
+    crypto::{SecretKey, Signature},
+    testing, util, DistAlgorithm, Fault, FaultKind, MemoryTransport, NetworkInfo, Target,
+};
 
+use crate::network::adversaries::{LateShareAdversary, WrongDocumentAdversary};
 use crate::network::{Adversary, MessageScheduler, NodeId, SilentAdversary, TestNetwork, TestNode};
 
 /// Tests a network of threshold signing instances with an optional expected value. Outputs the
@@ -125,3 +135,261 @@ fn test_threshold_sign_first_silent_50_samples() {
     let new_adversary = |_: usize, _: usize| SilentAdversary::new(MessageScheduler::First);
     test_threshold_sign_different_sizes(new_adversary, 50);
 }
+
+#[test]
+fn test_threshold_sign_without_faulty_nodes() {
+    // With fewer than four nodes no faults are tolerated, so a single share suffices.
+    for size in 1..4 {
+        let (_, netinfos) = testing::setup(size, 0);
+        let mut sigs = Vec::new();
+        for netinfo in netinfos.into_iter().map(Arc::new) {
+            assert_eq!(0, netinfo.num_faulty());
+            assert_eq!(0, netinfo.threshold());
+            let mut ts = ThresholdSign::new_with_document(netinfo, "degenerate")
+                .expect("Failed to set the document");
+            let step = ts.sign().expect("Failed to sign");
+            assert!(ts.terminated());
+            assert_eq!(1, step.output.len());
+            sigs.extend(step.output);
+        }
+        // Each node computed the same signature without receiving any shares from others.
+        assert!(sigs.windows(2).all(|w| w[0] == w[1]));
+    }
+}
+
+#[test]
+fn test_threshold_sign_elect_leader() {
+    let candidates: Vec<NodeId> = (0..5).map(NodeId).collect();
+    let mut rng = XorShiftRng::from_seed([3; 16]);
+    let sk: SecretKey = rng.gen();
+    let sig = sk.sign("no candidates");
+    assert_eq!(None, threshold_sign::elect_leader::<NodeId>(&sig, &[]));
+
+    // All nodes elect the same leader from the same signing round.
+    let new_adversary = |_| SilentAdversary::new(MessageScheduler::Random);
+    let new_ts = |netinfo: _| ThresholdSign::new_with_document(netinfo, "leader").expect("doc");
+    let mut network = TestNetwork::new(7, 0, new_adversary, new_ts);
+    network.input_all(());
+    while !network.nodes.values().all(TestNode::terminated) {
+        network.step();
+    }
+    let leaders: Vec<NodeId> = network
+        .nodes
+        .values()
+        .map(|node| *threshold_sign::elect_leader(&node.outputs()[0], &candidates).expect("leader"))
+        .collect();
+    assert_eq!(7, leaders.len());
+    assert!(leaders.windows(2).all(|w| w[0] == w[1]));
+
+    // Over many rounds, each candidate is elected about equally often.
+    let num_rounds = 2000;
+    let mut counts = vec![0; candidates.len()];
+    for i in 0..num_rounds {
+        let sig = sk.sign(format!("round {}", i));
+        let leader = threshold_sign::elect_leader(&sig, &candidates).expect("leader");
+        counts[leader.0] += 1;
+    }
+    let expected = num_rounds / candidates.len();
+    for count in counts {
+        assert!(
+            count > expected * 85 / 100 && count < expected * 115 / 100,
+            "{}",
+            count
+        );
+    }
+}
+
+/// Runs a network of threshold signing instances for the document `doc`, with the adversary
+/// controlling `num_adv` nodes, until all correct nodes have terminated. Returns the network.
+fn run_threshold_sign_with<A, F>(
+    num_good: usize,
+    num_adv: usize,
+    doc: &str,
+    new_adversary: F,
+) -> TestNetwork<A, ThresholdSign<NodeId>>
+where
+    A: Adversary<ThresholdSign<NodeId>>,
+    F: Fn(BTreeMap<NodeId, Arc<NetworkInfo<NodeId>>>) -> A,
+{
+    let mut rng = rand::thread_rng();
+    let new_coin = |netinfo| ThresholdSign::new_with_document(netinfo, doc).expect("document");
+    let mut network = TestNetwork::new(num_good, num_adv, new_adversary, new_coin);
+    network.input_all(());
+    network.observer.handle_input((), &mut rng);
+    while !network.nodes.values().all(TestNode::terminated) {
+        network.step();
+    }
+    let sig = network.observer.outputs()[0].clone();
+    assert!(network
+        .nodes
+        .values()
+        .all(|node| once(&sig).eq(node.outputs())));
+    network
+}
+
+#[test]
+fn test_threshold_sign_wrong_document_adversary() {
+    let _ = env_logger::try_init();
+
+    for &(num_good, num_adv) in &[(3, 1), (5, 2), (7, 3)] {
+        let new_adversary = |adv_nodes: BTreeMap<_, _>| {
+            WrongDocumentAdversary::new(MessageScheduler::Random, &adv_nodes, "Stale nonce")
+        };
+        let network = run_threshold_sign_with(num_good, num_adv, "Fresh nonce", new_adversary);
+        // Every correct node and the observer rejected every adversarial node's share.
+        assert_eq!((num_good + 1) * num_adv, network.faults.len());
+        for fault in &network.faults {
+            assert!(network.adv_nodes.contains_key(&fault.node_id));
+            assert_eq!(FaultKind::UnverifiedSignatureShareSender, fault.kind);
+        }
+    }
+}
+
+#[test]
+fn test_threshold_sign_late_share_adversary() {
+    let _ = env_logger::try_init();
+
+    for &(num_good, num_adv) in &[(3, 1), (5, 2), (7, 3)] {
+        let new_adversary = |adv_nodes: BTreeMap<_, _>| {
+            LateShareAdversary::new(MessageScheduler::Random, &adv_nodes, "Nonce")
+        };
+        // The late shares are correct, so they are not reported.
+        let network = run_threshold_sign_with(num_good, num_adv, "Nonce", new_adversary);
+        assert!(network.faults.is_empty());
+    }
+}
+
+#[test]
+fn test_threshold_sign_step_event() {
+    let (_, netinfos) = testing::setup(1, 0);
+    let netinfo = Arc::new(netinfos[0].clone());
+    let mut ts = ThresholdSign::new_with_document(netinfo, "event").expect("document");
+    // With a single node, our own share is enough: The step has one output and one message.
+    let step = ts.sign().expect("Failed to sign");
+    let event = step.to_event("ThresholdSign", &0);
+    assert_eq!("ThresholdSign", event.algorithm);
+    assert_eq!("0", event.node_id);
+    assert_eq!(vec![format!("{:?}", step.output[0])], event.outputs);
+    assert_eq!(1, event.messages.len());
+    assert_eq!(None, event.messages[0].target);
+    assert_eq!(
+        format!("{:?}", step.messages[0].message),
+        event.messages[0].message
+    );
+    assert!(event.faults.is_empty());
+
+    // Targets and faults are included with the nodes' IDs.
+    let fault: threshold_sign::Step<NodeId> =
+        Fault::new(NodeId(3), FaultKind::UnverifiedSignatureShareSender).into();
+    let event = fault.to_event("ThresholdSign", &NodeId(1));
+    assert_eq!("NodeId(1)", event.node_id);
+    assert_eq!(
+        vec![FaultEvent {
+            node_id: "NodeId(3)".to_string(),
+            kind: "UnverifiedSignatureShareSender".to_string(),
+        }],
+        event.faults
+    );
+    let msg = step.messages[0].message.clone();
+    let mut step = threshold_sign::Step::<NodeId>::default();
+    step.messages.push(Target::Node(NodeId(2)).message(msg));
+    let event = step.to_event("ThresholdSign", &NodeId(1));
+    assert_eq!(Some("NodeId(2)".to_string()), event.messages[0].target);
+
+    // The event can be serialized.
+    let ser = bincode::serialize(&event).expect("serialize event");
+    assert_eq!(
+        event,
+        bincode::deserialize(&ser).expect("deserialize event")
+    );
+}
+
+#[test]
+fn test_threshold_sign_memory_transport() {
+    let mut rng = XorShiftRng::from_seed([5; 16]);
+    let (_, netinfos) = testing::setup(4, 1);
+    let mut transports = MemoryTransport::network(0..4);
+    let mut nodes: BTreeMap<usize, ThresholdSign<usize>> = netinfos
+        .into_iter()
+        .enumerate()
+        .map(|(id, netinfo)| {
+            let ts =
+                ThresholdSign::new_with_document(Arc::new(netinfo), "transport").expect("document");
+            (id, ts)
+        })
+        .collect();
+    let mut outputs = BTreeMap::new();
+
+    // Every node publishes its share and handles the messages it has received so far.
+    for (id, ts) in &mut nodes {
+        let initial = ts.sign().expect("sign");
+        let transport = transports.get_mut(id).expect("transport");
+        let step = hbbft::run(ts, initial, transport, &mut rng).expect("run");
+        outputs
+            .entry(*id)
+            .or_insert_with(Vec::new)
+            .extend(step.output);
+    }
+    // Keep pumping the nodes until all of them have output a signature.
+    while !nodes.values().all(ThresholdSign::terminated) {
+        assert!(!transports[&0].is_idle(), "no messages left");
+        for (id, ts) in &mut nodes {
+            let transport = transports.get_mut(id).expect("transport");
+            let step = hbbft::run(ts, Default::default(), transport, &mut rng).expect("run");
+            outputs
+                .entry(*id)
+                .or_insert_with(Vec::new)
+                .extend(step.output);
+        }
+    }
+
+    let sig = outputs[&0][0].clone();
+    assert!(outputs.values().all(|sigs| *sigs == vec![sig.clone()]));
+}
+
+#[test]
+fn test_threshold_sign_record_replay() {
+    let mut rng = XorShiftRng::from_seed([6; 16]);
+    let (_, netinfos) = testing::setup(4, 1);
+    let new_ts = |id: usize, doc: &str| {
+        let netinfo = Arc::new(netinfos[id].clone());
+        ThresholdSign::new_with_document(netinfo, doc).expect("document")
+    };
+    let mut recorder = Recorder::new(new_ts(0, "record"), "ThresholdSign");
+    let mut others: BTreeMap<usize, _> = (1..4).map(|id| (id, new_ts(id, "record"))).collect();
+
+    // Node 0 signs and receives the shares of the others, which is recorded.
+    recorder
+        .handle_input((), &mut rng)
+        .expect("input")
+        .messages
+        .clear();
+    for (id, ts) in &mut others {
+        for tm in ts.sign().expect("sign").messages {
+            let _ = recorder.handle_message(id, tm.message, &mut rng);
+        }
+    }
+    // Node 0 outputs once it has one share besides its own, and ignores the other two.
+    assert!(recorder.terminated());
+    let trace = recorder.trace().clone();
+    assert_eq!(4, trace.entries.len());
+    assert_eq!(TraceEvent::Input(()), trace.entries[0].event);
+    let outputs: Vec<usize> = trace
+        .entries
+        .iter()
+        .map(|entry| entry.step.as_ref().expect("step").outputs.len())
+        .collect();
+    assert_eq!(vec![0, 1, 0, 0], outputs);
+
+    // The trace survives serialization, and a fresh instance reproduces it.
+    let bytes = bincode::serialize(&trace).expect("serialize");
+    let trace: recorder::DaTrace<ThresholdSign<usize>> =
+        bincode::deserialize(&bytes).expect("deserialize");
+    recorder::replay(&trace, &mut new_ts(0, "record"), &mut rng).expect("replay");
+
+    // An instance signing a different document sends a different share, so the steps differ.
+    assert_eq!(
+        Err(recorder::ReplayError::Mismatch(0)),
+        recorder::replay(&trace, &mut new_ts(0, "other"), &mut rng)
+    );
+}
@@ -0,0 +1,304 @@
+#![deny(unused_must_use)]
+//! Tests of the metrics, diagnostics and events `ThresholdSign` reports, and of its
+//! errors.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use failure::Fail;
+
+use hbbft::strict::{self, Strict};
+use hbbft::threshold_sign::{self, Message, ThresholdSign};
+use hbbft::{
+    testing, AlgoMetrics, Diagnose, DiagnosticTree, DistAlgorithm, Fault, FaultKind, InstanceId,
+    Instrumented, NetworkInfo, Target,
+};
+
+#[test]
+fn test_threshold_sign_strict_mode() {
+    let mut rng = rand::thread_rng();
+    let (_, netinfos) = testing::setup(4, 1);
+    let doc = "Strict";
+    let new_strict_ts = |id: usize| {
+        let netinfo = Arc::new(netinfos[id].clone());
+        Strict::new(ThresholdSign::new_with_document(netinfo, doc).expect("document"))
+    };
+    let sks1 = netinfos[1].secret_key_share().expect("secret key share");
+
+    // A correct share is handled normally.
+    let mut ts = new_strict_ts(0);
+    let step = ts
+        .handle_message(&1, Message::new(0, sks1.sign(doc)), &mut rng)
+        .expect("valid share");
+    assert!(step.fault_log.is_empty());
+
+    // A share for a different document is reported as an error instead of a fault.
+    let mut ts = new_strict_ts(0);
+    match ts.handle_message(&1, Message::new(0, sks1.sign("Lenient")), &mut rng) {
+        Err(strict::Error::Faults(fault_log)) => {
+            let fault = Fault::new(1, FaultKind::UnverifiedSignatureShareSender);
+            assert_eq!(vec![fault], fault_log.0);
+        }
+        result => panic!("expected a fault error, got {:?}", result),
+    }
+
+    // Errors of the wrapped algorithm are forwarded.
+    match ts.handle_message(&5, Message::new(0, sks1.sign(doc)), &mut rng) {
+        Err(strict::Error::Algorithm(threshold_sign::Error::UnknownSender)) => (),
+        result => panic!("expected an unknown sender error, got {:?}", result),
+    }
+}
+
+#[test]
+fn test_threshold_sign_error_interop() {
+    fn sign_without_document() -> Result<(), failure::Error> {
+        let mut rng = rand::thread_rng();
+        let netinfos = NetworkInfo::generate_map(0..4, &mut rng)?;
+        let mut ts = ThresholdSign::new(Arc::new(netinfos[&0].clone()));
+        let _ = ts.sign()?;
+        Ok(())
+    }
+
+    // `?` converts the algorithm's error without any `map_err`.
+    let err = sign_without_document().expect_err("signed without document");
+    let ts_err = err
+        .downcast_ref::<threshold_sign::Error>()
+        .expect("threshold sign error");
+    assert_eq!(threshold_sign::Error::DocumentHashIsNone, *ts_err);
+
+    // The compatibility wrapper is a `std::error::Error`, and keeps the message.
+    let boxed: Box<dyn std::error::Error + Send + Sync> =
+        Box::new(threshold_sign::Error::DocumentHashIsNone.compat());
+    assert_eq!(
+        threshold_sign::Error::DocumentHashIsNone.to_string(),
+        boxed.to_string()
+    );
+}
+
+#[test]
+fn test_threshold_sign_estimated_messages() {
+    let (_, netinfos) = testing::setup(7, 2);
+    let mut ts = ThresholdSign::new_with_document(Arc::new(netinfos[0].clone()), "cost")
+        .expect("Failed to set the document");
+    let step = ts.sign().expect("Failed to sign");
+    let share_size = bincode::serialize(&step.messages[0].message)
+        .expect("serialize")
+        .len();
+    // Each validator multicasts its share to all others.
+    for &num_nodes in &[1, 4, 7, 100] {
+        let estimate = threshold_sign::estimated_messages(num_nodes);
+        assert_eq!(num_nodes * (num_nodes - 1), estimate.messages);
+        assert_eq!(estimate.messages * share_size, estimate.bytes);
+        assert_eq!(num_nodes - 1, estimate.messages_per_node(num_nodes));
+    }
+}
+
+#[test]
+fn test_threshold_sign_metrics() {
+    let (_, netinfos) = testing::setup(4, 1);
+    let doc = "metrics";
+    let mut ts =
+        ThresholdSign::new_with_document(Arc::new(netinfos[0].clone()), doc).expect("document");
+    assert_eq!(AlgoMetrics::default(), ts.metrics());
+
+    // Our own share is sent, but not counted as received.
+    let step = ts.sign().expect("sign");
+    assert_eq!(1, step.messages.len());
+    let mut expected = AlgoMetrics {
+        messages_sent: 1,
+        ..AlgoMetrics::default()
+    };
+    assert_eq!(expected, ts.metrics());
+
+    // An invalid share is received and reported.
+    let wrong_share = netinfos[1].secret_key_share().expect("share").sign("other");
+    let step = ts
+        .handle_message(&1, Message::new(0, wrong_share))
+        .expect("share");
+    assert_eq!(1, step.fault_log.0.len());
+    expected.messages_received = 1;
+    expected.faults = 1;
+    assert_eq!(expected, ts.metrics());
+
+    // A valid share completes the signature, and later shares are still counted.
+    for (id, netinfo) in netinfos.iter().enumerate().skip(2).take(2) {
+        let share = netinfo.secret_key_share().expect("share").sign(doc);
+        let step = ts
+            .handle_message(&id, Message::new(0, share))
+            .expect("share");
+        assert_eq!(id == 2, !step.output.is_empty());
+    }
+    expected.messages_received = 3;
+    expected.terminated = true;
+    assert_eq!(expected, ts.metrics());
+}
+
+#[test]
+fn test_threshold_sign_diagnose() {
+    let (_, netinfos) = testing::setup(7, 2);
+    let doc = "Are we there yet?";
+    let share =
+        |id: usize| Message::new(0, netinfos[id].secret_key_share().expect("share").sign(doc));
+    let mut ts = ThresholdSign::new(Arc::new(netinfos[0].clone()));
+    let diagnose = |ts: &ThresholdSign<usize>| ts.diagnose().to_string();
+    assert_eq!(
+        "ThresholdSign: 0/3 shares, waiting for document\n",
+        diagnose(&ts)
+    );
+    ts.set_document(doc).expect("document");
+    let step = ts.handle_message(&1, share(1)).expect("share");
+    assert!(step.is_empty());
+    assert_eq!(
+        "ThresholdSign: 1/3 shares, waiting for input\n",
+        diagnose(&ts)
+    );
+    let step = ts.sign().expect("sign");
+    assert!(step.output.is_empty());
+    assert_eq!(
+        "ThresholdSign: 2/3 shares, waiting for shares\n",
+        diagnose(&ts)
+    );
+    let step = ts.handle_message(&2, share(2)).expect("share");
+    assert_eq!(1, step.output.len());
+    assert_eq!("ThresholdSign: 3/3 shares, terminated\n", diagnose(&ts));
+
+    // As a child in a composite diagnostic, the coin is indented.
+    let tree = DiagnosticTree::new("BinaryAgreement epoch 1").with_child(ts.diagnose());
+    assert_eq!(
+        "BinaryAgreement epoch 1\n  ThresholdSign: 3/3 shares, terminated\n",
+        tree.to_string()
+    );
+}
+
+#[test]
+fn test_threshold_sign_coin_sink() {
+    let (_, netinfos) = testing::setup(4, 1);
+    let doc = "Analytics";
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let sink_results = results.clone();
+    // A fake clock that only advances when the test sets it.
+    let millis = Arc::new(AtomicUsize::new(0));
+    let clock_millis = millis.clone();
+    let mut ts = ThresholdSign::builder(Arc::new(netinfos[0].clone()))
+        .document(doc)
+        .coin_sink(Arc::new(move |result| {
+            sink_results.lock().expect("lock").push(result)
+        }))
+        .clock(Arc::new(move || {
+            Duration::from_millis(clock_millis.load(Ordering::SeqCst) as u64)
+        }))
+        .build();
+    let step = ts.sign().expect("sign");
+    assert!(step.output.is_empty());
+    assert!(results.lock().expect("lock").is_empty());
+    let mut outputs = Vec::new();
+    for (id, netinfo) in netinfos.iter().enumerate().skip(1).take(3) {
+        millis.fetch_add(10, Ordering::SeqCst);
+        let share = Message::new(0, netinfo.secret_key_share().expect("share").sign(doc));
+        let step = ts.handle_message(&id, share).expect("share");
+        outputs.extend(step.output);
+    }
+    assert_eq!(1, outputs.len());
+
+    // The sink was called exactly once, when the second share arrived.
+    let results = results.lock().expect("lock");
+    assert_eq!(1, results.len());
+    assert_eq!(outputs[0].parity(), results[0].parity);
+    assert_eq!(2, results[0].shares_used);
+    assert_eq!(Some(Duration::from_millis(10)), results[0].elapsed);
+}
+
+#[test]
+fn test_threshold_sign_eta_to_output() {
+    // With ten nodes, _f = 3_, so four shares are needed.
+    let (_, netinfos) = testing::setup(10, 3);
+    let doc = "When?";
+    let share =
+        |id: usize| Message::new(0, netinfos[id].secret_key_share().expect("share").sign(doc));
+    let millis = Arc::new(AtomicUsize::new(0));
+    let clock_millis = millis.clone();
+    let set_time = |ms: usize| millis.store(ms, Ordering::SeqCst);
+    let mut ts = ThresholdSign::builder(Arc::new(netinfos[0].clone()))
+        .document(doc)
+        .clock(Arc::new(move || {
+            Duration::from_millis(clock_millis.load(Ordering::SeqCst) as u64)
+        }))
+        .build();
+    assert_eq!(None, ts.eta_to_output());
+
+    // A share arrives every 100 ms, so the fourth is expected at 400 ms.
+    let in_tolerance = |eta: Option<Duration>, expected_ms: u64| {
+        let eta = eta.expect("eta");
+        let expected = Duration::from_millis(expected_ms);
+        let diff = if eta > expected {
+            eta - expected
+        } else {
+            expected - eta
+        };
+        diff <= Duration::from_millis(1)
+    };
+    for id in 1..4 {
+        set_time(100 * id);
+        let step = ts.handle_message(&id, share(id)).expect("share");
+        assert!(step.output.is_empty());
+        assert!(in_tolerance(ts.eta_to_output(), 100 * (4 - id as u64)));
+    }
+    // A duplicate doesn't count as a new arrival.
+    set_time(350);
+    let step = ts.handle_message(&3, share(3)).expect("share");
+    assert!(step.is_empty());
+    assert!(in_tolerance(ts.eta_to_output(), 50));
+    // If the share is overdue, the estimate is zero.
+    set_time(500);
+    assert_eq!(Some(Duration::default()), ts.eta_to_output());
+
+    let step = ts.handle_message(&4, share(4)).expect("share");
+    assert_eq!(1, step.output.len());
+    assert_eq!(Some(Duration::default()), ts.eta_to_output());
+}
+
+#[test]
+fn test_threshold_sign_instance_id() {
+    let (_, netinfos) = testing::setup(4, 1);
+    let nonces = ["Coin A", "Coin B"];
+    let new_coin = |id: usize, nonce: &str| {
+        let netinfo = Arc::new(netinfos[id].clone());
+        ThresholdSign::new_with_document(netinfo, nonce).expect("document")
+    };
+    let unset = ThresholdSign::new(Arc::new(netinfos[0].clone()));
+    assert!(unset.instance_id().is_empty());
+
+    let mut senders: Vec<_> = nonces.iter().map(|nonce| new_coin(0, nonce)).collect();
+    assert_eq!(b"Coin A", senders[0].instance_id());
+    assert_ne!(senders[0].instance_id(), senders[1].instance_id());
+    let mut receivers: BTreeMap<Vec<u8>, ThresholdSign<usize>> = nonces
+        .iter()
+        .map(|nonce| new_coin(1, nonce))
+        .map(|ts| (ts.instance_id().to_vec(), ts))
+        .collect();
+
+    // Every tagged message carries its sender's id, and is dispatched to the matching instance,
+    // which accepts the share.
+    for ts in &mut senders {
+        let step = ts.sign().expect("sign");
+        assert_eq!(1, step.messages.len());
+        let instance_id = ts.instance_id().to_vec();
+        for tm in step.messages {
+            let tagged = tm.with_instance_id(&instance_id);
+            assert_eq!(Target::All, tagged.target);
+            assert_eq!(instance_id, tagged.message.instance_id);
+            let receiver = receivers
+                .get_mut(&tagged.message.instance_id)
+                .expect("instance");
+            let step = receiver
+                .handle_message(&0, tagged.message.message)
+                .expect("share");
+            assert!(step.fault_log.is_empty());
+        }
+    }
+    for ts in receivers.values() {
+        assert_eq!(vec![1, 2, 3], ts.missing_contributors());
+    }
+}
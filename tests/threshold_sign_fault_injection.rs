@@ -0,0 +1,165 @@
+#![cfg(feature = "fault-injection")]
+#![deny(unused_must_use)]
+//! Tests of the `ThresholdSign` error paths, using the failures that can be forced with the
+//! `fault-injection` feature.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+use hbbft::threshold_sign::{round_document, Error, InjectedFaults, Message, ThresholdSign};
+use hbbft::{DistAlgorithm, Fault, FaultKind, NetworkInfo};
+
+const DOC: &str = "Injected";
+
+/// Returns node 0's instance, and the messages with the valid shares of nodes 1 to 3.
+fn setup() -> (ThresholdSign<usize>, Vec<Message>) {
+    let mut rng = XorShiftRng::from_seed([14; 16]);
+    let netinfos = NetworkInfo::generate_map(0..4, &mut rng).expect("netinfos");
+    let shares = (1..4)
+        .map(|id| {
+            Message::new(
+                0,
+                netinfos[&id].secret_key_share().expect("share").sign(DOC),
+            )
+        })
+        .collect();
+    let netinfo = Arc::new(netinfos[&0].clone());
+    let ts = ThresholdSign::new_with_document(netinfo, DOC).expect("document");
+    (ts, shares)
+}
+
+#[test]
+fn test_threshold_sign_injected_combine_failure() {
+    let (mut ts, shares) = setup();
+    ts.inject_faults(InjectedFaults {
+        fail_combine: true,
+        invalid_shares: BTreeSet::new(),
+    });
+    let step = ts.handle_message(&1, shares[0].clone()).expect("share");
+    assert!(step.is_empty());
+    // With two shares the instance tries to combine them, and aborts.
+    assert_eq!(
+        Err(Error::VerificationFailed),
+        ts.handle_message(&2, shares[1].clone()).map(|_| ())
+    );
+    assert!(!ts.terminated());
+    assert_eq!(None, ts.output_signature());
+}
+
+#[test]
+fn test_threshold_sign_diagnostic_tampered_shares() {
+    let (mut ts, shares) = setup();
+    // Without enough shares, there is nothing to combine.
+    assert!(ts.try_combine_diagnostic().is_err());
+
+    // Node 1's share is for a different document, but recorded without verification.
+    let mut rng = XorShiftRng::from_seed([14; 16]);
+    let netinfos = NetworkInfo::generate_map(0..4, &mut rng).expect("netinfos");
+    let tampered = netinfos[&1]
+        .secret_key_share()
+        .expect("share")
+        .sign("Tampered");
+    ts.inject_share(&1, tampered).expect("inject");
+    ts.inject_share(&2, shares[1].share.clone())
+        .expect("inject");
+    let (sig, verified) = ts.try_combine_diagnostic().expect("diagnostic");
+    assert!(!verified);
+    assert!(!ts.public_key_set().public_key().verify(&sig, DOC));
+    assert!(!ts.terminated());
+
+    // The regular path still aborts.
+    assert_eq!(
+        Err(Error::VerificationFailed),
+        ts.handle_message(&3, shares[2].clone()).map(|_| ())
+    );
+    assert_eq!(None, ts.output_signature());
+}
+
+#[test]
+fn test_threshold_sign_diagnostic_valid_shares() {
+    let (mut ts, shares) = setup();
+    ts.inject_share(&1, shares[0].share.clone())
+        .expect("inject");
+    ts.inject_share(&2, shares[1].share.clone())
+        .expect("inject");
+    let (sig, verified) = ts.try_combine_diagnostic().expect("diagnostic");
+    assert!(verified);
+    assert!(ts.public_key_set().public_key().verify(&sig, DOC));
+}
+
+#[test]
+fn test_threshold_sign_injected_invalid_share() {
+    let (mut ts, shares) = setup();
+    let mut invalid_shares = BTreeSet::new();
+    invalid_shares.insert(1);
+    ts.inject_faults(InjectedFaults {
+        fail_combine: false,
+        invalid_shares,
+    });
+    // Node 1's valid share is rejected, and the sender is reported.
+    let step = ts.handle_message(&1, shares[0].clone()).expect("share");
+    let expected_fault = Fault::new(1, FaultKind::UnverifiedSignatureShareSender);
+    assert_eq!(vec![expected_fault], step.fault_log.0);
+    assert!(ts.missing_contributors().contains(&1));
+
+    // The other two shares suffice.
+    let step = ts.handle_message(&2, shares[1].clone()).expect("share");
+    assert!(step.output.is_empty());
+    let step = ts.handle_message(&3, shares[2].clone()).expect("share");
+    assert_eq!(1, step.output.len());
+    assert_eq!(Some(vec![2, 3]), ts.combining_set());
+}
+
+#[test]
+fn test_threshold_sign_unknown_sender() {
+    let (mut ts, shares) = setup();
+    assert_eq!(
+        Err(Error::UnknownSender),
+        ts.handle_message(&10, shares[0].clone()).map(|_| ())
+    );
+    // The share wasn't stored, and the instance still works.
+    assert_eq!(vec![0, 1, 2, 3], ts.missing_contributors());
+    let step = ts.handle_message(&1, shares[0].clone()).expect("share");
+    assert!(step.output.is_empty());
+    let step = ts.handle_message(&2, shares[1].clone()).expect("share");
+    assert_eq!(1, step.output.len());
+}
+
+#[test]
+fn test_threshold_sign_rotate_nonce_combine_failure() {
+    let (mut ts, _) = setup();
+    let mut rng = XorShiftRng::from_seed([14; 16]);
+    let netinfos = NetworkInfo::generate_map(0..4, &mut rng).expect("netinfos");
+    let doc = round_document("Next", 1);
+    let share = |id: usize| {
+        Message::new(
+            1,
+            netinfos[&id].secret_key_share().expect("share").sign(&doc),
+        )
+    };
+    // Nodes 1 and 2 rotated before us, and their shares are kept for the next round.
+    for id in 1..3 {
+        let step = ts.handle_message(&id, share(id)).expect("share");
+        assert!(step.is_empty());
+    }
+    ts.inject_faults(InjectedFaults {
+        fail_combine: true,
+        invalid_shares: BTreeSet::new(),
+    });
+    // Combining them fails, but both shares are still recorded.
+    assert_eq!(
+        Some(Error::VerificationFailed),
+        ts.rotate_nonce("Next").err()
+    );
+    assert_eq!(1, ts.round());
+    assert_eq!(vec![0, 3], ts.missing_contributors());
+
+    // Once combining works again, the next share completes the signature.
+    ts.inject_faults(InjectedFaults::default());
+    let step = ts.handle_message(&3, share(3)).expect("share");
+    assert_eq!(1, step.output.len());
+    assert_eq!(Some(vec![1, 2]), ts.combining_set());
+}
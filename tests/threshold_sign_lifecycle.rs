@@ -0,0 +1,300 @@
+#![deny(unused_must_use)]
+//! Tests of constructing, rotating, timing out and shutting down `ThresholdSign` instances.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+use hbbft::threshold_sign::{self, Message, ThresholdSign};
+use hbbft::{
+    crypto::Signature, testing, DistAlgorithm, Fault, FaultKind, Instrumented, NetworkInfo,
+};
+
+#[test]
+fn test_threshold_sign_timeout() {
+    let (_, netinfos) = testing::setup(4, 1);
+    let doc = "Deadline";
+    let mut ts = ThresholdSign::new(Arc::new(netinfos[0].clone()));
+    let expected = |received_shares, has_document| {
+        Some(threshold_sign::Event::DeadlineMissed {
+            received_shares,
+            has_document,
+        })
+    };
+    assert_eq!(expected(0, false), ts.handle_timeout());
+
+    // The timeout doesn't prevent the instance from terminating.
+    ts.set_document(doc).expect("document");
+    assert!(ts.sign().expect("sign").output.is_empty());
+    assert_eq!(expected(1, true), ts.handle_timeout());
+    assert_eq!(expected(1, true), ts.handle_timeout());
+    let sks1 = netinfos[1].secret_key_share().expect("secret key share");
+    let step = ts.handle_message(&1, Message::new(0, sks1.sign(doc)));
+    assert_eq!(1, step.expect("share").output.len());
+    assert!(ts.terminated());
+
+    // After termination, a timeout is a no-op.
+    assert_eq!(None, ts.handle_timeout());
+}
+
+#[test]
+fn test_threshold_sign_shutdown() {
+    let mut rng = XorShiftRng::from_seed([13; 16]);
+    let (_, netinfos) = testing::setup(4, 1);
+    let doc = "Last words";
+    let mut nodes: Vec<_> = (0..4)
+        .map(|id| {
+            ThresholdSign::new_with_document(Arc::new(netinfos[id].clone()), doc).expect("document")
+        })
+        .collect();
+
+    // Node 0 signs and shuts down. Nothing else is owed to its peers, and further input and
+    // messages are ignored.
+    let step0 = nodes[0].handle_input((), &mut rng).expect("sign");
+    assert_eq!(1, step0.messages.len());
+    assert!(DistAlgorithm::shutdown(&mut nodes[0]).is_empty());
+    assert!(nodes[0].terminated());
+    assert!(nodes[0]
+        .handle_input((), &mut rng)
+        .expect("input")
+        .is_empty());
+    let share1 = netinfos[1].secret_key_share().expect("share").sign(doc);
+    let step = nodes[0]
+        .handle_message(&1, Message::new(0, share1))
+        .expect("share");
+    assert!(step.is_empty());
+    assert_eq!(None, nodes[0].output_signature());
+
+    // Node 3 shuts down before signing, and never sends its share.
+    assert!(nodes[3].shutdown().is_empty());
+    assert!(nodes[3].sign().expect("sign").is_empty());
+    assert!(nodes[3].publish_share().expect("publish").is_empty());
+
+    // The share node 0 sent before shutting down still counts for the others.
+    let msg0 = step0.messages[0].message.clone();
+    let step1 = nodes[1].sign().expect("sign");
+    let step = nodes[1].handle_message(&0, msg0.clone()).expect("share");
+    assert_eq!(1, step.output.len());
+    let msg1 = step1.messages[0].message.clone();
+    assert!(nodes[2]
+        .handle_message(&0, msg0)
+        .expect("share")
+        .output
+        .is_empty());
+    let step = nodes[2].handle_message(&1, msg1).expect("share");
+    assert_eq!(1, step.output.len());
+}
+
+#[test]
+fn test_threshold_sign_builder() {
+    let (_, netinfos) = testing::setup(4, 1);
+    let netinfo = Arc::new(netinfos[0].clone());
+    let doc = "Built";
+    let shares: Vec<_> = (1..4)
+        .map(|id| Message::new(0, netinfos[id].secret_key_share().expect("share").sign(doc)))
+        .collect();
+
+    /// Signs, handles the shares, and returns the sent messages and the outputs.
+    fn run(ts: &mut ThresholdSign<usize>, shares: &[Message]) -> (Vec<Message>, Vec<Signature>) {
+        let mut step = ts.sign().expect("sign");
+        for (i, share) in shares.iter().enumerate() {
+            step.extend(ts.handle_message(&(i + 1), share.clone()).expect("share"));
+        }
+        let msgs = step.messages.into_iter().map(|tm| tm.message).collect();
+        (msgs, step.output)
+    }
+
+    // A default-built instance behaves exactly like one created with the constructors.
+    let mut built = ThresholdSign::builder(netinfo.clone()).build();
+    let mut ts = ThresholdSign::new(netinfo.clone());
+    assert_eq!(ts.metrics(), built.metrics());
+    assert_eq!(ts.preview_input(), built.preview_input());
+    built.set_document(doc).expect("document");
+    ts.set_document(doc).expect("document");
+    let expected = run(&mut ts, &shares);
+    assert_eq!(1, expected.1.len());
+    assert_eq!(expected, run(&mut built, &shares));
+    assert_eq!(ts.metrics(), built.metrics());
+    assert_eq!(ts.combining_set(), built.combining_set());
+
+    let mut built = ThresholdSign::builder(netinfo.clone())
+        .document(doc)
+        .build();
+    let mut ts = ThresholdSign::new_with_document(netinfo.clone(), doc).expect("document");
+    assert_eq!(run(&mut ts, &shares), run(&mut built, &shares));
+
+    // The options have the same effect as the setters.
+    let mut built = ThresholdSign::builder(netinfo.clone())
+        .document(doc)
+        .defer_output(true)
+        .collect_late_shares(true)
+        .build();
+    let (msgs, outputs) = run(&mut built, &shares);
+    assert_eq!(expected.0, msgs);
+    assert!(outputs.is_empty());
+    assert_eq!(Some(expected.1[0].clone()), built.release_output());
+    let contributors: BTreeSet<usize> = (0..4).collect();
+    assert_eq!(contributors, built.all_contributors());
+    assert_eq!(
+        Err(threshold_sign::Error::MultipleMessagesToSign),
+        built.set_document(doc)
+    );
+}
+
+#[test]
+fn test_threshold_sign_rotate_nonce() {
+    let (pk_set, netinfos) = testing::setup(4, 1);
+    let share = |id: usize, round: u64, doc: &[u8]| {
+        let sks = netinfos[id].secret_key_share().expect("share");
+        Message::new(round, sks.sign(doc))
+    };
+    let nonce = b"restart";
+    let mut ts =
+        ThresholdSign::new_with_document(Arc::new(netinfos[0].clone()), nonce).expect("document");
+    assert_eq!(0, ts.round());
+    let step = ts.handle_message(&1, share(1, 0, nonce)).expect("share");
+    assert!(step.is_empty());
+
+    // After rotating, node 1's share doesn't count anymore, and a share for the old round is
+    // ignored. A share for the new round that signs the old document is rejected, even though the
+    // nonce is the same.
+    let step = ts.rotate_nonce(nonce).expect("rotate nonce");
+    assert!(step.is_empty());
+    assert_eq!(1, ts.round());
+    assert_eq!(vec![0, 1, 2, 3], ts.missing_contributors());
+    let step = ts
+        .handle_message(&2, share(2, 0, nonce))
+        .expect("stale share");
+    assert!(step.is_empty());
+    let step = ts
+        .handle_message(&2, share(2, 1, nonce))
+        .expect("wrong share");
+    let expected_fault = Fault::new(2, FaultKind::UnverifiedSignatureShareSender);
+    assert_eq!(vec![expected_fault], step.fault_log.0);
+    let step = ts.sign().expect("sign");
+    assert_eq!(1, step.messages.len());
+    assert_eq!(1, step.messages[0].message.round);
+    assert!(step.output.is_empty());
+
+    // A share for the new round completes the signature, which is valid for the round document.
+    let doc = threshold_sign::round_document(nonce, 1);
+    let step = ts.handle_message(&1, share(1, 1, &doc)).expect("share");
+    assert_eq!(1, step.output.len());
+    assert!(pk_set.public_key().verify(&step.output[0], &doc));
+    assert!(!pk_set.public_key().verify(&step.output[0], nonce));
+
+    // After terminating, the instance can be rotated again.
+    assert!(ts.terminated());
+    let step = ts.rotate_nonce(nonce).expect("rotate nonce");
+    assert!(step.is_empty());
+    assert!(!ts.terminated());
+    assert_eq!(None, ts.output_signature());
+    let step = ts.sign().expect("sign");
+    assert_eq!(1, step.messages.len());
+}
+
+#[test]
+fn test_threshold_sign_rotate_nonce_peer_first() {
+    let (pk_set, netinfos) = testing::setup(4, 1);
+    let nonce = b"peer first";
+    let mut ts =
+        ThresholdSign::new_with_document(Arc::new(netinfos[0].clone()), nonce).expect("document");
+
+    // Node 3 rotates and signs the next round before we do.
+    let mut peer =
+        ThresholdSign::new_with_document(Arc::new(netinfos[3].clone()), nonce).expect("document");
+    let step = peer.rotate_nonce("next").expect("rotate nonce");
+    assert!(step.is_empty());
+    let step = peer.sign().expect("sign");
+    let peer_msg = step.messages[0].message.clone();
+    assert_eq!(1, peer_msg.round);
+
+    // Its share is kept for the next round: It is neither counted nor reported.
+    let step = ts.handle_message(&3, peer_msg).expect("next round share");
+    assert!(step.is_empty());
+    assert_eq!(vec![0, 1, 2, 3], ts.missing_contributors());
+    let doc = threshold_sign::round_document("next", 1);
+    let sks2 = netinfos[2].secret_key_share().expect("share");
+    let step = ts
+        .handle_message(&2, Message::new(1, sks2.sign(&doc)))
+        .expect("next round share");
+    assert!(step.is_empty());
+    let sks1 = netinfos[1].secret_key_share().expect("share");
+    let step = ts
+        .handle_message(&1, Message::new(1, sks1.sign(nonce)))
+        .expect("next round share");
+    assert!(step.is_empty());
+
+    // Once we rotate, too, the invalid share is reported, and the valid ones produce the
+    // signature.
+    let step = ts.rotate_nonce("next").expect("rotate nonce");
+    let expected_fault = Fault::new(1, FaultKind::UnverifiedSignatureShareSender);
+    assert_eq!(vec![expected_fault], step.fault_log.0);
+    assert_eq!(1, step.output.len());
+    assert!(pk_set.public_key().verify(&step.output[0], &doc));
+    assert_eq!(1, step.messages.len());
+    assert_eq!(1, step.messages[0].message.round);
+}
+
+#[test]
+fn test_threshold_sign_rotate_netinfo() {
+    let (old_pk_set, old_netinfos) = testing::setup(4, 1);
+    let mut rng = XorShiftRng::from_seed([15; 16]);
+    let new_netinfos = NetworkInfo::generate_map(0..4, &mut rng).expect("netinfos");
+    let doc = "Checkpoint";
+    let mut ts =
+        ThresholdSign::new_with_document(Arc::new(old_netinfos[0].clone()), doc).expect("document");
+    let step = ts.sign().expect("sign");
+    assert!(step.output.is_empty());
+
+    // Mid-round, the keys can't be replaced.
+    let new_netinfo = Arc::new(new_netinfos[&0].clone());
+    assert_eq!(
+        Err(threshold_sign::Error::NotTerminated),
+        ts.rotate_netinfo(new_netinfo.clone())
+    );
+    let share = Message::new(
+        0,
+        old_netinfos[1].secret_key_share().expect("share").sign(doc),
+    );
+    let step = ts.handle_message(&1, share).expect("share");
+    assert_eq!(1, step.output.len());
+    assert!(old_pk_set.public_key().verify(&step.output[0], doc));
+
+    // After termination they can, and the next round uses the new keys.
+    ts.rotate_netinfo(new_netinfo.clone())
+        .expect("rotate netinfo");
+    assert_eq!(
+        Some(&step.output[0]),
+        ts.output_signature(),
+        "the old signature is kept"
+    );
+    let step = ts.rotate_nonce(doc).expect("rotate nonce");
+    assert!(step.is_empty());
+    let round_doc = threshold_sign::round_document(doc, 1);
+    let step = ts.sign().expect("sign");
+    assert!(step.output.is_empty());
+    let old_share = Message::new(
+        1,
+        old_netinfos[1]
+            .secret_key_share()
+            .expect("share")
+            .sign(&round_doc),
+    );
+    let step = ts.handle_message(&1, old_share).expect("share");
+    let expected_fault = Fault::new(1, FaultKind::UnverifiedSignatureShareSender);
+    assert_eq!(vec![expected_fault], step.fault_log.0);
+    let new_share = Message::new(
+        1,
+        new_netinfos[&2]
+            .secret_key_share()
+            .expect("share")
+            .sign(&round_doc),
+    );
+    let step = ts.handle_message(&2, new_share).expect("share");
+    assert_eq!(1, step.output.len());
+    let new_pk = new_netinfo.public_key_set().public_key();
+    assert!(new_pk.verify(&step.output[0], &round_doc));
+}
@@ -0,0 +1,419 @@
+#![deny(unused_must_use)]
+//! Tests of the signature `ThresholdSign` outputs, and of the shares it is combined from.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+use hbbft::threshold_sign::{self, Message, ThresholdSign};
+use hbbft::{crypto::SecretKey, testing, DistAlgorithm, NetworkInfo};
+
+#[test]
+fn test_threshold_sign_verify_round() {
+    let (pk_set, netinfos) = testing::setup(7, 2);
+    let doc = "A coin round to audit";
+    let sign = |doc: &str| -> BTreeMap<u64, _> {
+        netinfos
+            .iter()
+            .map(|netinfo| {
+                let idx = netinfo.node_index(netinfo.our_id()).expect("node index") as u64;
+                let sks = netinfo.secret_key_share().expect("secret key share");
+                (idx, sks.sign(doc))
+            })
+            .collect()
+    };
+    let shares = sign(doc);
+    let coin = pk_set
+        .combine_signatures(&shares)
+        .expect("Failed to combine shares")
+        .parity();
+
+    // A correct recording verifies, any `threshold + 1` of the shares suffice.
+    let verify = |shares: &BTreeMap<u64, _>, claimed| {
+        threshold_sign::verify_round(&pk_set, doc, shares, claimed)
+    };
+    assert_eq!(Ok(true), verify(&shares, coin));
+    let few_shares: BTreeMap<_, _> = shares.clone().into_iter().skip(4).collect();
+    assert_eq!(Ok(true), verify(&few_shares, coin));
+    assert_eq!(Ok(false), verify(&shares, !coin));
+
+    // A tampered share is detected, even if the claimed value is correct.
+    let mut tampered = shares.clone();
+    tampered.insert(3, sign("A different document")[&3].clone());
+    assert_eq!(Ok(false), verify(&tampered, coin));
+
+    // Too few shares can't be combined.
+    let too_few: BTreeMap<_, _> = shares.into_iter().skip(5).collect();
+    assert!(verify(&too_few, coin).is_err());
+}
+
+#[test]
+fn test_threshold_sign_defer_output() {
+    let (pk_set, netinfos) = testing::setup(4, 1);
+    let doc = "Not yet";
+    let mut ts = ThresholdSign::new_with_document(Arc::new(netinfos[0].clone()), doc)
+        .expect("Failed to set the document");
+    ts.set_defer_output(true);
+    assert!(ts.sign().expect("Failed to sign").output.is_empty());
+    assert_eq!(None, ts.release_output());
+
+    // The second share completes the signature, but it is not output.
+    let sks = netinfos[1].secret_key_share().expect("secret key share");
+    let step = ts
+        .handle_message(&1, Message::new(0, sks.sign(doc)))
+        .expect("Failed to handle share");
+    assert!(step.output.is_empty());
+    assert!(ts.terminated());
+
+    // The nonce can't be rotated while the signature hasn't been released.
+    assert_eq!(
+        Some(threshold_sign::Error::UnreleasedOutput),
+        ts.rotate_nonce("Next").err()
+    );
+    assert_eq!(0, ts.round());
+
+    // It is released exactly once.
+    let sig = ts.release_output().expect("deferred signature");
+    let pk = pk_set.public_key();
+    assert!(pk.verify(&sig, doc));
+    assert_eq!(None, ts.release_output());
+    let step = ts.rotate_nonce("Next").expect("rotate nonce");
+    assert!(step.is_empty());
+    assert_eq!(1, ts.round());
+}
+
+#[test]
+fn test_threshold_sign_combining_set() {
+    let (pk_set, netinfos) = testing::setup(7, 2);
+    let doc = "Which shares?";
+    let mut ts = ThresholdSign::new_with_document(Arc::new(netinfos[0].clone()), doc)
+        .expect("Failed to set the document");
+    let mut shares = BTreeMap::new();
+    let mut outputs = Vec::new();
+    // Shares from nodes 6, 2 and 4 are the first three, and suffice with _f = 2_.
+    for &id in &[6, 2, 4] {
+        assert_eq!(None, ts.combining_set());
+        let idx = netinfos[id].node_index(&id).expect("node index") as u64;
+        let sks = netinfos[id].secret_key_share().expect("secret key share");
+        let share = sks.sign(doc);
+        shares.insert(idx, share.clone());
+        let step = ts
+            .handle_message(&id, Message::new(0, share))
+            .expect("share");
+        assert_eq!(shares.len() == 3, !step.output.is_empty());
+        outputs.extend(step.output);
+    }
+    let combining_set = ts.combining_set().expect("combining set");
+    assert_eq!(shares.keys().cloned().collect::<Vec<_>>(), combining_set);
+    // The reported shares can be used to audit the round.
+    let parity = outputs[0].parity();
+    assert_eq!(
+        Ok(true),
+        threshold_sign::verify_round(&pk_set, doc, &shares, parity)
+    );
+}
+
+/// Returns all permutations of the given items.
+fn permutations<T: Clone>(items: &[T]) -> Vec<Vec<T>> {
+    if items.is_empty() {
+        return vec![vec![]];
+    }
+    let mut result = Vec::new();
+    for i in 0..items.len() {
+        let mut rest = items.to_vec();
+        let first = rest.remove(i);
+        for mut perm in permutations(&rest) {
+            perm.insert(0, first.clone());
+            result.push(perm);
+        }
+    }
+    result
+}
+
+#[test]
+fn test_threshold_sign_order_independence() {
+    let mut rng = XorShiftRng::from_seed([42; 16]);
+    let (_, netinfos) = testing::setup(7, 2);
+    let doc = "Order independence";
+    let shares: Vec<(usize, Message)> = netinfos
+        .iter()
+        .skip(1)
+        .map(|netinfo| {
+            let sks = netinfo.secret_key_share().expect("secret key share");
+            (*netinfo.our_id(), Message::new(0, sks.sign(doc)))
+        })
+        .collect();
+    let netinfo = Arc::new(netinfos[0].clone());
+    let expected = netinfo.public_key_set().combine_signatures(
+        shares
+            .iter()
+            .take(netinfo.num_faulty() + 1)
+            .map(|(id, msg)| (*id, &msg.share)),
+    );
+    let expected = expected.expect("combine signatures");
+
+    // Feeds the shares to a new instance in the given order, and returns the output.
+    let run = |order: &[(usize, Message)]| {
+        let mut ts = ThresholdSign::new_with_document(netinfo.clone(), doc).expect("document");
+        let mut outputs = Vec::new();
+        for (id, msg) in order {
+            let step = ts.handle_message(id, msg.clone()).expect("share");
+            assert!(step.fault_log.is_empty());
+            outputs.extend(step.output);
+        }
+        assert_eq!(1, outputs.len());
+        outputs.pop().expect("signature")
+    };
+
+    // Every permutation of the first four shares, of which any three suffice.
+    for order in permutations(&shares[..4]) {
+        let sig = run(&order);
+        assert_eq!(expected, sig);
+        assert_eq!(expected.parity(), sig.parity());
+    }
+    // Random permutations of all six shares.
+    let mut order = shares.clone();
+    for _ in 0..50 {
+        order.shuffle(&mut rng);
+        assert_eq!(expected, run(&order));
+    }
+}
+
+#[test]
+fn test_threshold_sign_output_signature() {
+    let (pk_set, netinfos) = testing::setup(4, 1);
+    let nonce = "VRF nonce";
+    let mut ts =
+        ThresholdSign::new_with_document(Arc::new(netinfos[0].clone()), nonce).expect("document");
+    ts.set_defer_output(true);
+    assert_eq!(None, ts.output_signature());
+    assert_eq!(None, ts.proof_bytes());
+
+    // Two shares from other nodes suffice to compute the signature.
+    for (id, netinfo) in netinfos.iter().enumerate().skip(1).take(2) {
+        let share = netinfo.secret_key_share().expect("share").sign(nonce);
+        let step = ts
+            .handle_message(&id, Message::new(0, share))
+            .expect("share");
+        assert!(step.output.is_empty());
+    }
+    assert!(ts.terminated());
+    let released = ts.release_output().expect("signature");
+
+    // The signature is retained after it was released, and verifies against the master key.
+    let sig = ts.output_signature().expect("signature").clone();
+    assert_eq!(released, sig);
+    let pk = pk_set.public_key();
+    assert!(pk.verify(&sig, nonce));
+    let proof = ts.proof_bytes().expect("proof");
+    assert_eq!(sig.to_bytes().to_vec(), proof);
+    assert!(!pk.verify(&sig, "other nonce"));
+}
+
+#[test]
+fn test_threshold_sign_combine_from_messages() {
+    let (pk_set, netinfos) = testing::setup(7, 2);
+    let netinfo = Arc::new(netinfos[0].clone());
+    let doc = "offline";
+    let mut ts = ThresholdSign::new_with_document(netinfo.clone(), doc).expect("document");
+    let share_msg = |id: usize, doc: &str| {
+        Message::new(0, netinfos[id].secret_key_share().expect("share").sign(doc))
+    };
+
+    // The wire contains an invalid share from node 1, and node 2's share twice.
+    let msgs = vec![
+        (1, share_msg(1, "other")),
+        (2, share_msg(2, doc)),
+        (2, share_msg(2, doc)),
+        (4, share_msg(4, doc)),
+        (5, share_msg(5, doc)),
+    ];
+    let index_of = |id: &usize| netinfo.node_index(id).expect("index") as u64;
+
+    // With only two valid shares, the signature cannot be computed yet.
+    assert!(
+        threshold_sign::combine_from_messages(&pk_set, index_of, doc, msgs[..4].to_vec()).is_err()
+    );
+
+    let mut outputs = Vec::new();
+    for (id, msg) in msgs.clone() {
+        let step = ts.handle_message(&id, msg).expect("share");
+        outputs.extend(step.output);
+    }
+    assert_eq!(1, outputs.len());
+    let sig = threshold_sign::combine_from_messages(&pk_set, index_of, doc, msgs).expect("combine");
+    assert_eq!(outputs[0], sig);
+    assert!(pk_set.public_key().verify(&sig, doc));
+}
+
+#[test]
+fn test_threshold_sign_contributed_to_output() {
+    let mut rng = XorShiftRng::from_seed([12; 16]);
+    let (_, netinfos) = testing::setup(4, 1);
+    let doc = "Who gets a reward?";
+    let share = |id: usize| {
+        let sks = netinfos[id].secret_key_share().expect("secret key share");
+        Message::new(0, sks.sign(doc))
+    };
+    let new_ts = |netinfo: &NetworkInfo<usize>| {
+        ThresholdSign::new_with_document(Arc::new(netinfo.clone()), doc).expect("document")
+    };
+
+    // Node 0 signs before the signature is computed, so its share counts.
+    let mut ts0 = new_ts(&netinfos[0]);
+    let step = ts0.sign().expect("sign");
+    assert_eq!(1, step.messages.len());
+    assert!(!ts0.contributed_to_output());
+    let step = ts0.handle_message(&1, share(1)).expect("share");
+    assert_eq!(1, step.output.len());
+    assert!(ts0.contributed_to_output());
+
+    // Node 3 receives enough shares before signing. It still sends its share, but that didn't
+    // contribute to its output.
+    let mut ts3 = new_ts(&netinfos[3]);
+    let step = ts3.handle_message(&0, share(0)).expect("share");
+    assert!(step.output.is_empty());
+    let step = ts3.handle_message(&1, share(1)).expect("share");
+    assert_eq!(1, step.output.len());
+    assert_eq!(1, step.messages.len());
+    assert!(!ts3.contributed_to_output());
+
+    // An observer never contributes.
+    let netinfo = &netinfos[0];
+    let observer_info = NetworkInfo::new(
+        10,
+        None,
+        netinfo.public_key_set().clone(),
+        rng.gen::<SecretKey>(),
+        netinfo.public_key_map().clone(),
+    );
+    let mut observer = new_ts(&observer_info);
+    let step = observer.sign().expect("sign");
+    assert!(step.messages.is_empty());
+    let step = observer.handle_message(&0, share(0)).expect("share");
+    assert!(step.output.is_empty());
+    let step = observer.handle_message(&1, share(1)).expect("share");
+    assert_eq!(1, step.output.len());
+    assert!(!observer.contributed_to_output());
+}
+
+#[test]
+fn test_threshold_sign_public_key_set() {
+    let (pk_set, netinfos) = testing::setup(4, 1);
+    let doc = "Verify me";
+    let shares: Vec<_> = netinfos
+        .iter()
+        .map(|netinfo| Message::new(0, netinfo.secret_key_share().expect("share").sign(doc)))
+        .collect();
+    // The `NetworkInfo` is moved into the instance.
+    let netinfo = netinfos.into_iter().next().expect("netinfo");
+    let mut ts = ThresholdSign::new_with_document(Arc::new(netinfo), doc).expect("document");
+    assert_eq!(pk_set, *ts.public_key_set());
+
+    let mut outputs = Vec::new();
+    for (id, share) in shares.iter().enumerate().skip(1).take(2) {
+        let step = ts.handle_message(&id, share.clone()).expect("share");
+        outputs.extend(step.output);
+    }
+    assert!(ts.terminated());
+    assert_eq!(1, outputs.len());
+    assert!(ts.public_key_set().public_key().verify(&outputs[0], doc));
+    // The key set also verifies the individual shares.
+    for (idx, share) in shares.iter().enumerate() {
+        let pk_share = ts.public_key_set().public_key_share(idx);
+        assert!(pk_share.verify(&share.share, doc));
+    }
+}
+
+#[test]
+fn test_threshold_sign_deterministic_combining_set() {
+    // With seven nodes, _f = 2_, so three shares are combined.
+    let (pk_set, netinfos) = testing::setup(7, 2);
+    let doc = "Which three?";
+    let share =
+        |id: usize| Message::new(0, netinfos[id].secret_key_share().expect("share").sign(doc));
+    let mut results = Vec::new();
+    // Node 3 receives three shares before it knows the document, so together with its own there
+    // are _f + 2_ when it signs. The three with the lowest IDs are used, in any arrival order.
+    for order in permutations(&[6, 5, 1]) {
+        let mut ts = ThresholdSign::new(Arc::new(netinfos[3].clone()));
+        for id in &order {
+            let step = ts.handle_message(id, share(*id)).expect("early share");
+            assert!(step.is_empty());
+        }
+        ts.set_document(doc).expect("document");
+        let step = ts.sign().expect("sign");
+        assert_eq!(1, step.output.len());
+        assert_eq!(Some(vec![1, 3, 5]), ts.combining_set());
+        assert!(ts.contributed_to_output());
+        results.push(step.output[0].clone());
+    }
+    assert_eq!(6, results.len());
+    assert!(results.iter().all(|sig| *sig == results[0]));
+    assert!(pk_set.public_key().verify(&results[0], doc));
+}
+
+#[test]
+fn test_threshold_sign_clone() {
+    let (pk_set, netinfos) = testing::setup(7, 2);
+    let doc = "Speculate";
+    let share =
+        |id: usize| Message::new(0, netinfos[id].secret_key_share().expect("share").sign(doc));
+    let netinfo = Arc::new(netinfos[0].clone());
+    let mut ts = ThresholdSign::new_with_document(netinfo, doc).expect("document");
+    let step = ts.sign().expect("sign");
+    assert!(step.output.is_empty());
+    let step = ts.handle_message(&1, share(1)).expect("share");
+    assert!(step.output.is_empty());
+
+    // The fork outputs the signature after one more share.
+    let mut fork = ts.clone();
+    let step = fork.handle_message(&2, share(2)).expect("share");
+    assert_eq!(1, step.output.len());
+    assert!(fork.terminated());
+
+    // The original is still waiting, and hasn't seen node 2's share.
+    assert!(!ts.terminated());
+    assert_eq!(None, ts.output_signature());
+    assert_eq!(None, ts.combining_set());
+    assert_eq!(vec![2, 3, 4, 5, 6], ts.missing_contributors());
+    let step = ts.handle_message(&3, share(3)).expect("share");
+    assert_eq!(1, step.output.len());
+    assert_eq!(Some(vec![0, 1, 3]), ts.combining_set());
+    assert_eq!(Some(vec![0, 1, 2]), fork.combining_set());
+    assert_eq!(fork.output_signature(), ts.output_signature());
+    assert!(pk_set.public_key().verify(&step.output[0], doc));
+}
+
+#[test]
+fn test_threshold_sign_canonical_subset_of_all_shares() {
+    let mut rng = XorShiftRng::from_seed([17; 16]);
+    let (pk_set, netinfos) = testing::setup(7, 2);
+    let doc = "All of them";
+    let share =
+        |id: usize| Message::new(0, netinfos[id].secret_key_share().expect("share").sign(doc));
+    let mut order: Vec<usize> = (0..7).filter(|id| *id != 4).collect();
+    let mut expected = None;
+    for _ in 0..10 {
+        // Node 4 receives everyone else's share before the document, so all _N_ shares are
+        // present when it signs. Only the three lowest indices are combined.
+        order.shuffle(&mut rng);
+        let mut ts = ThresholdSign::new(Arc::new(netinfos[4].clone()));
+        for id in &order {
+            let step = ts.handle_message(id, share(*id)).expect("early share");
+            assert!(step.is_empty());
+        }
+        ts.set_document(doc).expect("document");
+        let step = ts.sign().expect("sign");
+        assert_eq!(1, step.output.len());
+        assert_eq!(Some(vec![0, 1, 2]), ts.combining_set());
+        let sig = step.output[0].clone();
+        assert_eq!(*expected.get_or_insert_with(|| sig.clone()), sig);
+    }
+    assert!(pk_set
+        .public_key()
+        .verify(&expected.expect("signature"), doc));
+}
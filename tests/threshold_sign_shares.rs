@@ -0,0 +1,551 @@
+#![deny(unused_must_use)]
+//! Tests of how `ThresholdSign` sends, receives and verifies signature shares.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+use hbbft::threshold_sign::{self, Message, ShareSigner, ThresholdSign};
+use hbbft::{
+    crypto::{Signature, SignatureShare},
+    crypto_util, testing, AlgoMetrics, DistAlgorithm, Fault, FaultKind, Instrumented, NetworkInfo,
+    Target,
+};
+
+#[test]
+fn test_threshold_sign_step_contains_share() {
+    let mut rng = rand::thread_rng();
+    let (_, netinfos) = testing::setup(4, 1);
+    let netinfo = Arc::new(netinfos[0].clone());
+    let doc = "Sign me, and tell everyone about it.";
+    let mut ts =
+        ThresholdSign::new_with_document(netinfo.clone(), doc).expect("Failed to set the document");
+    let step = ts.handle_input((), &mut rng).expect("Failed to sign");
+    // The step itself contains our share, addressed to all nodes. One share is not enough yet.
+    assert!(step.output.is_empty());
+    assert!(step.fault_log.is_empty());
+    assert_eq!(1, step.messages.len());
+    let tm = &step.messages[0];
+    assert_eq!(Target::All, tm.target);
+    let Message { ref share, .. } = tm.message;
+    let pk_share = netinfo.public_key_share(&0).expect("public key share");
+    assert!(pk_share.verify(share, doc));
+    // A second input doesn't produce the share again.
+    assert!(ts
+        .handle_input((), &mut rng)
+        .expect("Failed to sign")
+        .is_empty());
+}
+
+#[test]
+fn test_threshold_sign_quorum() {
+    let (_, netinfos) = testing::setup(7, 2);
+    let doc = "Quorum";
+    let shares: Vec<_> = netinfos
+        .iter()
+        .map(|netinfo| {
+            let sks = netinfo.secret_key_share().expect("secret key share");
+            (*netinfo.our_id(), Message::new(0, sks.sign(doc)))
+        })
+        .collect();
+    let netinfo = Arc::new(netinfos[0].clone());
+    let num_faulty = netinfo.num_faulty();
+
+    // With the document known, the quorum coincides with the output.
+    let mut ts = ThresholdSign::new_with_document(netinfo.clone(), doc).expect("document");
+    for (i, (id, msg)) in shares.iter().skip(1).enumerate() {
+        assert!(!ts.has_quorum());
+        let step = ts.handle_message(id, msg.clone()).expect("share");
+        assert_eq!(i == num_faulty, ts.has_quorum());
+        assert_eq!(ts.has_quorum(), !step.output.is_empty());
+        if ts.has_quorum() {
+            break;
+        }
+    }
+
+    // Without the document, the quorum is reached at the `f + 1`-th share, but the output has to
+    // wait for the document.
+    let mut ts = ThresholdSign::new(netinfo);
+    for (i, (id, msg)) in shares.iter().skip(1).take(num_faulty + 1).enumerate() {
+        assert!(!ts.has_quorum());
+        let step = ts.handle_message(id, msg.clone()).expect("share");
+        assert_eq!(i == num_faulty, ts.has_quorum());
+        assert!(step.output.is_empty());
+    }
+    ts.set_document(doc).expect("document");
+    assert_eq!(1, ts.sign().expect("sign").output.len());
+}
+
+#[test]
+fn test_threshold_sign_message_batch() {
+    let mut rng = rand::thread_rng();
+    let num_coins = 3;
+    let (_, netinfos) = testing::setup(4, 1);
+    // Each node runs several independent coins, and each message is tagged with the coin's index.
+    let mut nodes: BTreeMap<usize, Vec<ThresholdSign<usize>>> = netinfos
+        .into_iter()
+        .enumerate()
+        .map(|(id, netinfo)| {
+            let netinfo = Arc::new(netinfo);
+            let coins = (0..num_coins)
+                .map(|i| {
+                    let doc = format!("coin {}", i);
+                    ThresholdSign::new_with_document(netinfo.clone(), doc).expect("document")
+                })
+                .collect();
+            (id, coins)
+        })
+        .collect();
+    let mut outputs: BTreeMap<(usize, usize), Signature> = BTreeMap::new();
+    let mut queue = Vec::new();
+    for (id, coins) in &mut nodes {
+        let msgs = coins.iter_mut().enumerate().flat_map(|(i, coin)| {
+            let step = coin
+                .handle_input((), &mut rand::thread_rng())
+                .expect("sign");
+            assert!(step.output.is_empty());
+            step.messages
+                .into_iter()
+                .map(move |tm| tm.map(|msg| (i, msg)))
+        });
+        // All shares for the same target are sent in a single batch.
+        let batches = hbbft::TargetedMessage::batch(msgs);
+        assert_eq!(1, batches.len());
+        assert_eq!(Target::All, batches[0].target);
+        assert_eq!(num_coins, batches[0].message.len());
+        queue.extend(batches.into_iter().map(|batch| (*id, batch)));
+    }
+    while let Some((sender_id, batch)) = queue.pop() {
+        let ser_batch = bincode::serialize(&batch.message).expect("serialize batch");
+        for (id, coins) in &mut nodes {
+            if *id == sender_id {
+                continue;
+            }
+            let msgs: Vec<(usize, Message)> =
+                bincode::deserialize(&ser_batch).expect("deserialize batch");
+            // Split the batch and dispatch the shares to the coins they belong to.
+            for (i, coin) in coins.iter_mut().enumerate() {
+                let coin_msgs = msgs
+                    .iter()
+                    .filter(|(j, _)| i == *j)
+                    .map(|(_, msg)| msg.clone());
+                let step = coin
+                    .handle_message_batch(&sender_id, coin_msgs, &mut rng)
+                    .expect("handle batch");
+                assert!(step.fault_log.is_empty());
+                assert!(step.messages.is_empty());
+                for sig in step.output {
+                    assert!(outputs.insert((*id, i), sig).is_none());
+                }
+            }
+        }
+    }
+    assert_eq!(4 * num_coins, outputs.len());
+    for i in 0..num_coins {
+        let sig = &outputs[&(0, i)];
+        assert!((1..4).all(|id| outputs[&(id, i)] == *sig));
+        assert!(i == 0 || outputs[&(0, 0)] != *sig);
+    }
+}
+
+#[test]
+fn test_threshold_sign_missing_contributors() {
+    let (_, netinfos) = testing::setup(7, 2);
+    let doc = "Who is missing?";
+    let netinfo = Arc::new(netinfos[0].clone());
+    let mut ts = ThresholdSign::new_with_document(netinfo, doc).expect("document");
+    assert_eq!((0..7).collect::<Vec<_>>(), ts.missing_contributors());
+
+    // Each share removes its sender from the list, until the signature is output.
+    for id in (1..7).rev() {
+        let sks = netinfos[id].secret_key_share().expect("secret key share");
+        let step = ts
+            .handle_message(&id, Message::new(0, sks.sign(doc)))
+            .expect("share");
+        if ts.terminated() {
+            assert_eq!(1, step.output.len());
+            break;
+        }
+        assert_eq!((0..id).collect::<Vec<_>>(), ts.missing_contributors());
+    }
+    assert!(ts.terminated());
+    assert!(ts.missing_contributors().is_empty());
+}
+
+#[test]
+fn test_threshold_sign_publish_share() {
+    let (_, netinfos) = testing::setup(4, 1);
+    let doc = "Early bird";
+    let share_msg = |id: usize| {
+        let sks = netinfos[id].secret_key_share().expect("secret key share");
+        Message::new(0, sks.sign(doc))
+    };
+    let new_ts = || {
+        let netinfo = Arc::new(netinfos[0].clone());
+        ThresholdSign::new_with_document(netinfo, doc).expect("document")
+    };
+
+    // Publishing sends our share, but doesn't count it: One more share is not enough to output.
+    let mut ts = new_ts();
+    let step = ts.publish_share().expect("publish share");
+    assert!(step.output.is_empty());
+    assert_eq!(vec![Target::All.message(share_msg(0))], step.messages);
+    assert!(ts.publish_share().expect("publish share").is_empty());
+    let step = ts.handle_message(&1, share_msg(1)).expect("share");
+    assert!(step.is_empty());
+    assert_eq!(vec![0, 2, 3], ts.missing_contributors());
+    // Signing counts our share without sending it again, and completes the signature.
+    let step = ts.sign().expect("sign");
+    assert!(step.messages.is_empty());
+    assert_eq!(1, step.output.len());
+    assert!(ts.terminated());
+
+    // Without signing, we output once _f + 1_ other nodes' shares arrived.
+    let mut ts = new_ts();
+    assert_eq!(1, ts.publish_share().expect("publish share").messages.len());
+    assert!(ts
+        .handle_message(&1, share_msg(1))
+        .expect("share")
+        .is_empty());
+    let step = ts.handle_message(&2, share_msg(2)).expect("share");
+    assert!(step.messages.is_empty());
+    assert_eq!(1, step.output.len());
+}
+
+#[test]
+fn test_threshold_sign_verify_share_by_index() {
+    let (_, netinfos) = testing::setup(4, 1);
+    let netinfo = Arc::new(netinfos[0].clone());
+    let doc = "by index";
+    let mut ts = ThresholdSign::new(netinfo.clone());
+
+    // Without a document, shares cannot be verified.
+    let share = netinfos[1].secret_key_share().expect("share").sign(doc);
+    assert_eq!(
+        Err(threshold_sign::Error::DocumentHashIsNone),
+        ts.verify_share_by_index(1, &share)
+    );
+
+    // Verifying by index agrees with verifying against each sender's public key share.
+    ts.set_document(doc).expect("document");
+    for (id, sender_netinfo) in netinfos.iter().enumerate() {
+        let share = sender_netinfo.secret_key_share().expect("share").sign(doc);
+        for (other_id, pk_share) in netinfo.public_key_share_map() {
+            let idx = netinfo.node_index(other_id).expect("index") as u64;
+            let by_index = ts.verify_share_by_index(idx, &share).expect("verify");
+            assert_eq!(pk_share.verify(&share, doc), by_index);
+            assert_eq!(id == *other_id, by_index);
+        }
+        // There is no validator with index 4.
+        assert_eq!(Ok(false), ts.verify_share_by_index(4, &share));
+    }
+}
+
+/// A signer that returns a fixed share and records the documents it was asked to sign.
+#[derive(Debug)]
+struct MockSigner {
+    share: SignatureShare,
+    docs: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl ShareSigner for MockSigner {
+    fn sign(&self, doc: &[u8]) -> SignatureShare {
+        self.docs.lock().expect("docs").push(doc.to_vec());
+        self.share.clone()
+    }
+}
+
+#[test]
+fn test_threshold_sign_share_signer() {
+    let (_, netinfos) = testing::setup(4, 1);
+    let doc = "signed elsewhere";
+    let share = netinfos[0].secret_key_share().expect("share").sign(doc);
+
+    // Node 0's secret key share is kept out of its `NetworkInfo`, and only known to the signer.
+    let netinfo = &netinfos[0];
+    let netinfo = NetworkInfo::new(
+        0,
+        None,
+        netinfo.public_key_set().clone(),
+        netinfo.secret_key().clone(),
+        netinfo.public_key_map().clone(),
+    );
+    let docs = Arc::new(Mutex::new(Vec::new()));
+    let mut ts = ThresholdSign::new_with_document(Arc::new(netinfo), doc).expect("document");
+    ts.set_share_signer(MockSigner {
+        share: share.clone(),
+        docs: docs.clone(),
+    });
+
+    // The signer's share is multicast, and counts towards our output.
+    let step = ts.sign().expect("sign");
+    assert_eq!(
+        vec![Target::All.message(Message::new(0, share))],
+        step.messages
+    );
+    assert!(step.fault_log.is_empty());
+    assert_eq!(vec![doc.as_bytes().to_vec()], *docs.lock().expect("docs"));
+    let other_share = netinfos[1].secret_key_share().expect("share").sign(doc);
+    let step = ts
+        .handle_message(&1, Message::new(0, other_share))
+        .expect("share");
+    assert_eq!(1, step.output.len());
+}
+
+#[test]
+fn test_threshold_sign_wire_version() {
+    let (_, netinfos) = testing::setup(4, 1);
+    let share = netinfos[0]
+        .secret_key_share()
+        .expect("share")
+        .sign("versioned");
+    let msg = Message::new(0, share);
+
+    // The version is serialized as the first byte, followed by the round and the share.
+    let bytes = bincode::serialize(&msg).expect("serialize");
+    assert_eq!(Message::wire_version(), bytes[0]);
+    let decoded: Message = bincode::deserialize(&bytes).expect("deserialize");
+    assert_eq!(msg, decoded);
+
+    // A message from a node with a newer format is rejected with a clear error.
+    let mut bumped = bytes.clone();
+    bumped[0] = Message::wire_version() + 1;
+    let err = bincode::deserialize::<Message>(&bumped).expect_err("bumped version accepted");
+    assert!(err.to_string().contains("wire version"), "{}", err);
+}
+
+#[test]
+fn test_threshold_sign_own_message_ignored() {
+    let (_, netinfos) = testing::setup(4, 1);
+    let doc = "loopback";
+    let mut ts =
+        ThresholdSign::new_with_document(Arc::new(netinfos[0].clone()), doc).expect("document");
+    let step = ts.sign().expect("sign");
+    let own_msg = step.messages[0].message.clone();
+    let metrics = ts.metrics();
+
+    // Our own share, looped back by the transport, is ignored without a fault.
+    let step = ts.handle_message(&0, own_msg).expect("own share");
+    assert!(step.is_empty());
+    // Even an invalid share with our ID is ignored, since it can't be from us.
+    let wrong_share = netinfos[0].secret_key_share().expect("share").sign("other");
+    let step = ts
+        .handle_message(&0, Message::new(0, wrong_share))
+        .expect("own share");
+    assert!(step.is_empty());
+    assert_eq!(metrics, ts.metrics());
+    assert!(!ts.terminated());
+    assert!(!ts.missing_contributors().contains(&0));
+}
+
+#[test]
+fn test_threshold_sign_custom_message() {
+    let (pk_set, netinfos) = testing::setup(7, 2);
+    let checkpoint = b"checkpoint: epoch 42, state root 0x1234";
+    let mut nodes: Vec<_> = netinfos
+        .into_iter()
+        .map(|netinfo| ThresholdSign::new(Arc::new(netinfo)))
+        .collect();
+
+    // Every node signs the application's message. Its share is multicast to everyone else.
+    let mut queue = Vec::new();
+    for (id, ts) in nodes.iter_mut().enumerate() {
+        let step = ts.sign_document(&checkpoint[..]).expect("sign");
+        assert!(step.output.is_empty());
+        queue.extend(step.messages.into_iter().map(|tm| (id, tm.message)));
+    }
+    let mut outputs = BTreeMap::new();
+    for (sender_id, msg) in queue {
+        for (id, ts) in nodes.iter_mut().enumerate() {
+            if id == sender_id {
+                continue;
+            }
+            let step = ts.handle_message(&sender_id, msg.clone()).expect("share");
+            assert!(step.fault_log.is_empty());
+            if let Some(sig) = step.output.into_iter().next() {
+                assert!(outputs.insert(id, sig).is_none());
+            }
+        }
+    }
+
+    // All nodes output the same group signature, which is valid for the master public key.
+    assert_eq!(7, outputs.len());
+    let sig = outputs[&0].clone();
+    assert!(outputs.values().all(|s| *s == sig));
+    assert!(pk_set.public_key().verify(&sig, &checkpoint[..]));
+    assert!(!pk_set.public_key().verify(&sig, "another checkpoint"));
+
+    // The document can only be set once.
+    assert_eq!(
+        Err(threshold_sign::Error::MultipleMessagesToSign),
+        nodes[0].sign_document("another checkpoint").map(|_| ())
+    );
+}
+
+#[test]
+fn test_threshold_sign_late_shares() {
+    let (_, netinfos) = testing::setup(7, 2);
+    let doc = "Who was there?";
+    let share = |id: usize, doc: &str| {
+        Message::new(0, netinfos[id].secret_key_share().expect("share").sign(doc))
+    };
+    let mut ts =
+        ThresholdSign::new_with_document(Arc::new(netinfos[0].clone()), doc).expect("document");
+    ts.set_collect_late_shares(true);
+    let mut outputs = Vec::new();
+    for id in 1..4 {
+        let step = ts.handle_message(&id, share(id, doc)).expect("share");
+        outputs.extend(step.output);
+    }
+    assert!(ts.terminated());
+    assert_eq!(1, outputs.len());
+    let combining_set = ts.combining_set();
+    // We only sent our own share after computing the signature, so it counts as late.
+    let expected: BTreeSet<usize> = (0..4).collect();
+    assert_eq!(expected, ts.all_contributors());
+
+    // A valid late share is recorded, an invalid one is reported, and a repeated one is ignored.
+    let step = ts.handle_message(&4, share(4, doc)).expect("late share");
+    assert!(step.is_empty());
+    let step = ts
+        .handle_message(&5, share(5, "other"))
+        .expect("late share");
+    let expected_fault = Fault::new(5, FaultKind::UnverifiedSignatureShareSender);
+    assert_eq!(vec![expected_fault], step.fault_log.0);
+    assert!(step.output.is_empty());
+    let step = ts.handle_message(&4, share(4, doc)).expect("late share");
+    assert!(step.is_empty());
+    let expected: BTreeSet<usize> = (0..5).collect();
+    assert_eq!(expected, ts.all_contributors());
+
+    // The output is unchanged.
+    assert_eq!(Some(&outputs[0]), ts.output_signature());
+    assert_eq!(combining_set, ts.combining_set());
+
+    // Without the option, late shares are dropped.
+    let mut ts =
+        ThresholdSign::new_with_document(Arc::new(netinfos[0].clone()), doc).expect("document");
+    let mut outputs = Vec::new();
+    for id in 1..5 {
+        let step = ts.handle_message(&id, share(id, doc)).expect("share");
+        outputs.extend(step.output);
+    }
+    assert_eq!(1, outputs.len());
+    let step = ts
+        .handle_message(&5, share(5, "other"))
+        .expect("late share");
+    assert!(step.is_empty());
+    let expected: BTreeSet<usize> = (1..4).collect();
+    assert_eq!(expected, ts.all_contributors());
+}
+
+#[test]
+fn test_threshold_sign_at_most_one_message() {
+    let (_, netinfos) = testing::setup(4, 1);
+    let doc = "One share each";
+    let mut nodes: Vec<_> = netinfos
+        .into_iter()
+        .map(|netinfo| ThresholdSign::new_with_document(Arc::new(netinfo), doc).expect("doc"))
+        .collect();
+    let mut rng = XorShiftRng::from_seed([16; 16]);
+
+    // Every node publishes and signs repeatedly, and handles everyone else's messages.
+    let mut sent = vec![0; nodes.len()];
+    let mut queue = Vec::new();
+    for _ in 0..2 {
+        for (id, ts) in nodes.iter_mut().enumerate() {
+            let mut step = ts.publish_share().expect("publish");
+            step.extend(ts.sign().expect("sign"));
+            step.extend(ts.handle_input((), &mut rng).expect("input"));
+            sent[id] += step.messages.len();
+            queue.extend(step.messages.into_iter().map(|tm| (id, tm.message)));
+        }
+        for (sender_id, msg) in queue.drain(..) {
+            for (id, ts) in nodes.iter_mut().enumerate() {
+                if id != sender_id {
+                    let step = ts.handle_message(&sender_id, msg.clone()).expect("share");
+                    sent[id] += step.messages.len();
+                }
+            }
+        }
+    }
+    assert!(nodes.iter().all(ThresholdSign::terminated));
+    assert_eq!(vec![1; 4], sent);
+}
+
+#[test]
+fn test_threshold_sign_preview_input() {
+    let mut rng = XorShiftRng::from_seed([17; 16]);
+    let (_, netinfos) = testing::setup(4, 1);
+    let doc = "Dry run";
+    let mut ts = ThresholdSign::new(Arc::new(netinfos[0].clone()));
+    assert_eq!(
+        Err(threshold_sign::Error::DocumentHashIsNone),
+        ts.preview_input()
+    );
+    ts.set_document(doc).expect("document");
+
+    // Previewing doesn't change the state, and predicts the message sent on input.
+    let preview = ts.preview_input().expect("preview");
+    assert_eq!(1, preview.len());
+    assert_eq!(preview, ts.preview_input().expect("preview"));
+    assert_eq!(AlgoMetrics::default(), ts.metrics());
+    let step = ts.handle_input((), &mut rng).expect("input");
+    let sent: Vec<_> = step.messages.into_iter().map(|tm| tm.message).collect();
+    assert_eq!(preview, sent);
+    // Once the share was sent, there is nothing more to send.
+    assert!(ts.preview_input().expect("preview").is_empty());
+
+    // After `publish_share`, the input doesn't send the share again.
+    let mut ts =
+        ThresholdSign::new_with_document(Arc::new(netinfos[1].clone()), doc).expect("document");
+    let step = ts.publish_share().expect("publish");
+    assert_eq!(1, step.messages.len());
+    assert!(ts.preview_input().expect("preview").is_empty());
+    assert!(ts
+        .handle_input((), &mut rng)
+        .expect("input")
+        .messages
+        .is_empty());
+}
+
+#[test]
+fn test_threshold_sign_malformed_share() {
+    use hbbft::crypto::{G2Affine, SIG_SIZE};
+    use hbbft::pairing::CurveAffine;
+
+    let (_, netinfos) = testing::setup(4, 1);
+    let doc = "Malformed";
+    // The identity element deserializes, but is never a valid share.
+    let mut bytes = [0u8; SIG_SIZE];
+    bytes.copy_from_slice(G2Affine::zero().into_compressed().as_ref());
+    let identity = SignatureShare::from_bytes(bytes).expect("identity share");
+    let valid = netinfos[1].secret_key_share().expect("share").sign(doc);
+    assert!(!crypto_util::is_well_formed(&identity));
+    assert!(crypto_util::is_well_formed(&valid));
+
+    // The share is rejected even before the document is known, when verification is postponed,
+    // so it is the malformed check and not the verification that rejects it.
+    let mut ts = ThresholdSign::new(Arc::new(netinfos[0].clone()));
+    let step = ts
+        .handle_message(&1, Message::new(0, identity.clone()))
+        .expect("share");
+    let expected_fault = Fault::new(1, FaultKind::MalformedSignatureShare);
+    assert_eq!(vec![expected_fault.clone()], step.fault_log.0);
+    assert!(ts.missing_contributors().contains(&1));
+
+    // The sender can't use it to prevent a signature, either.
+    ts.set_document(doc).expect("document");
+    let step = ts.sign().expect("sign");
+    assert!(step.output.is_empty());
+    let step = ts
+        .handle_message(&1, Message::new(0, identity))
+        .expect("share");
+    assert_eq!(vec![expected_fault], step.fault_log.0);
+    let step = ts
+        .handle_message(&1, Message::new(0, valid))
+        .expect("share");
+    assert_eq!(1, step.output.len());
+}